@@ -0,0 +1,301 @@
+use crate::btree;
+use crate::fractal_heap::FractalHeap;
+use crate::storage::Contents;
+use crate::{parse, resolve_shared, shared_message_body, SharedMessageIndex};
+use crate::{Dataspace, Datatype, Error, FromHdf5, Hdf5Dtype, Hdf5File};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+/// An attribute as its message stores it, whose type and dataspace are resolved and decoded when
+/// it is first read, so that an attribute whose type or dataspace cannot be read does not stop its
+/// object or the other attributes loading
+#[derive(Debug)]
+pub(crate) struct Attribute {
+    parsed: parse::header::Attribute,
+    decoded: OnceLock<Decoded>,
+}
+
+#[derive(Debug)]
+struct Decoded {
+    dtype: Datatype,
+    dataspace: Dataspace,
+}
+
+impl Attribute {
+    /// Keep an attribute from its message, whose type and dataspace may still be shared
+    pub(crate) fn from(parsed: parse::header::Attribute) -> Self {
+        Self {
+            parsed,
+            decoded: OnceLock::new(),
+        }
+    }
+
+    /// The attribute's type and dataspace, once the shared messages that they may be kept in have
+    /// been resolved
+    fn decoded(&self, file: &Hdf5File) -> Result<&Decoded, Error> {
+        if let Some(decoded) = self.decoded.get() {
+            return Ok(decoded);
+        }
+        let mut datatype = self.parsed.datatype.clone();
+        resolve_shared(
+            &file.contents,
+            &file.shared,
+            0x3,
+            &mut datatype,
+            |m| match m {
+                parse::header::Message::DataType(m) => Some(m),
+                _ => None,
+            },
+        )?;
+        let mut dataspace = self.parsed.dataspace.clone();
+        resolve_shared(
+            &file.contents,
+            &file.shared,
+            0x1,
+            &mut dataspace,
+            |m| match m {
+                parse::header::Message::Dataspace(m) => Some(m),
+                _ => None,
+            },
+        )?;
+        let unresolved = || Error::Unsupported {
+            feature: format!(
+                "shared messages that attribute {:?} refers to",
+                self.parsed.name
+            ),
+        };
+        let decoded = Decoded {
+            dtype: Datatype::from(datatype.here().ok_or_else(unresolved)?)?,
+            dataspace: Dataspace::from(dataspace.here().ok_or_else(unresolved)?),
+        };
+        Ok(self.decoded.get_or_init(|| decoded))
+    }
+}
+
+/// The attributes of a group, dataset or committed datatype, which are only loaded when they are
+/// first read
+///
+/// Attributes in dense storage take a walk of a B-tree and a fractal heap to load, so objects with
+/// many of them still open quickly, and an object whose attribute storage is damaged can still be
+/// opened and read.
+#[derive(Debug)]
+pub(crate) struct ObjectAttributes {
+    /// The attribute messages in the object header, as they are stored
+    compact: Vec<parse::header::Attribute>,
+    /// Where the attributes in dense storage are, if the object has any
+    info: Option<parse::header::AttributeInfo>,
+    loaded: OnceLock<BTreeMap<String, Attribute>>,
+}
+
+impl ObjectAttributes {
+    pub(crate) const fn new(
+        compact: Vec<parse::header::Attribute>,
+        info: Option<parse::header::AttributeInfo>,
+    ) -> Self {
+        Self {
+            compact,
+            info,
+            loaded: OnceLock::new(),
+        }
+    }
+
+    /// The attributes by name, including those in dense storage
+    fn load(&self, file: &Hdf5File) -> Result<&BTreeMap<String, Attribute>, Error> {
+        if let Some(attributes) = self.loaded.get() {
+            return Ok(attributes);
+        }
+        let mut parsed = self.compact.clone();
+        if let Some(info) = &self.info {
+            parsed.extend(dense_attributes(&file.contents, &file.shared, info)?);
+        }
+        let attributes = parsed
+            .into_iter()
+            .map(|attribute| (attribute.name.clone(), Attribute::from(attribute)))
+            .collect();
+        Ok(self.loaded.get_or_init(|| attributes))
+    }
+}
+
+/// Load the attributes that an object keeps in dense storage, which is described by its
+/// attribute info message
+fn dense_attributes(
+    contents: &Contents,
+    shared: &[SharedMessageIndex],
+    info: &parse::header::AttributeInfo,
+) -> Result<Vec<parse::header::Attribute>, Error> {
+    use parse::header::Message;
+    if info.fractal_heap_address == parse::UNDEFINED_ADDRESS {
+        return Ok(Vec::new());
+    }
+    let heap = FractalHeap::read(contents, info.fractal_heap_address)?;
+    let mut attributes = Vec::new();
+    for record in btree::records(
+        contents,
+        info.name_btree_address,
+        btree::ATTRIBUTE_NAME_RECORD,
+    )? {
+        // Each record holds a heap ID, message flags, a creation order and a hash of the name
+        if record.len() < 17 {
+            return Err(Error::parse("attribute name record is too short"));
+        }
+        // The heap ID of a shared attribute is in the shared message heap instead
+        let (id, flags) = (&record[..8], record[8]);
+        let body = if flags & 0b10 != 0 {
+            shared_message_body(contents, shared, 0xC, id)?
+        } else {
+            heap.object(id)?
+        };
+        if let (_, Message::Attribute(attribute)) =
+            parse::message(body, 0xC).map_err(|e| Error::nom(contents, e))?
+        {
+            attributes.push(attribute);
+        }
+    }
+    Ok(attributes)
+}
+
+/// The attributes attached to a group, dataset or committed datatype
+///
+/// The attributes are loaded when one of them is first looked up, and an error in loading them is
+/// returned from each lookup.
+#[derive(Clone, Copy)]
+pub struct Attributes<'a> {
+    file: &'a Hdf5File,
+    attributes: &'a ObjectAttributes,
+}
+
+impl<'a> Attributes<'a> {
+    pub(crate) fn new(file: &'a Hdf5File, attributes: &'a ObjectAttributes) -> Self {
+        Self { file, attributes }
+    }
+
+    fn loaded(&self) -> Result<&'a BTreeMap<String, Attribute>, Error> {
+        self.attributes.load(self.file)
+    }
+
+    /// The named attribute, with its type and dataspace decoded
+    fn find(&self, name: &str) -> Result<(&'a Attribute, &'a Decoded), Error> {
+        let attribute = self.loaded()?.get(name).ok_or_else(|| Error::NotFound {
+            path: name.to_string(),
+        })?;
+        Ok((attribute, attribute.decoded(self.file)?))
+    }
+
+    /// Look up the named attribute and, if its type is compatible and it holds exactly one
+    /// element, return a copy of that element
+    pub fn get<T: FromHdf5>(&self, name: &str) -> Result<T, Error> {
+        let (attribute, decoded) = self.find(name)?;
+        let elements = decoded.dataspace.num_elements();
+        if elements != 1 {
+            return Err(Error::NotScalar { elements });
+        }
+        Ok(self.convert(attribute, decoded)?.remove(0))
+    }
+
+    /// Look up the named attribute and, if its type is compatible, return a copy of all its
+    /// elements in row-major order
+    pub fn get_vec<T: FromHdf5>(&self, name: &str) -> Result<Vec<T>, Error> {
+        let (attribute, decoded) = self.find(name)?;
+        self.convert(attribute, decoded)
+    }
+
+    fn convert<T: FromHdf5>(
+        &self,
+        attribute: &Attribute,
+        decoded: &Decoded,
+    ) -> Result<Vec<T>, Error> {
+        let dtype = &decoded.dtype;
+        if !T::from_types().contains(&dtype.class) {
+            return Err(Error::TypeMismatch {
+                expected: T::from_types(),
+                found: dtype.class,
+            });
+        }
+        let len = decoded.dataspace.byte_size(dtype.size)?;
+        let stored = &attribute.parsed.data;
+        let data = stored.get(..len).ok_or_else(|| {
+            Error::parse(format!(
+                "attribute needs {} bytes but only {} are stored",
+                len,
+                stored.len()
+            ))
+        })?;
+        self.file
+            .options
+            .limits
+            .check_allocation(len / dtype.size * std::mem::size_of::<T>())?;
+        dtype
+            .to_native(Cow::Borrowed(data))
+            .chunks_exact(dtype.size)
+            .map(|element| T::convert(self.file, dtype.class, element))
+            .collect()
+    }
+
+    /// Iterate over the name, type and dataspace of each attribute, sorted by name, where an
+    /// attribute whose type or dataspace cannot be decoded is produced as an error in its place
+    ///
+    /// If the attributes cannot be loaded, that error is the only item.
+    pub fn iter(&self) -> impl Iterator<Item = Result<(&'a str, Hdf5Dtype, &'a Dataspace), Error>> {
+        let file = self.file;
+        let (attributes, error) = match self.loaded() {
+            Ok(attributes) => (Some(attributes), None),
+            Err(e) => (None, Some(e)),
+        };
+        error
+            .map(Err)
+            .into_iter()
+            .chain(
+                attributes
+                    .into_iter()
+                    .flatten()
+                    .map(move |(name, attribute)| {
+                        let decoded = attribute.decoded(file)?;
+                        Ok((name.as_str(), decoded.dtype.class, &decoded.dataspace))
+                    }),
+            )
+    }
+
+    /// The type of the named attribute
+    pub fn dtype(&self, name: &str) -> Result<Hdf5Dtype, Error> {
+        Ok(self.find(name)?.1.dtype.class)
+    }
+
+    /// The shape of the named attribute
+    pub fn dataspace(&self, name: &str) -> Result<&'a Dataspace, Error> {
+        Ok(&self.find(name)?.1.dataspace)
+    }
+
+    /// Whether an attribute with this name exists
+    pub fn contains(&self, name: &str) -> Result<bool, Error> {
+        Ok(self.loaded()?.contains_key(name))
+    }
+
+    /// The number of attributes
+    pub fn len(&self) -> Result<usize, Error> {
+        Ok(self.loaded()?.len())
+    }
+
+    /// Whether there are no attributes
+    pub fn is_empty(&self) -> Result<bool, Error> {
+        Ok(self.loaded()?.is_empty())
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl<'a> Attributes<'a> {
+    /// Look up the named attribute and, if its type is compatible, return a copy of its data in
+    /// an array that has the shape of its dataspace
+    ///
+    /// Use `IxDyn` as the dimension to accept attributes of any rank.
+    pub fn get_array<T: FromHdf5, D: ndarray::Dimension>(
+        &self,
+        name: &str,
+    ) -> Result<ndarray::Array<T, D>, Error> {
+        let (attribute, decoded) = self.find(name)?;
+        let shape = decoded.dataspace.array_shape::<D>()?;
+        ndarray::ArrayD::from_shape_vec(shape, self.convert(attribute, decoded)?)
+            .and_then(|array| array.into_dimensionality())
+            .map_err(|e| Error::parse(e.to_string()))
+    }
+}
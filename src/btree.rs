@@ -0,0 +1,99 @@
+use crate::storage::Contents;
+use crate::{parse, Error};
+
+/// The types of record that can be stored in a version 2 B-tree
+pub(crate) const LINK_NAME_RECORD: u8 = 5;
+pub(crate) const ATTRIBUTE_NAME_RECORD: u8 = 8;
+pub(crate) const CHUNK_RECORD: u8 = 10;
+pub(crate) const FILTERED_CHUNK_RECORD: u8 = 11;
+
+/// Collect the records from every node of the version 2 B-tree at `address`, which must hold
+/// records of type `ty`
+pub(crate) fn records(contents: &Contents, address: u64, ty: u8) -> Result<Vec<&[u8]>, Error> {
+    let header = parse::btree_v2_header(contents.bytes_from(address)?)
+        .map_err(|e| Error::nom(contents, e))?
+        .1;
+    if header.ty != ty {
+        return Err(Error::parse(format!(
+            "expected a B-tree of type {} but found type {}",
+            ty, header.ty
+        )));
+    }
+    let record_size = usize::from(header.record_size);
+    if record_size == 0 || header.node_size < 10 {
+        return Err(Error::parse("B-tree nodes are too small"));
+    }
+
+    // The number of bytes that store a count of records depends on the most that can fit below
+    // a node at each depth
+    let leaf_records = (header.node_size as u64 - 10) / record_size as u64;
+    let records_size = enc_size(leaf_records);
+    let mut total_records_sizes = vec![0];
+    let mut max_total_records = leaf_records;
+    for depth in 1..=usize::from(header.depth) {
+        let pointer_size = 8
+            + records_size
+            + if depth > 1 {
+                total_records_sizes[depth - 1]
+            } else {
+                0
+            };
+        let max_records = (header.node_size as u64).saturating_sub(10 + pointer_size as u64)
+            / (record_size + pointer_size) as u64;
+        max_total_records = (max_records + 1)
+            .saturating_mul(max_total_records)
+            .saturating_add(max_records);
+        total_records_sizes.push(enc_size(max_total_records));
+    }
+
+    // The record count comes from the file, so it only bounds the search rather than sizing it
+    let mut records = Vec::new();
+    let mut nodes = vec![(
+        header.root_address,
+        header.root_records as u64,
+        header.depth,
+    )];
+    while let Some((address, count, depth)) = nodes.pop() {
+        let count = count as u16;
+        let input = contents.bytes_from(address)?;
+        if depth == 0 {
+            records.extend(
+                parse::btree_v2_leaf(input, header.record_size, count)
+                    .map_err(|e| Error::nom(contents, e))?
+                    .1,
+            );
+        } else {
+            let total_records_size = if depth > 1 {
+                total_records_sizes[usize::from(depth) - 1]
+            } else {
+                0
+            };
+            let (node_records, children) = parse::btree_v2_internal(
+                input,
+                header.record_size,
+                count,
+                records_size,
+                total_records_size,
+            )
+            .map_err(|e| Error::nom(contents, e))?
+            .1;
+            records.extend(node_records);
+            nodes.extend(
+                children
+                    .into_iter()
+                    .map(|child| (child.address, child.records, depth - 1)),
+            );
+        }
+        if records.len() as u64 > header.total_records {
+            return Err(Error::parse(
+                "B-tree holds more records than its header says",
+            ));
+        }
+    }
+    Ok(records)
+}
+
+/// The number of bytes needed to store numbers up to `n`
+fn enc_size(n: u64) -> usize {
+    (63 - n.max(1).leading_zeros()) as usize / 8 + 1
+}
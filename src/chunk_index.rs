@@ -0,0 +1,583 @@
+use crate::dataset::{DatasetHandle, Layout};
+use crate::parse::header::ChunkIndex;
+use crate::parse::{self, ChunkEntry, IndexedChunk, UNDEFINED_ADDRESS};
+use crate::selection::for_each_index;
+use crate::{btree, Error, Hdf5File};
+use std::convert::TryFrom;
+
+/// The chunks of a dataset that have been written, in no particular order
+///
+/// The offsets of each entry are those of its first element, followed by a zero for the dimension
+/// of the element's bytes as in the entries of a version 1 B-tree. Datasets that are not chunked
+/// have no chunks.
+pub(crate) fn entries(dataset: &DatasetHandle<'_>) -> Result<Vec<ChunkEntry>, Error> {
+    let (index, chunk_shape, unfiltered_edges) = match dataset.layout() {
+        Layout::Chunked {
+            index,
+            chunk_shape,
+            unfiltered_edges,
+        } => (index, chunk_shape, *unfiltered_edges),
+        _ => return Ok(Vec::new()),
+    };
+    let file = dataset.file();
+    let rank = chunk_shape.len();
+    let filtered = !dataset.filters().is_empty();
+    let grid = Grid::new(dataset.dataspace().max_dims(), chunk_shape);
+    // The size of a chunk that is stored without filters
+    let chunk_size = dataset.datatype().size as u64 * chunk_shape.iter().product::<u64>();
+
+    // Arrays number their chunks in row-major order over the grid of chunks, and B-trees keep
+    // the position of each chunk in the grid
+    let (scaled, chunks) = match *index {
+        ChunkIndex::BTreeV1 { address } => return btree_v1(file, address, rank as u8 + 1),
+        ChunkIndex::Single { address, .. } | ChunkIndex::Implicit { address }
+            if address == UNDEFINED_ADDRESS =>
+        {
+            return Ok(Vec::new())
+        }
+        ChunkIndex::Single {
+            address,
+            filtered: single,
+        } => (
+            vec![vec![0; rank]],
+            vec![IndexedChunk {
+                address,
+                filtered: single,
+            }],
+        ),
+        ChunkIndex::Implicit { address } => {
+            implicit(file, address, chunk_size, &grid, dataset.shape())?
+        }
+        ChunkIndex::FixedArray { address } => {
+            let elements = fixed_array(file, address, filtered, grid.len()?)?;
+            grid.scale(elements, 0)?
+        }
+        ChunkIndex::ExtensibleArray { address } => {
+            // The unlimited dimension is numbered first
+            let unlimited = grid.unlimited().ok_or_else(|| {
+                Error::parse("an extensible array indexes a dataset with no unlimited dimension")
+            })?;
+            let elements = extensible_array(file, address, filtered)?;
+            grid.scale(elements, unlimited)?
+        }
+        ChunkIndex::BTreeV2 { address } => btree_v2(file, address, rank, filtered)?,
+    };
+
+    scaled
+        .into_iter()
+        .zip(chunks)
+        .filter(|(_, chunk)| chunk.address != UNDEFINED_ADDRESS)
+        .map(|(scaled, chunk)| {
+            let mut offsets = scaled
+                .iter()
+                .zip(chunk_shape)
+                .map(|(i, len)| i.checked_mul(*len))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| Error::parse(format!("chunk {:?} is too far out", scaled)))?;
+            let (chunk_size, mut filter_mask) = chunk.filtered.unwrap_or((chunk_size, 0));
+            // Chunks that only partly lie inside the dataset may be kept without their filters
+            let partial = offsets
+                .iter()
+                .zip(chunk_shape)
+                .zip(dataset.shape())
+                .any(|((offset, len), size)| offset.saturating_add(*len) > *size);
+            if unfiltered_edges && partial {
+                filter_mask = u32::MAX;
+            }
+            offsets.push(0);
+            Ok(ChunkEntry {
+                chunk_size,
+                filter_mask,
+                offsets,
+                address: chunk.address,
+            })
+        })
+        .collect()
+}
+
+/// The grid of chunks over the maximum shape of a dataset
+struct Grid<'a> {
+    /// How many chunks fit along each dimension, where `None` means that the dimension is
+    /// unlimited
+    lens: Vec<Option<u64>>,
+    chunk_shape: &'a [u64],
+}
+
+impl<'a> Grid<'a> {
+    fn new(max_shape: &[Option<u64>], chunk_shape: &'a [u64]) -> Self {
+        let lens = max_shape
+            .iter()
+            .zip(chunk_shape)
+            .map(|(max, len)| max.map(|max| max.div_ceil(*len)))
+            .collect();
+        Self { lens, chunk_shape }
+    }
+
+    fn unlimited(&self) -> Option<usize> {
+        self.lens.iter().position(Option::is_none)
+    }
+
+    fn too_many(&self) -> Error {
+        Error::parse(format!(
+            "chunks of shape {:?} cannot be numbered over the maximum shape of their dataset",
+            self.chunk_shape
+        ))
+    }
+
+    /// The number of chunks in the grid, which must have no unlimited dimension
+    fn len(&self) -> Result<u64, Error> {
+        self.lens
+            .iter()
+            .try_fold(1u64, |n, len| n.checked_mul((*len)?))
+            .ok_or_else(|| self.too_many())
+    }
+
+    /// How far apart the numbers of consecutive chunks are along each dimension, when chunks are
+    /// numbered in row-major order after dimension `first` is moved to the front
+    ///
+    /// The first dimension is the only one that may be unlimited.
+    fn down_chunks(&self, first: usize) -> Result<Vec<u64>, Error> {
+        let mut down = vec![0; self.lens.len()];
+        let mut n = 1u64;
+        for d in (0..self.lens.len()).rev().filter(|d| *d != first) {
+            down[d] = n;
+            n = self.lens[d]
+                .and_then(|len| n.checked_mul(len))
+                .filter(|n| *n != 0)
+                .ok_or_else(|| self.too_many())?;
+        }
+        if let Some(first) = down.get_mut(first) {
+            *first = n;
+        }
+        Ok(down)
+    }
+
+    /// The position in the grid of each chunk, from its number when chunks are numbered with
+    /// dimension `first` moved to the front
+    fn scale(
+        &self,
+        chunks: Vec<(u64, IndexedChunk)>,
+        first: usize,
+    ) -> Result<(Vec<Vec<u64>>, Vec<IndexedChunk>), Error> {
+        let down = self.down_chunks(first)?;
+        let order = std::iter::once(first)
+            .chain((0..down.len()).filter(|d| *d != first))
+            .collect::<Vec<_>>();
+        Ok(chunks
+            .into_iter()
+            .map(|(number, chunk)| {
+                let mut scaled = vec![0; down.len()];
+                let mut rest = number;
+                for d in &order {
+                    scaled[*d] = rest / down[*d];
+                    rest %= down[*d];
+                }
+                (scaled, chunk)
+            })
+            .unzip())
+    }
+}
+
+/// Walk a chunk B-tree and collect the entries of all its leaves
+fn btree_v1(file: &Hdf5File, address: u64, dimensionality: u8) -> Result<Vec<ChunkEntry>, Error> {
+    // The B-tree is only made when the first chunk is written
+    if address == UNDEFINED_ADDRESS {
+        return Ok(Vec::new());
+    }
+    btree_v1_below(file, address, dimensionality, None)
+}
+
+/// Collect the leaf entries below the chunk B-tree node at `address`, which must be at a lower
+/// level than its parent's `parent_level` so that the walk always ends
+fn btree_v1_below(
+    file: &Hdf5File,
+    address: u64,
+    dimensionality: u8,
+    parent_level: Option<u8>,
+) -> Result<Vec<ChunkEntry>, Error> {
+    let node = parse::chunk_node(file.bytes_from(address)?, dimensionality, 8)
+        .map_err(|e| Error::nom(file.contents(), e))?
+        .1;
+    if parent_level.is_some_and(|level| node.node_level >= level) {
+        return Err(Error::parse(format!(
+            "chunk B-tree node at {:#x} is not below its parent",
+            address
+        )));
+    }
+    if node.node_level == 0 {
+        return Ok(node.entries);
+    }
+    let mut entries = Vec::new();
+    for child in node.entries {
+        entries.extend(btree_v1_below(
+            file,
+            child.address,
+            dimensionality,
+            Some(node.node_level),
+        )?);
+    }
+    Ok(entries)
+}
+
+/// The chunks of a version 2 B-tree and their positions in the grid of chunks
+fn btree_v2(
+    file: &Hdf5File,
+    address: u64,
+    rank: usize,
+    filtered: bool,
+) -> Result<(Vec<Vec<u64>>, Vec<IndexedChunk>), Error> {
+    if address == UNDEFINED_ADDRESS {
+        return Ok((Vec::new(), Vec::new()));
+    }
+    let ty = if filtered {
+        btree::FILTERED_CHUNK_RECORD
+    } else {
+        btree::CHUNK_RECORD
+    };
+    let mut scaled = Vec::new();
+    let mut chunks = Vec::new();
+    for record in btree::records(file.contents(), address, ty)? {
+        let (chunk, position) = parse::chunk_record(record, rank, filtered)
+            .map_err(|e| Error::nom(file.contents(), e))?
+            .1;
+        scaled.push(position);
+        chunks.push(chunk);
+    }
+    Ok((scaled, chunks))
+}
+
+/// The chunks that are stored one after another from `address`, in row-major order over the grid
+/// of chunks, of which only those that hold part of a dataset of `shape` are kept
+fn implicit(
+    file: &Hdf5File,
+    address: u64,
+    chunk_size: u64,
+    grid: &Grid<'_>,
+    shape: &[u64],
+) -> Result<(Vec<Vec<u64>>, Vec<IndexedChunk>), Error> {
+    let past_end = || Error::OutOfBounds { address };
+    let down = grid.down_chunks(0)?;
+    // Every chunk is allocated along with the first, so the last one must be in the file, which
+    // bounds how many there can be
+    let total = grid.len()?;
+    let last = total
+        .checked_sub(1)
+        .and_then(|last| last.checked_mul(chunk_size))
+        .and_then(|last| last.checked_add(address))
+        .ok_or_else(past_end)?;
+    file.bytes(last, chunk_size)?;
+
+    let lens = shape
+        .iter()
+        .zip(grid.chunk_shape)
+        .map(|(size, len)| usize::try_from(size.div_ceil(*len)))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| past_end())?;
+    // Each chunk takes its position in the grid and where it is
+    let entry_size = std::mem::size_of::<IndexedChunk>() + lens.len() * std::mem::size_of::<u64>();
+    file.options.limits.check_allocation(
+        lens.iter()
+            .try_fold(entry_size, |size, len| size.checked_mul(*len))
+            .unwrap_or(usize::MAX),
+    )?;
+    let mut scaled = Vec::new();
+    let mut chunks = Vec::new();
+    for_each_index(&lens, |index| {
+        let position = index.iter().map(|i| *i as u64).collect::<Vec<_>>();
+        // A dataset that is larger than its maximum shape has chunks past the last one
+        let number = position
+            .iter()
+            .zip(&down)
+            .try_fold(0u64, |number, (i, down)| {
+                number.checked_add(i.checked_mul(*down)?)
+            })
+            .filter(|number| *number < total)
+            .ok_or_else(past_end)?;
+        let chunk_address = number
+            .checked_mul(chunk_size)
+            .and_then(|offset| offset.checked_add(address))
+            .ok_or_else(past_end)?;
+        chunks.push(IndexedChunk {
+            address: chunk_address,
+            filtered: None,
+        });
+        scaled.push(position);
+        Ok(())
+    })?;
+    Ok((scaled, chunks))
+}
+
+/// Whether bit `i` of a page bitmap is set, counting from the most significant bit of each byte
+fn page_initialized(bitmap: &[u8], i: usize) -> bool {
+    bitmap
+        .get(i / 8)
+        .is_some_and(|byte| byte & (0x80 >> (i % 8)) != 0)
+}
+
+/// Check that the elements of a fixed or extensible array are `size` bytes, which must hold a
+/// chunk's address, and for filtered chunks a size of up to eight bytes and a filter mask
+fn check_element_size(address: u64, size: u8, filtered: bool) -> Result<(), Error> {
+    let expected = if filtered { 13..=20 } else { 8..=8 };
+    if !expected.contains(&size) {
+        return Err(Error::parse(format!(
+            "chunk index at {:#x} has elements of {} bytes",
+            address, size
+        )));
+    }
+    Ok(())
+}
+
+/// Parse the chunks in consecutive elements of a fixed or extensible array, numbering them from
+/// `first`
+fn array_elements(
+    file: &Hdf5File,
+    elements: &[u8],
+    element_size: usize,
+    first: u64,
+    filtered: bool,
+    chunks: &mut Vec<(u64, IndexedChunk)>,
+) -> Result<(), Error> {
+    for (number, element) in (first..).zip(elements.chunks_exact(element_size)) {
+        let chunk = parse::chunk_element(element, filtered)
+            .map_err(|e| Error::nom(file.contents(), e))?
+            .1;
+        chunks.push((number, chunk));
+    }
+    Ok(())
+}
+
+/// The chunks in a fixed array of `len` elements, by their number in the grid of chunks
+fn fixed_array(
+    file: &Hdf5File,
+    address: u64,
+    filtered: bool,
+    len: u64,
+) -> Result<Vec<(u64, IndexedChunk)>, Error> {
+    let header = parse::fixed_array_header(file.bytes_from(address)?)
+        .map_err(|e| Error::nom(file.contents(), e))?
+        .1;
+    if header.data_block_address == UNDEFINED_ADDRESS {
+        return Ok(Vec::new());
+    }
+    if header.elements != len || header.page_bits >= 64 {
+        return Err(Error::parse(format!(
+            "fixed array at {:#x} of {} elements does not index {} chunks",
+            address, header.elements, len
+        )));
+    }
+    check_element_size(address, header.element_size, filtered)?;
+    let element_size = usize::from(header.element_size);
+    let too_large = || Error::OutOfBounds {
+        address: header.data_block_address,
+    };
+
+    let mut chunks = Vec::new();
+    let page_len = 1u64 << header.page_bits;
+    if len <= page_len {
+        let size = usize::try_from(len)
+            .ok()
+            .and_then(|len| len.checked_mul(element_size))
+            .ok_or_else(too_large)?;
+        let (elements, _) =
+            parse::fixed_array_data_block(file.bytes_from(header.data_block_address)?, 0)
+                .map_err(|e| Error::nom(file.contents(), e))?;
+        let elements = elements.get(..size).ok_or_else(too_large)?;
+        array_elements(file, elements, element_size, 0, filtered, &mut chunks)?;
+        return Ok(chunks);
+    }
+
+    // Only the pages that have been written are in the file, and they come after the checksum
+    // that ends the rest of the block
+    let pages = usize::try_from(len.div_ceil(page_len)).map_err(|_| too_large())?;
+    let bitmap_size = pages.div_ceil(8);
+    let bitmap =
+        parse::fixed_array_data_block(file.bytes_from(header.data_block_address)?, bitmap_size)
+            .map_err(|e| Error::nom(file.contents(), e))?
+            .1;
+    let prefix = (4 + 1 + 1 + 8 + bitmap_size + 4) as u64;
+    let page_size = page_len
+        .checked_mul(element_size as u64)
+        .ok_or_else(too_large)?;
+    for page in (0..pages).filter(|page| page_initialized(bitmap, *page)) {
+        let first = page as u64 * page_len;
+        let page_address = (page as u64)
+            .checked_mul(page_size.saturating_add(4))
+            .and_then(|offset| offset.checked_add(prefix))
+            .and_then(|offset| offset.checked_add(header.data_block_address))
+            .ok_or_else(too_large)?;
+        // The last page only holds the elements that are left
+        let size = std::cmp::min(page_len, len - first) * element_size as u64;
+        let elements = file.bytes(page_address, size)?;
+        array_elements(file, &elements, element_size, first, filtered, &mut chunks)?;
+    }
+    Ok(chunks)
+}
+
+/// The chunks in an extensible array, by their number in the grid of chunks
+///
+/// The elements of an extensible array are kept first in its index block, and then in data
+/// blocks that double in size every other super block. The first super blocks are small enough
+/// that the index block holds the addresses of their data blocks itself.
+fn extensible_array(
+    file: &Hdf5File,
+    address: u64,
+    filtered: bool,
+) -> Result<Vec<(u64, IndexedChunk)>, Error> {
+    let header = parse::extensible_array_header(file.bytes_from(address)?)
+        .map_err(|e| Error::nom(file.contents(), e))?
+        .1;
+    if header.index_block_address == UNDEFINED_ADDRESS {
+        return Ok(Vec::new());
+    }
+    let malformed = || {
+        Error::parse(format!(
+            "extensible array at {:#x} has malformed parameters",
+            address
+        ))
+    };
+    let min_elements = header.data_block_min_elements;
+    let min_data_blocks = header.super_block_min_data_blocks;
+    if !min_elements.is_power_of_two()
+        || !min_data_blocks.is_power_of_two()
+        || header.max_elements_bits > 64
+        || u32::from(header.max_elements_bits) < min_elements.trailing_zeros()
+        || header.page_bits >= 64
+    {
+        return Err(malformed());
+    }
+    check_element_size(address, header.element_size, filtered)?;
+    let element_size = usize::from(header.element_size);
+    let super_blocks =
+        1 + usize::from(header.max_elements_bits) - min_elements.trailing_zeros() as usize;
+    let direct_super_blocks = 2 * min_data_blocks.trailing_zeros() as usize;
+    let indirect_super_blocks = super_blocks
+        .checked_sub(direct_super_blocks)
+        .ok_or_else(malformed)?;
+    let block_offset_size = usize::from(header.max_elements_bits).div_ceil(8);
+    let page_len = 1u64 << header.page_bits;
+
+    let index_block = parse::extensible_array_index_block(
+        file.bytes_from(header.index_block_address)?,
+        usize::from(header.index_block_elements) * element_size,
+        2 * (usize::from(min_data_blocks) - 1),
+        indirect_super_blocks,
+    )
+    .map_err(|e| Error::nom(file.contents(), e))?
+    .1;
+    let mut chunks = Vec::new();
+    array_elements(
+        file,
+        index_block.elements,
+        element_size,
+        0,
+        filtered,
+        &mut chunks,
+    )?;
+
+    let mut first = u64::from(header.index_block_elements);
+    let mut first_data_block = 0;
+    for super_block in 0..super_blocks {
+        if first >= header.max_index_set {
+            break;
+        }
+        let data_blocks = 1usize << (super_block / 2);
+        let block_len = u64::from(min_elements) << super_block.div_ceil(2);
+        let block_size = block_len
+            .checked_mul(element_size as u64)
+            .ok_or_else(malformed)?;
+        let paged = block_len > page_len;
+        let pages = if paged { block_len / page_len } else { 1 };
+
+        let (bitmap, addresses) = if super_block < direct_super_blocks {
+            if paged {
+                return Err(Error::Unsupported {
+                    feature: String::from("paged data blocks in an extensible array index block"),
+                });
+            }
+            let addresses = index_block
+                .data_block_addresses
+                .get(first_data_block..first_data_block + data_blocks)
+                .ok_or_else(malformed)?;
+            first_data_block += data_blocks;
+            (&[][..], addresses.to_vec())
+        } else {
+            let super_block_address =
+                index_block.super_block_addresses[super_block - direct_super_blocks];
+            if super_block_address == UNDEFINED_ADDRESS {
+                first = first.saturating_add(block_len.saturating_mul(data_blocks as u64));
+                continue;
+            }
+            let bitmap_bits = if paged {
+                usize::try_from(pages)
+                    .ok()
+                    .and_then(|pages| pages.checked_mul(data_blocks))
+                    .ok_or_else(malformed)?
+            } else {
+                0
+            };
+            parse::extensible_array_super_block(
+                file.bytes_from(super_block_address)?,
+                block_offset_size,
+                bitmap_bits.div_ceil(8),
+                data_blocks,
+            )
+            .map_err(|e| Error::nom(file.contents(), e))?
+            .1
+        };
+
+        for (i, data_block_address) in addresses.into_iter().enumerate() {
+            let block_first = first;
+            first = first.saturating_add(block_len);
+            if data_block_address == UNDEFINED_ADDRESS {
+                continue;
+            }
+            if !paged {
+                let size = usize::try_from(block_size).map_err(|_| malformed())?;
+                let elements = parse::extensible_array_data_block(
+                    file.bytes_from(data_block_address)?,
+                    block_offset_size,
+                    size,
+                )
+                .map_err(|e| Error::nom(file.contents(), e))?
+                .1;
+                array_elements(
+                    file,
+                    elements,
+                    element_size,
+                    block_first,
+                    filtered,
+                    &mut chunks,
+                )?;
+                continue;
+            }
+            // The pages of a paged data block come after the checksum that ends the rest of the
+            // block, and its super block records which of them have been written
+            let prefix = (4 + 1 + 1 + 8 + block_offset_size + 4) as u64;
+            let page_size = page_len
+                .checked_mul(element_size as u64)
+                .ok_or_else(malformed)?;
+            for page in 0..pages {
+                if !page_initialized(bitmap, i * pages as usize + page as usize) {
+                    continue;
+                }
+                let page_address = page
+                    .checked_mul(page_size.saturating_add(4))
+                    .and_then(|offset| offset.checked_add(prefix))
+                    .and_then(|offset| offset.checked_add(data_block_address))
+                    .ok_or(Error::OutOfBounds {
+                        address: data_block_address,
+                    })?;
+                let elements = file.bytes(page_address, page_size)?;
+                array_elements(
+                    file,
+                    &elements,
+                    element_size,
+                    block_first + page * page_len,
+                    filtered,
+                    &mut chunks,
+                )?;
+            }
+        }
+    }
+    Ok(chunks)
+}
@@ -0,0 +1,108 @@
+use crate::chunk_index;
+use crate::dataset::{DatasetHandle, Layout};
+use crate::{parse, Error, FromHdf5};
+use std::borrow::Cow;
+
+/// An iterator over the chunks of a dataset, which decodes one chunk at a time
+///
+/// Created by [`DatasetHandle::chunks`].
+pub struct Chunks<'a> {
+    dataset: DatasetHandle<'a>,
+    entries: std::vec::IntoIter<parse::ChunkEntry>,
+    // Datasets that are not chunked are produced as a single chunk
+    unchunked: Option<Cow<'a, [u8]>>,
+}
+
+impl<'a> Chunks<'a> {
+    pub(crate) fn new(dataset: DatasetHandle<'a>) -> Result<Self, Error> {
+        let (entries, unchunked) = match dataset.layout() {
+            Layout::Chunked { .. } => (chunk_index::entries(&dataset)?, None),
+            _ => (Vec::new(), Some(dataset.read_raw()?)),
+        };
+        Ok(Self {
+            dataset,
+            entries: entries.into_iter(),
+            unchunked,
+        })
+    }
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = Result<Chunk<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(data) = self.unchunked.take() {
+            return Some(Ok(Chunk {
+                dataset: self.dataset,
+                offset: vec![0; self.dataset.shape().len()],
+                shape: self.dataset.shape().to_vec(),
+                data,
+            }));
+        }
+        let entry = self.entries.next()?;
+        let rank = self.dataset.shape().len();
+        Some(self.dataset.read_chunk(&entry).map(|data| Chunk {
+            dataset: self.dataset,
+            offset: entry.offsets[..rank].to_vec(),
+            shape: self.dataset.chunk_shape().unwrap_or(&[]).to_vec(),
+            data,
+        }))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.entries.len() + self.unchunked.is_some() as usize;
+        (len, Some(len))
+    }
+}
+
+/// One decoded chunk of a dataset
+pub struct Chunk<'a> {
+    dataset: DatasetHandle<'a>,
+    offset: Vec<u64>,
+    shape: Vec<u64>,
+    data: Cow<'a, [u8]>,
+}
+
+impl<'a> Chunk<'a> {
+    /// The position in the dataset of the first element of this chunk
+    pub fn offset(&self) -> &[u64] {
+        &self.offset
+    }
+
+    /// The shape of this chunk as it is stored
+    ///
+    /// Chunks on the upper edge of a dataset are stored at full size, so part of them may lie
+    /// outside of the dataset. See [`Chunk::extent`].
+    pub fn shape(&self) -> &[u64] {
+        &self.shape
+    }
+
+    /// The shape of the part of this chunk that lies inside the dataset, which is empty if a
+    /// malformed file places the chunk past its end
+    pub fn extent(&self) -> Vec<u64> {
+        self.shape
+            .iter()
+            .zip(&self.offset)
+            .zip(self.dataset.shape())
+            .map(|((len, offset), size)| (*len).min(size.saturating_sub(*offset)))
+            .collect()
+    }
+
+    /// The raw bytes of this chunk, in row-major order and in the file's byte order
+    ///
+    /// Chunks that were stored without filters are borrowed directly from the file.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Take the raw bytes of this chunk, which may be borrowed from the file
+    pub fn into_data(self) -> Cow<'a, [u8]> {
+        self.data
+    }
+
+    /// Convert every element of this chunk to `T`, in row-major order over [`Chunk::shape`]
+    pub fn read<T: FromHdf5>(&self) -> Result<Vec<T>, Error> {
+        self.dataset.check_type::<T>()?;
+        self.dataset.convert(Cow::Borrowed(&self.data))
+    }
+}
@@ -0,0 +1,995 @@
+use crate::attribute::{Attributes, ObjectAttributes};
+use crate::chunk_index;
+use crate::chunks::Chunks;
+use crate::filters::{self, Filter};
+use crate::options::Limits;
+use crate::selection::{self, copy_orthogonal, for_each_index, Axis, Hyperslab, Indices};
+use crate::virtual_dataset;
+#[cfg(feature = "ndarray")]
+use crate::Hdf5Native;
+use crate::{parse, Dataspace, Datatype, Error, FromHdf5, Hdf5Dtype, Hdf5File};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{Read, Seek, SeekFrom};
+
+#[derive(Debug)]
+pub(crate) enum Layout {
+    Compact(Vec<u8>),
+    Contiguous {
+        address: u64,
+        size: u64,
+    },
+    Chunked {
+        index: parse::header::ChunkIndex,
+        chunk_shape: Vec<u64>,
+        /// Whether chunks that reach past the upper edge of the dataset are stored without
+        /// filters
+        unfiltered_edges: bool,
+    },
+    /// Raw data that is kept in other files, in segments that follow one another, whose names
+    /// are in the local heap at `heap_address`
+    External {
+        heap_address: u64,
+        segments: Vec<parse::header::ExternalSegment>,
+    },
+    /// Elements that are taken from other datasets, by mappings that are kept in a global heap
+    Virtual {
+        heap_address: u64,
+        index: u32,
+    },
+}
+
+#[derive(Debug)]
+pub(crate) struct Dataset {
+    pub(crate) address: u64,
+    pub(crate) dataspace: Dataspace,
+    pub(crate) dtype: Datatype,
+    pub(crate) layout: Layout,
+    pub(crate) filters: Vec<Filter>,
+    /// The value of elements that were never written, when one is defined
+    pub(crate) fill_value: Option<Vec<u8>>,
+    pub(crate) attributes: ObjectAttributes,
+}
+
+impl Dataset {
+    /// Build a dataset from the messages in its object header, which is at `address`
+    pub(crate) fn from(address: u64, messages: Vec<parse::header::Message>) -> Result<Self, Error> {
+        use parse::header::{DataLayout, Message};
+        let mut dataspace = None;
+        let mut dtype = None;
+        let mut layout = None;
+        let mut external = None;
+        let mut filters = Vec::new();
+        let mut fill_value = None;
+        let mut fill_value_old = None;
+        let mut attributes = Vec::new();
+        let mut attribute_info = None;
+        for message in messages {
+            match message {
+                Message::DataLayout(m) => layout = Some(m),
+                Message::ExternalDataFiles(m) => external = Some(m),
+                Message::DataType(m) => dtype = Some(Datatype::from(m)?),
+                Message::Dataspace(m) => dataspace = Some(Dataspace::from(m)),
+                Message::FilterPipeline(m) => {
+                    filters = m.filters.into_iter().map(Filter::from).collect();
+                }
+                Message::Attribute(m) => attributes.push(m),
+                Message::DataStorageFillValue(m) => fill_value = Some(m.fill_value),
+                Message::FillValueOld(m) => fill_value_old = Some(m),
+                Message::AttributeInfo(m) => attribute_info = Some(m),
+                Message::ObjectModificationTime(_) => {}
+                Message::Nil | Message::Unknown(_) => {}
+                m => {
+                    return Err(Error::Unsupported {
+                        feature: format!("message type {:#x} in a dataset", m.message_type()),
+                    })
+                }
+            }
+        }
+
+        let missing = |what: &str| Error::parse(format!("dataset has no {} message", what));
+        let dataspace = dataspace.ok_or_else(|| missing("dataspace"))?;
+        let dtype = dtype.ok_or_else(|| missing("datatype"))?;
+        // A value that is not the size of an element, such as the empty one of a message that
+        // defines no value, is of no use. Otherwise the new message takes the place of the old
+        // one when a file has both.
+        let usable = |fill: &Vec<u8>| fill.len() == dtype.size;
+        let fill_value = fill_value.filter(usable).or(fill_value_old.filter(usable));
+        let layout = match layout.ok_or_else(|| missing("layout"))? {
+            DataLayout::Compact { data } => Layout::Compact(data),
+            DataLayout::Contiguous { address, size } => match external {
+                // The layout message of data in external files says that it is contiguous, with
+                // no address in this file
+                Some(external) => Layout::External {
+                    heap_address: external.heap_address,
+                    segments: external.segments,
+                },
+                None => Layout::Contiguous {
+                    address,
+                    size: match size {
+                        Some(size) => size,
+                        None => dataspace.byte_size(dtype.size)? as u64,
+                    },
+                },
+            },
+            DataLayout::Chunked {
+                index,
+                mut dimensions,
+                unfiltered_edges,
+            } => {
+                // The last dimension of a chunk is the size of one element
+                dimensions.pop();
+                let chunk_size = dimensions.iter().try_fold(dtype.size, |size, d| {
+                    size.checked_mul(usize::try_from(*d).ok()?)
+                });
+                if dimensions.len() != dataspace.dims().len()
+                    || dimensions.contains(&0)
+                    || chunk_size.is_none()
+                {
+                    return Err(Error::parse(format!(
+                        "chunks of shape {:?} do not fit a dataspace of shape {:?}",
+                        dimensions,
+                        dataspace.dims()
+                    )));
+                }
+                Layout::Chunked {
+                    index,
+                    chunk_shape: dimensions,
+                    unfiltered_edges,
+                }
+            }
+            DataLayout::Virtual {
+                heap_address,
+                index,
+            } => Layout::Virtual {
+                heap_address,
+                index,
+            },
+        };
+
+        Ok(Self {
+            address,
+            dataspace,
+            dtype,
+            layout,
+            filters,
+            fill_value,
+            attributes: ObjectAttributes::new(attributes, attribute_info),
+        })
+    }
+}
+
+/// A dataset in an opened HDF5 file
+#[derive(Clone, Copy)]
+pub struct DatasetHandle<'a> {
+    file: &'a Hdf5File,
+    dataset: &'a Dataset,
+    // How many virtual datasets this one is being read for, which bounds chains of virtual
+    // datasets that are mapped from each other
+    depth: usize,
+}
+
+impl<'a> DatasetHandle<'a> {
+    pub(crate) fn new(file: &'a Hdf5File, dataset: &'a Dataset) -> Self {
+        Self {
+            file,
+            dataset,
+            depth: 0,
+        }
+    }
+
+    /// This dataset as a source of the virtual dataset `of`
+    pub(crate) fn mapped_into(self, of: &DatasetHandle) -> Self {
+        Self {
+            depth: of.depth + 1,
+            ..self
+        }
+    }
+
+    pub(crate) fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub(crate) fn address(&self) -> u64 {
+        self.dataset.address
+    }
+
+    /// The dataspace of this dataset, which describes its shape
+    pub fn dataspace(&self) -> &'a Dataspace {
+        &self.dataset.dataspace
+    }
+
+    /// The size of each dimension of this dataset
+    pub fn shape(&self) -> &'a [u64] {
+        self.dataset.dataspace.dims()
+    }
+
+    /// The type of each element in this dataset
+    pub fn dtype(&self) -> Hdf5Dtype {
+        self.dataset.dtype.class
+    }
+
+    /// The attributes attached to this dataset
+    pub fn attrs(&self) -> Attributes<'a> {
+        Attributes::new(self.file, &self.dataset.attributes)
+    }
+
+    /// The size of each chunk, if this dataset uses chunked storage
+    pub fn chunk_shape(&self) -> Option<&'a [u64]> {
+        match &self.dataset.layout {
+            Layout::Chunked { chunk_shape, .. } => Some(chunk_shape),
+            _ => None,
+        }
+    }
+
+    /// The filters that are applied to each chunk of this dataset
+    pub fn filters(&self) -> &'a [Filter] {
+        &self.dataset.filters
+    }
+
+    /// Iterate over the chunks of this dataset, decoding one at a time
+    ///
+    /// A dataset that is not chunked is produced as a single chunk that holds all of it.
+    pub fn chunks(&self) -> Result<Chunks<'a>, Error> {
+        Chunks::new(*self)
+    }
+
+    pub(crate) fn file(&self) -> &'a Hdf5File {
+        self.file
+    }
+
+    pub(crate) fn layout(&self) -> &'a Layout {
+        &self.dataset.layout
+    }
+
+    pub(crate) fn datatype(&self) -> &'a Datatype {
+        &self.dataset.dtype
+    }
+
+    /// `size` bytes of elements that all hold the fill value, or zeros when there is none
+    pub(crate) fn filled(&self, size: usize) -> Vec<u8> {
+        match &self.dataset.fill_value {
+            Some(fill) => fill.repeat(size / fill.len()),
+            None => vec![0; size],
+        }
+    }
+
+    /// Read the entire dataset, converting each element to `T`
+    ///
+    /// Elements are returned in row-major order.
+    pub fn read<T: FromHdf5>(&self) -> Result<Vec<T>, Error> {
+        self.check_type::<T>()?;
+        self.convert(self.read_raw()?)
+    }
+
+    /// Read the elements selected by a hyperslab, converting each element to `T`
+    ///
+    /// Elements are returned in row-major order, as an array with the shape of
+    /// [`Hyperslab::shape`]. Only the chunks that the selection touches are read.
+    pub fn read_hyperslab<T: FromHdf5>(&self, selection: &Hyperslab) -> Result<Vec<T>, Error> {
+        self.check_type::<T>()?;
+        let axes = selection.axes(self.shape(), self.dataset.dtype.size, self.limits())?;
+        self.convert(Cow::Owned(self.read_orthogonal(&axes)?))
+    }
+
+    /// Read the outer product of a list of indices along each dimension, converting each element
+    /// to `T`
+    ///
+    /// This is like h5py's `ds[[3, 17, 900], :]`. Elements are returned in row-major order, as an
+    /// array with one dimension per entry of `indices`, and each dimension holds the selected
+    /// indices in the order that they were listed. Each chunk is decoded at most once.
+    pub fn read_indices<T: FromHdf5>(&self, indices: &[Indices]) -> Result<Vec<T>, Error> {
+        self.check_type::<T>()?;
+        let axes = Indices::axes(
+            indices,
+            self.shape(),
+            self.dataset.dtype.size,
+            self.limits(),
+        )?;
+        self.convert(Cow::Owned(self.read_orthogonal(&axes)?))
+    }
+
+    /// Read the elements at a list of points, converting each element to `T`
+    ///
+    /// Elements are returned in the order that the points were listed. The points are sorted
+    /// internally so that each chunk is decoded at most once.
+    pub fn read_points<T: FromHdf5, P: AsRef<[u64]>>(&self, points: &[P]) -> Result<Vec<T>, Error> {
+        self.check_type::<T>()?;
+        selection::check_points(points, self.shape())?;
+        self.convert(Cow::Owned(self.read_points_raw(points)?))
+    }
+
+    pub(crate) fn check_type<T: FromHdf5>(&self) -> Result<(), Error> {
+        if T::from_types().contains(&self.dataset.dtype.class) {
+            Ok(())
+        } else {
+            Err(Error::TypeMismatch {
+                expected: T::from_types(),
+                found: self.dataset.dtype.class,
+            })
+        }
+    }
+
+    /// Convert raw elements that are in the file's byte order
+    pub(crate) fn convert<T: FromHdf5>(&self, data: Cow<[u8]>) -> Result<Vec<T>, Error> {
+        let dtype = &self.dataset.dtype;
+        self.limits()
+            .check_allocation(data.len() / dtype.size * std::mem::size_of::<T>())?;
+        dtype
+            .to_native(data)
+            .chunks_exact(dtype.size)
+            .map(|element| T::convert(self.file, dtype.class, element))
+            .collect()
+    }
+
+    /// The bytes of the whole dataset in row-major order and in the file's byte order
+    pub(crate) fn read_raw(&self) -> Result<Cow<'a, [u8]>, Error> {
+        let expected = self.dataset.dataspace.byte_size(self.dataset.dtype.size)?;
+        let data = match &self.dataset.layout {
+            Layout::Compact(data) => Cow::Borrowed(&data[..]),
+            // Storage that was never allocated holds only the fill value
+            Layout::Contiguous { address, .. } if *address == parse::UNDEFINED_ADDRESS => {
+                self.limits().check_allocation(expected)?;
+                Cow::Owned(self.filled(expected))
+            }
+            Layout::Contiguous { address, size } => self.file.bytes(*address, *size)?,
+            Layout::Chunked { chunk_shape, .. } => Cow::Owned(self.read_chunked(chunk_shape)?),
+            Layout::External {
+                heap_address,
+                segments,
+            } => Cow::Owned(self.read_external(*heap_address, segments)?),
+            Layout::Virtual { .. } => {
+                selection::check_size(self.shape(), self.dataset.dtype.size, self.limits())?;
+                let axes = self
+                    .shape()
+                    .iter()
+                    .map(|len| (0..*len).map(|i| (i, i as usize)).collect())
+                    .collect::<Vec<_>>();
+                Cow::Owned(self.read_orthogonal(&axes)?)
+            }
+        };
+        if data.len() < expected {
+            return Err(Error::parse(format!(
+                "dataset storage holds {} bytes but its dataspace and type require {}",
+                data.len(),
+                expected
+            )));
+        }
+        Ok(match data {
+            Cow::Borrowed(data) => Cow::Borrowed(&data[..expected]),
+            Cow::Owned(mut data) => {
+                data.truncate(expected);
+                Cow::Owned(data)
+            }
+        })
+    }
+
+    fn read_chunked(&self, chunk_shape: &[u64]) -> Result<Vec<u8>, Error> {
+        let element_size = self.dataset.dtype.size;
+        let shape = self.shape();
+        let size = self.dataset.dataspace.byte_size(element_size)?;
+        self.limits().check_allocation(size)?;
+        // Chunks that were never written hold the fill value
+        let mut output = self.filled(size);
+        for entry in chunk_index::entries(self)? {
+            let chunk = self.read_chunk(&entry)?;
+            copy_chunk(
+                &mut output,
+                shape,
+                &chunk,
+                chunk_shape,
+                &entry.offsets,
+                element_size,
+            )?;
+        }
+        Ok(output)
+    }
+
+    /// The bytes of an orthogonal selection in row-major order and in the file's byte order
+    pub(crate) fn read_orthogonal(&self, axes: &[Axis]) -> Result<Vec<u8>, Error> {
+        let element_size = self.dataset.dtype.size;
+        let output_shape = axes.iter().map(Vec::len).collect::<Vec<_>>();
+        let size = output_shape
+            .iter()
+            .try_fold(element_size, |size, len| size.checked_mul(*len))
+            .ok_or_else(|| Error::parse("the selection does not fit in memory"))?;
+        self.limits().check_allocation(size)?;
+        let mut output = self.filled(size);
+        if output.is_empty() {
+            return Ok(output);
+        }
+        let shape = self.shape();
+        match &self.dataset.layout {
+            Layout::Compact(_) => {
+                let data = self.read_raw()?;
+                copy_orthogonal(
+                    &mut output,
+                    &output_shape,
+                    axes,
+                    shape,
+                    element_size,
+                    |offset, destination| copy_from(&data, offset, destination),
+                )?;
+            }
+            Layout::External {
+                heap_address,
+                segments,
+            } => {
+                let mut files = ExternalFiles::new(self, *heap_address, segments)?;
+                copy_orthogonal(
+                    &mut output,
+                    &output_shape,
+                    axes,
+                    shape,
+                    element_size,
+                    |offset, destination| files.read(offset, destination),
+                )?;
+            }
+            // Elements that no source maps to are left filled
+            Layout::Virtual {
+                heap_address,
+                index,
+            } => virtual_dataset::read(self, *heap_address, *index, axes, &mut output)?,
+            Layout::Contiguous { address, .. } if *address == parse::UNDEFINED_ADDRESS => {}
+            Layout::Contiguous { address, size } => {
+                copy_orthogonal(
+                    &mut output,
+                    &output_shape,
+                    axes,
+                    shape,
+                    element_size,
+                    |offset, destination| {
+                        let len = destination.len() as u64;
+                        let start = address
+                            .checked_add(offset)
+                            .ok_or(Error::OutOfBounds { address: *address })?;
+                        if offset.checked_add(len).is_none_or(|end| end > *size) {
+                            return Err(Error::OutOfBounds { address: start });
+                        }
+                        destination.copy_from_slice(&self.file.bytes(start, len)?);
+                        Ok(())
+                    },
+                )?;
+            }
+            Layout::Chunked { chunk_shape, .. } => {
+                let rank = chunk_shape.len();
+                let index = self.chunks_by_offset()?;
+
+                // Split the selection along each dimension into the parts that fall in each chunk
+                let mut chunk_axes = Vec::with_capacity(rank);
+                for (axis, chunk_len) in axes.iter().zip(chunk_shape) {
+                    let mut parts: Vec<(u64, Axis)> = Vec::new();
+                    for (coordinate, position) in axis {
+                        let chunk_start = coordinate - coordinate % chunk_len;
+                        let local = (coordinate - chunk_start, *position);
+                        match parts.last_mut() {
+                            Some((start, part)) if *start == chunk_start => part.push(local),
+                            _ => parts.push((chunk_start, vec![local])),
+                        }
+                    }
+                    chunk_axes.push(parts);
+                }
+
+                let lens = chunk_axes.iter().map(Vec::len).collect::<Vec<_>>();
+                for_each_index(&lens, |chunk| {
+                    let offsets = (0..rank)
+                        .map(|d| chunk_axes[d][chunk[d]].0)
+                        .collect::<Vec<_>>();
+                    let entry = match index.get(&offsets[..]) {
+                        Some(entry) => entry,
+                        // Chunks that were never written are left filled
+                        None => return Ok(()),
+                    };
+                    let data = self.read_chunk(entry)?;
+                    let local_axes = (0..rank)
+                        .map(|d| chunk_axes[d][chunk[d]].1.clone())
+                        .collect::<Vec<_>>();
+                    copy_orthogonal(
+                        &mut output,
+                        &output_shape,
+                        &local_axes,
+                        chunk_shape,
+                        element_size,
+                        |offset, destination| copy_from(&data, offset, destination),
+                    )
+                })?;
+            }
+        }
+        Ok(output)
+    }
+
+    /// The bytes at a list of points in the order listed and in the file's byte order
+    pub(crate) fn read_points_raw<P: AsRef<[u64]>>(&self, points: &[P]) -> Result<Vec<u8>, Error> {
+        let element_size = self.dataset.dtype.size;
+        // The position of a point among the elements of an array whose dimensions have `strides`
+        let linear = |point: &[u64], strides: &[u64]| {
+            point
+                .iter()
+                .zip(strides)
+                .try_fold(0u64, |sum, (i, stride)| {
+                    i.checked_mul(*stride)?.checked_add(sum)
+                })
+                .ok_or_else(|| Error::InvalidSelection {
+                    reason: format!("point {:?} is too far into the dataset to address", point),
+                })
+        };
+        let size = points
+            .len()
+            .checked_mul(element_size)
+            .ok_or_else(|| Error::parse("the selection does not fit in memory"))?;
+        self.limits().check_allocation(size)?;
+        let mut output = self.filled(size);
+
+        // Sort the points by where they are stored, remembering where each one goes
+        let mut order = match &self.dataset.layout {
+            Layout::Chunked { chunk_shape, .. } => {
+                let chunk_strides = selection::strides(chunk_shape)?;
+                points
+                    .iter()
+                    .enumerate()
+                    .map(|(position, point)| {
+                        let point = point.as_ref();
+                        let chunk = point
+                            .iter()
+                            .zip(chunk_shape)
+                            .map(|(i, len)| i - i % len)
+                            .collect::<Vec<_>>();
+                        let local = point
+                            .iter()
+                            .zip(&chunk)
+                            .map(|(i, start)| i - start)
+                            .collect::<Vec<_>>();
+                        Ok((chunk, linear(&local, &chunk_strides)?, position))
+                    })
+                    .collect::<Result<Vec<(Vec<u64>, u64, usize)>, Error>>()?
+            }
+            _ => {
+                let strides = selection::strides(self.shape())?;
+                points
+                    .iter()
+                    .enumerate()
+                    .map(|(position, point)| {
+                        Ok((Vec::new(), linear(point.as_ref(), &strides)?, position))
+                    })
+                    .collect::<Result<_, Error>>()?
+            }
+        };
+        order.sort_unstable();
+
+        match &self.dataset.layout {
+            Layout::Compact(_) => {
+                let data = self.read_raw()?;
+                for (_, offset, position) in order {
+                    let start = position * element_size;
+                    copy_from(
+                        &data,
+                        offset.saturating_mul(element_size as u64),
+                        &mut output[start..start + element_size],
+                    )?;
+                }
+            }
+            // Each point is looked for among the mappings on its own
+            Layout::Virtual {
+                heap_address,
+                index,
+            } => virtual_dataset::read_points(self, *heap_address, *index, points, &mut output)?,
+            Layout::External {
+                heap_address,
+                segments,
+            } => {
+                let mut files = ExternalFiles::new(self, *heap_address, segments)?;
+                read_runs(&order, element_size, &mut output, |offset, len| {
+                    // Runs are no longer than the points in them, whose output fits in memory
+                    let mut data = vec![0; len as usize];
+                    files.read(offset, &mut data)?;
+                    Ok(Cow::Owned(data))
+                })?;
+            }
+            Layout::Contiguous { address, .. } if *address == parse::UNDEFINED_ADDRESS => {}
+            Layout::Contiguous { address, size } => {
+                read_runs(&order, element_size, &mut output, |offset, len| {
+                    let start = address
+                        .checked_add(offset)
+                        .ok_or(Error::OutOfBounds { address: *address })?;
+                    if offset.checked_add(len).is_none_or(|end| end > *size) {
+                        return Err(Error::OutOfBounds { address: start });
+                    }
+                    self.file.bytes(start, len)
+                })?;
+            }
+            Layout::Chunked { .. } => {
+                let index = self.chunks_by_offset()?;
+                let mut group_start = 0;
+                while group_start < order.len() {
+                    let chunk = &order[group_start].0;
+                    let group_end = group_start
+                        + order[group_start..]
+                            .iter()
+                            .take_while(|(c, _, _)| c == chunk)
+                            .count();
+                    // Chunks that were never written are left filled
+                    if let Some(entry) = index.get(&chunk[..]) {
+                        let data = self.read_chunk(entry)?;
+                        for (_, offset, position) in &order[group_start..group_end] {
+                            let start = position * element_size;
+                            copy_from(
+                                &data,
+                                offset * element_size as u64,
+                                &mut output[start..start + element_size],
+                            )?;
+                        }
+                    }
+                    group_start = group_end;
+                }
+            }
+        }
+        Ok(output)
+    }
+
+    /// Look up the chunks of this dataset by the offset of their first element
+    fn chunks_by_offset(&self) -> Result<HashMap<Vec<u64>, parse::ChunkEntry>, Error> {
+        let rank = self.shape().len();
+        Ok(chunk_index::entries(self)?
+            .into_iter()
+            .map(|entry| (entry.offsets[..rank].to_vec(), entry))
+            .collect())
+    }
+
+    /// Read and decode one chunk
+    pub(crate) fn read_chunk(&self, entry: &parse::ChunkEntry) -> Result<Cow<'a, [u8]>, Error> {
+        let limits = self.limits();
+        limits.check_chunk_size(usize::try_from(entry.chunk_size).unwrap_or(usize::MAX))?;
+        let stored = self.file.bytes(entry.address, entry.chunk_size)?;
+        let decoded = filters::decode(
+            &self.dataset.filters,
+            entry.filter_mask,
+            stored,
+            self.dataset.dtype.size,
+            std::cmp::min(limits.max_chunk_size, limits.max_allocation),
+        )?;
+        limits.check_chunk_size(decoded.len())?;
+        limits.check_allocation(decoded.len())?;
+        Ok(decoded)
+    }
+
+    /// Read the data that is kept in the external files that `segments` name
+    fn read_external(
+        &self,
+        heap_address: u64,
+        segments: &[parse::header::ExternalSegment],
+    ) -> Result<Vec<u8>, Error> {
+        let size = self.dataset.dataspace.byte_size(self.dataset.dtype.size)?;
+        self.limits().check_allocation(size)?;
+        let mut output = vec![0; size];
+        ExternalFiles::new(self, heap_address, segments)?.read(0, &mut output)?;
+        Ok(output)
+    }
+
+    pub(crate) fn limits(&self) -> &'a Limits {
+        &self.file.options.limits
+    }
+}
+
+/// Copy the points in `order`, which are sorted by their offsets in the data, to their positions
+/// in `output`, reading each run of points that are next to each other in the data at once
+///
+/// `read` is given the offset and length in bytes of a run.
+fn read_runs<'d>(
+    order: &[(Vec<u64>, u64, usize)],
+    element_size: usize,
+    output: &mut [u8],
+    mut read: impl FnMut(u64, u64) -> Result<Cow<'d, [u8]>, Error>,
+) -> Result<(), Error> {
+    let too_far = |offset: u64| Error::InvalidSelection {
+        reason: format!("element {} is too far into the dataset to address", offset),
+    };
+    let mut group_start = 0;
+    while group_start < order.len() {
+        let first = order[group_start].1;
+        let mut group_end = group_start + 1;
+        while group_end < order.len()
+            && order[group_end].1 <= order[group_end - 1].1.saturating_add(1)
+        {
+            group_end += 1;
+        }
+        let last = order[group_end - 1].1;
+        let len = (last - first + 1)
+            .checked_mul(element_size as u64)
+            .ok_or_else(|| too_far(last))?;
+        let offset = first
+            .checked_mul(element_size as u64)
+            .ok_or_else(|| too_far(first))?;
+        let data = read(offset, len)?;
+        for (_, offset, position) in &order[group_start..group_end] {
+            let start = position * element_size;
+            copy_from(
+                &data,
+                (offset - first) * element_size as u64,
+                &mut output[start..start + element_size],
+            )?;
+        }
+        group_start = group_end;
+    }
+    Ok(())
+}
+
+/// The external files that the raw data of a dataset is kept in, in segments that follow one
+/// another, which are opened when they are first read from
+///
+/// As in the reference library, the parts of segments that are past the end of their files read
+/// as zeros.
+struct ExternalFiles<'a> {
+    file: &'a Hdf5File,
+    /// The address of the data of the local heap that holds the files' names
+    names_address: u64,
+    segments: &'a [parse::header::ExternalSegment],
+    opened: Vec<Option<std::fs::File>>,
+}
+
+impl<'a> ExternalFiles<'a> {
+    /// The external files of `dataset`, whose names are in the local heap at `heap_address`,
+    /// which must hold all of its data
+    fn new(
+        dataset: &DatasetHandle<'a>,
+        heap_address: u64,
+        segments: &'a [parse::header::ExternalSegment],
+    ) -> Result<Self, Error> {
+        let file = dataset.file;
+        let heap = parse::local_heap(file.bytes_from(heap_address)?, 8, 8)
+            .map_err(|e| Error::nom(file.contents(), e))?
+            .1;
+        let size = dataset
+            .dataset
+            .dataspace
+            .byte_size(dataset.dataset.dtype.size)?;
+        let held = segments
+            .iter()
+            .fold(0u64, |held, segment| held.saturating_add(segment.size));
+        if held < size as u64 {
+            return Err(Error::parse(format!(
+                "external files hold {} bytes but the dataspace and type require {}",
+                held, size
+            )));
+        }
+        Ok(Self {
+            file,
+            names_address: heap.address_of_data_segment,
+            segments,
+            opened: segments.iter().map(|_| None).collect(),
+        })
+    }
+
+    /// Fill `destination` with the bytes of the data from `offset` on
+    fn read(&mut self, offset: u64, destination: &mut [u8]) -> Result<(), Error> {
+        // Where the segment starts in the data
+        let mut start = 0u64;
+        let mut filled = 0;
+        for i in 0..self.segments.len() {
+            if filled == destination.len() {
+                break;
+            }
+            let segment = &self.segments[i];
+            let end = start.saturating_add(segment.size);
+            let position = offset.saturating_add(filled as u64);
+            if position < end {
+                let len = usize::try_from(end - position)
+                    .map_or(destination.len() - filled, |len| {
+                        std::cmp::min(len, destination.len() - filled)
+                    });
+                let at =
+                    segment
+                        .offset
+                        .checked_add(position - start)
+                        .ok_or(Error::OutOfBounds {
+                            address: segment.offset,
+                        })?;
+                let file = self.open(i)?;
+                file.seek(SeekFrom::Start(at))?;
+                let part = &mut destination[filled..filled + len];
+                let mut read = 0;
+                while read < len {
+                    match file.read(&mut part[read..])? {
+                        0 => break,
+                        n => read += n,
+                    }
+                }
+                part[read..].fill(0);
+                filled += len;
+            }
+            start = end;
+        }
+        if filled < destination.len() {
+            return Err(Error::parse(
+                "the selection reaches past the end of the external files",
+            ));
+        }
+        Ok(())
+    }
+
+    /// The file of segment `i`, which is opened if it has not been yet
+    fn open(&mut self, i: usize) -> Result<&mut std::fs::File, Error> {
+        let file = match self.opened[i].take() {
+            Some(file) => file,
+            None => {
+                let name_address = self
+                    .names_address
+                    .checked_add(self.segments[i].name_offset)
+                    .ok_or(Error::OutOfBounds {
+                        address: self.segments[i].name_offset,
+                    })?;
+                let name = self.file.bytes_from(name_address)?;
+                let end = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+                let name = String::from_utf8_lossy(&name[..end]).into_owned();
+                std::fs::File::open(self.file.external_file_path(&name)?).map_err(|e| {
+                    match e.kind() {
+                        std::io::ErrorKind::NotFound => Error::NotFound { path: name },
+                        _ => Error::Io(e),
+                    }
+                })?
+            }
+        };
+        Ok(self.opened[i].insert(file))
+    }
+}
+
+/// Fill `destination` from `source`, starting at `offset`
+pub(crate) fn copy_from(source: &[u8], offset: u64, destination: &mut [u8]) -> Result<(), Error> {
+    let range = usize::try_from(offset)
+        .ok()
+        .and_then(|start| Some(start..start.checked_add(destination.len())?));
+    match range.and_then(|range| source.get(range)) {
+        Some(source) => {
+            destination.copy_from_slice(source);
+            Ok(())
+        }
+        None => Err(Error::parse(format!(
+            "selection reads past the end of a {} byte buffer",
+            source.len()
+        ))),
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl<'a> DatasetHandle<'a> {
+    /// Read the entire dataset into an array that has the shape of its dataspace, converting
+    /// each element to `T`
+    ///
+    /// Use `IxDyn` as the dimension to accept datasets of any rank.
+    pub fn read_array<T: FromHdf5, D: ndarray::Dimension>(
+        &self,
+    ) -> Result<ndarray::Array<T, D>, Error> {
+        let shape = self.dataset.dataspace.array_shape::<D>()?;
+        ndarray::ArrayD::from_shape_vec(shape, self.read()?)
+            .and_then(|array| array.into_dimensionality())
+            .map_err(|e| Error::parse(e.to_string()))
+    }
+
+    /// View the dataset in place without copying it
+    ///
+    /// This is only possible when the dataset is stored contiguously or compactly, its type is
+    /// exactly `T` in the host's byte order, and its data is suitably aligned for `T`.
+    pub fn view_array<T: Hdf5Native, D: ndarray::Dimension>(
+        &self,
+    ) -> Result<ndarray::ArrayView<'a, T, D>, Error> {
+        let dtype = &self.dataset.dtype;
+        let native = &T::from_types()[..1];
+        if native[0] != dtype.class || std::mem::size_of::<T>() != dtype.size {
+            return Err(Error::TypeMismatch {
+                expected: native,
+                found: dtype.class,
+            });
+        }
+        if dtype.byte_order != crate::ByteOrder::NATIVE {
+            return Err(Error::Unsupported {
+                feature: String::from("viewing data that is not in the host's byte order"),
+            });
+        }
+        let data = match &self.dataset.layout {
+            Layout::Chunked { .. } => {
+                return Err(Error::Unsupported {
+                    feature: String::from("viewing chunked data"),
+                })
+            }
+            Layout::External { .. } => {
+                return Err(Error::Unsupported {
+                    feature: String::from("viewing data in external files"),
+                })
+            }
+            Layout::Virtual { .. } => {
+                return Err(Error::Unsupported {
+                    feature: String::from("viewing virtual datasets"),
+                })
+            }
+            Layout::Contiguous { address, .. } if *address == parse::UNDEFINED_ADDRESS => {
+                return Err(Error::Unsupported {
+                    feature: String::from("viewing data that was never written"),
+                })
+            }
+            _ => self.read_raw()?,
+        };
+        let data = match data {
+            Cow::Borrowed(data) => data,
+            // Contiguous data is only copied when it is split between member files
+            Cow::Owned(_) => {
+                return Err(Error::Unsupported {
+                    feature: String::from("viewing data that spans member files"),
+                })
+            }
+        };
+        if data.as_ptr().align_offset(std::mem::align_of::<T>()) != 0 {
+            return Err(Error::Unsupported {
+                feature: String::from("viewing data that is not aligned for its type"),
+            });
+        }
+        let shape = self.dataset.dataspace.array_shape::<D>()?;
+        // Safety: The type check above ensures that T is a number type which has exactly the
+        // size and byte order of the stored elements, every bit pattern is valid for these
+        // types, and the pointer has been checked for alignment. read_raw ensures that data
+        // holds exactly as many bytes as the dataspace requires.
+        let elements = unsafe {
+            std::slice::from_raw_parts(data.as_ptr() as *const T, data.len() / dtype.size)
+        };
+        ndarray::ArrayViewD::from_shape(shape, elements)
+            .and_then(|array| array.into_dimensionality())
+            .map_err(|e| Error::parse(e.to_string()))
+    }
+}
+
+/// Copy one decoded chunk into its place in a row-major buffer that holds the whole dataset
+///
+/// Chunks on the upper edge of the dataset are stored at full size, so only the part of the chunk
+/// that falls inside `shape` is copied.
+fn copy_chunk(
+    output: &mut [u8],
+    shape: &[u64],
+    chunk: &[u8],
+    chunk_shape: &[u64],
+    chunk_offset: &[u64],
+    element_size: usize,
+) -> Result<(), Error> {
+    let rank = shape.len();
+    let chunk_elements = chunk_shape.iter().product::<u64>() as usize;
+    if chunk.len() < chunk_elements * element_size {
+        return Err(Error::parse(format!(
+            "chunk at offset {:?} holds {} bytes but should hold {}",
+            chunk_offset,
+            chunk.len(),
+            chunk_elements * element_size
+        )));
+    }
+    let extent = (0..rank)
+        .map(|d| chunk_shape[d].min(shape[d].saturating_sub(chunk_offset[d])))
+        .collect::<Vec<_>>();
+    if rank == 0 || extent.contains(&0) {
+        return Ok(());
+    }
+    let row = extent[rank - 1] as usize * element_size;
+    let mut index = vec![0; rank - 1];
+    loop {
+        let mut source = 0;
+        let mut destination = 0;
+        for d in 0..rank {
+            let i = index.get(d).copied().unwrap_or(0);
+            source = source * chunk_shape[d] + i;
+            destination = destination * shape[d] + chunk_offset[d] + i;
+        }
+        let source = source as usize * element_size;
+        let destination = destination as usize * element_size;
+        output[destination..destination + row].copy_from_slice(&chunk[source..source + row]);
+
+        // Advance to the next row of the chunk, like an odometer
+        let mut d = rank - 1;
+        loop {
+            if d == 0 {
+                return Ok(());
+            }
+            d -= 1;
+            index[d] += 1;
+            if index[d] < extent[d] {
+                break;
+            }
+            index[d] = 0;
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use crate::attribute::{Attributes, ObjectAttributes};
+use crate::{parse, Error, Hdf5Dtype, Hdf5File};
+
+/// A datatype that is stored as an object of its own, so that datasets and attributes can share
+/// it by referring to its object header
+#[derive(Debug)]
+pub(crate) struct CommittedDatatype {
+    pub(crate) address: u64,
+    // Kept as it is stored, so that a class that cannot be decoded still makes an object
+    pub(crate) raw: parse::header::DataType,
+    pub(crate) attributes: ObjectAttributes,
+}
+
+impl CommittedDatatype {
+    /// Build a committed datatype from the messages in its object header, which is at `address`
+    pub(crate) fn from(address: u64, messages: Vec<parse::header::Message>) -> Result<Self, Error> {
+        use parse::header::Message;
+        let mut raw = None;
+        let mut attributes = Vec::new();
+        let mut attribute_info = None;
+        for message in messages {
+            match message {
+                Message::DataType(m) => raw = Some(m),
+                Message::Attribute(m) => attributes.push(m),
+                Message::AttributeInfo(m) => attribute_info = Some(m),
+                _ => {}
+            }
+        }
+        Ok(Self {
+            address,
+            raw: raw.ok_or_else(|| Error::parse("committed datatype has no datatype message"))?,
+            attributes: ObjectAttributes::new(attributes, attribute_info),
+        })
+    }
+}
+
+/// A datatype that is stored in a group under its own name
+#[derive(Clone, Copy)]
+pub struct DatatypeHandle<'a> {
+    file: &'a Hdf5File,
+    datatype: &'a CommittedDatatype,
+}
+
+impl<'a> DatatypeHandle<'a> {
+    pub(crate) fn new(file: &'a Hdf5File, datatype: &'a CommittedDatatype) -> Self {
+        Self { file, datatype }
+    }
+
+    pub(crate) fn address(&self) -> u64 {
+        self.datatype.address
+    }
+
+    /// The type that this object describes, or [`Error::Unsupported`] if it is of a class that
+    /// this library cannot decode, such as a compound type
+    pub fn dtype(&self) -> Result<Hdf5Dtype, Error> {
+        Hdf5Dtype::from(&self.datatype.raw)
+    }
+
+    /// The number of bytes in each element of this type
+    pub fn size(&self) -> usize {
+        self.datatype.raw.size as usize
+    }
+
+    /// The attributes attached to this datatype
+    pub fn attrs(&self) -> Attributes<'a> {
+        Attributes::new(self.file, &self.datatype.attributes)
+    }
+}
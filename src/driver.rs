@@ -0,0 +1,139 @@
+//! Files that the family, split and multi drivers wrote, which keep the bytes of one file in
+//! several member files
+
+use crate::parse::{DriverInfo, MultiMember};
+use crate::storage::{self, Contents};
+use crate::Error;
+use std::path::{Component, Path, PathBuf};
+
+/// Add the member files that `info` describes to `contents`, which holds the member with the
+/// superblock, opened from `path`
+///
+/// `end` is the address just past the end of the whole file, counting from the start of the
+/// first member.
+pub(crate) fn open_members(
+    contents: &mut Contents,
+    info: DriverInfo,
+    path: Option<&Path>,
+    end: u64,
+) -> Result<(), Error> {
+    match info {
+        DriverInfo::Family { member_size } => {
+            if member_size == 0 {
+                return Err(Error::parse("family members are empty"));
+            }
+            // The first member is already open
+            for index in 1..end.div_ceil(member_size) {
+                let member = path
+                    .and_then(|path| family_member(path, index))
+                    .ok_or_else(|| member_names_unknown(path))?;
+                contents.add_member(index * member_size, Box::new(storage::map(&member)?));
+            }
+            Ok(())
+        }
+        DriverInfo::Multi { members } => {
+            let (first, others) = members
+                .split_first()
+                .ok_or_else(|| Error::parse("a multi driver file has no members"))?;
+            let (path, name) = path
+                .and_then(|path| Some((path, multi_name(path, first)?)))
+                .ok_or_else(|| member_names_unknown(path))?;
+            for member in others {
+                // The other members are next to the one with the superblock, so their names
+                // cannot lead to any other directory
+                let file = member.name.replace("%s", &name);
+                if !is_file_name(&file) {
+                    return Err(Error::ExternalFileDenied { file });
+                }
+                let file = path.with_file_name(file);
+                contents.add_member(member.address, Box::new(storage::map(&file)?));
+            }
+            Ok(())
+        }
+        DriverInfo::Other(name) => Err(Error::Unsupported {
+            feature: format!("files written by the {:?} driver", name),
+        }),
+    }
+}
+
+fn member_names_unknown(path: Option<&Path>) -> Error {
+    Error::Unsupported {
+        feature: match path {
+            Some(path) => format!("finding the member files of {:?} from its name", path),
+            None => String::from("finding the member files of a file that is not on disk"),
+        },
+    }
+}
+
+/// Whether `name` names a file in the current directory, rather than being absolute, leading out
+/// with `..` or naming a file in some other directory
+fn is_file_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    ) && !name.contains(['/', '\\'])
+}
+
+/// Whether the file name in `path` is a pattern for the names of family members, such as
+/// `out_%05d.h5`, rather than the name of the first member
+///
+/// A file that exists by that name is opened as it is, so that names like `100%done.h5` are not
+/// taken for patterns.
+pub(crate) fn is_family_pattern(path: &Path) -> bool {
+    printf_integer(path).is_some() && !path.exists()
+}
+
+/// The path of the family member numbered `index`, given either the pattern that the member
+/// names follow or the path of the first member, whose number is the last run of digits in its
+/// name before the extension
+pub(crate) fn family_member(path: &Path, index: u64) -> Option<PathBuf> {
+    let name = path.file_name()?.to_str()?;
+    let conversion = printf_integer(path).filter(|_| is_family_pattern(path));
+    let (start, end, width, zero) = match conversion {
+        Some(conversion) => conversion,
+        None => {
+            let stem = path.file_stem()?.to_str()?;
+            let end = stem.rfind(|c: char| c.is_ascii_digit())? + 1;
+            let start = stem[..end]
+                .rfind(|c: char| !c.is_ascii_digit())
+                .map_or(0, |i| i + 1);
+            (start, end, end - start, true)
+        }
+    };
+    let number = if zero {
+        format!("{:0width$}", index, width = width)
+    } else {
+        format!("{:width$}", index, width = width)
+    };
+    Some(path.with_file_name(format!("{}{}{}", &name[..start], number, &name[end..])))
+}
+
+/// Where the first `%d` conversion is in the file name in `path`, with its width and whether it
+/// is padded with zeros
+fn printf_integer(path: &Path) -> Option<(usize, usize, usize, bool)> {
+    let name = path.file_name()?.to_str()?;
+    name.match_indices('%').find_map(|(start, _)| {
+        let spec = &name[start + 1..];
+        let length = spec.find(|c: char| !c.is_ascii_digit())?;
+        if !spec[length..].starts_with('d') {
+            return None;
+        }
+        let width = match length {
+            0 => 0,
+            _ => spec[..length].parse().ok()?,
+        };
+        Some((start, start + length + 2, width, spec.starts_with('0')))
+    })
+}
+
+/// What `%s` stood for in the name of the member with the superblock, which the other members'
+/// names are made from
+fn multi_name(path: &Path, member: &MultiMember) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    let (prefix, suffix) = member.name.split_at(member.name.find("%s")?);
+    let suffix = &suffix[2..];
+    name.strip_prefix(prefix)?
+        .strip_suffix(suffix)
+        .map(String::from)
+}
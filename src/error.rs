@@ -1,25 +1,207 @@
+use crate::storage::Contents;
+
 /// All errors that this library can emit
 pub enum Error {
     Io(std::io::Error),
-    Parse(String),
+    /// A structure in the file is malformed
+    Parse {
+        /// What is wrong with the structure
+        reason: String,
+        /// The offset at which parsing failed, when it is known, which like every address in the
+        /// file counts from the superblock, after any user block
+        offset: Option<u64>,
+        /// The structures that were being parsed, outermost first, such as `"object header"`
+        context: Vec<&'static str>,
+        /// The path of the object whose metadata was being read, when it is known
+        path: Option<String>,
+    },
+    /// Nothing exists at the requested path
+    NotFound {
+        path: String,
+    },
+    /// The stored type cannot be converted to the requested type
+    TypeMismatch {
+        expected: &'static [crate::Hdf5Dtype],
+        found: crate::Hdf5Dtype,
+    },
+    /// The file uses a part of the format that this library does not implement
+    Unsupported {
+        feature: String,
+    },
+    /// The number of dimensions requested does not match the stored data
+    RankMismatch {
+        expected: usize,
+        found: usize,
+    },
+    /// A single value was requested from data that does not hold exactly one element
+    NotScalar {
+        elements: u64,
+    },
+    /// A selection does not fit the dataset it is applied to
+    InvalidSelection {
+        reason: String,
+    },
+    /// The file refers to an address beyond its end
+    OutOfBounds {
+        address: u64,
+    },
+    /// Links lead to more links than can be followed, usually because they form a cycle
+    TooManyLinks {
+        path: String,
+    },
+    /// An external link leads to a file that the filter in [`crate::OpenOptions`] refused
+    ExternalLinkDenied {
+        file: String,
+    },
+    /// A dataset's raw data is said to be in a file outside of the directory that external files
+    /// are looked for in, or a member of a multi driver file is said to be outside of the
+    /// directory of the member with the superblock, because its name is absolute or leads out
+    /// with `..`
+    ExternalFileDenied {
+        file: String,
+    },
+    /// Reading the file would go over one of the [`crate::Limits`] it was opened with
+    LimitExceeded {
+        limit: &'static str,
+        value: u64,
+        maximum: u64,
+    },
 }
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             Self::Io(e) => write!(f, "{}", e),
-            Self::Parse(e) => write!(f, "{}", e),
+            Self::Parse {
+                reason,
+                offset,
+                context,
+                path,
+            } => {
+                write!(f, "{}", reason)?;
+                if let Some(offset) = offset {
+                    write!(f, " at offset {:#x}", offset)?;
+                }
+                if !context.is_empty() {
+                    write!(f, " while parsing {}", context.join(" > "))?;
+                }
+                if let Some(path) = path {
+                    write!(f, " in {:?}", path)?;
+                }
+                Ok(())
+            }
+            Self::NotFound { path } => write!(f, "nothing found at {:?}", path),
+            Self::TypeMismatch { expected, found } => write!(
+                f,
+                "stored type {:?} is not compatible with any of {:?}",
+                found, expected
+            ),
+            Self::Unsupported { feature } => write!(f, "unsupported: {}", feature),
+            Self::RankMismatch { expected, found } => write!(
+                f,
+                "expected {} dimensions but the data has {}",
+                expected, found
+            ),
+            Self::NotScalar { elements } => {
+                write!(f, "expected a single element but the data has {}", elements)
+            }
+            Self::InvalidSelection { reason } => write!(f, "invalid selection: {}", reason),
+            Self::OutOfBounds { address } => {
+                write!(f, "address {:#x} is past the end of the file", address)
+            }
+            Self::TooManyLinks { path } => {
+                write!(f, "too many links were followed to reach {:?}", path)
+            }
+            Self::ExternalLinkDenied { file } => {
+                write!(f, "following an external link to {:?} is not allowed", file)
+            }
+            Self::ExternalFileDenied { file } => write!(
+                f,
+                "reading {:?} outside the directory that it is looked for in is not allowed",
+                file
+            ),
+            Self::LimitExceeded {
+                limit,
+                value,
+                maximum,
+            } => write!(f, "{} of {} is over the limit of {}", limit, value, maximum),
         }
     }
 }
 
+// The message is all that `unwrap` and `expect` need to show
 impl std::fmt::Debug for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self {
-            Self::Io(e) => write!(f, "{}", e),
-            Self::Parse(e) => write!(f, "{}", e),
+        std::fmt::Display::fmt(self, f)
+    }
+}
+
+impl Error {
+    /// A parse error with no location, for structures that parsed but do not make sense
+    pub(crate) fn parse<S: Into<String>>(reason: S) -> Self {
+        Self::Parse {
+            reason: reason.into(),
+            offset: None,
+            context: Vec::new(),
+            path: None,
         }
     }
+
+    /// Turn an error from parsing part of `contents` into a parse error that records the address
+    /// where it happened
+    pub(crate) fn nom(contents: &Contents, e: nom::Err<nom::error::VerboseError<&[u8]>>) -> Self {
+        Self::from_nom(e, |input| contents.address_of(input))
+    }
+
+    fn from_nom(
+        e: nom::Err<nom::error::VerboseError<&[u8]>>,
+        address_of: impl Fn(&[u8]) -> Option<u64>,
+    ) -> Self {
+        use nom::error::{ErrorKind, VerboseErrorKind};
+        let errors = match e {
+            nom::Err::Incomplete(_) => {
+                return Self::parse("the file ends in the middle of a structure");
+            }
+            nom::Err::Error(e) | nom::Err::Failure(e) => e.errors,
+        };
+        // The first error is where parsing stopped, and contexts are added as it unwinds
+        let offset = errors.first().and_then(|(input, _)| address_of(input));
+        let reason = match errors.first().map(|(_, kind)| kind) {
+            Some(VerboseErrorKind::Nom(ErrorKind::Tag)) => {
+                String::from("unexpected signature, version or type")
+            }
+            Some(VerboseErrorKind::Nom(ErrorKind::Eof)) => String::from("sizes are inconsistent"),
+            Some(VerboseErrorKind::Nom(kind)) => format!("{:?} failed", kind),
+            Some(VerboseErrorKind::Char(c)) => format!("expected {:?}", c),
+            Some(VerboseErrorKind::Context(context)) => format!("invalid {}", context),
+            None => String::from("unknown error"),
+        };
+        let mut context = errors
+            .iter()
+            .filter_map(|(_, kind)| match kind {
+                VerboseErrorKind::Context(context) => Some(*context),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        context.reverse();
+        Self::Parse {
+            reason,
+            offset,
+            context,
+            path: None,
+        }
+    }
+
+    /// Record that this error happened while reading the object at `path`, unless it already
+    /// names a more specific object
+    pub(crate) fn in_object(mut self, object_path: &str) -> Self {
+        if let Self::Parse { path, .. } = &mut self {
+            if path.is_none() {
+                *path = Some(object_path.to_string());
+            }
+        }
+        self
+    }
 }
 
 impl std::error::Error for Error {}
@@ -31,17 +213,9 @@ impl From<std::io::Error> for Error {
 }
 
 impl From<nom::Err<nom::error::VerboseError<&[u8]>>> for Error {
+    /// Convert an error from parsing a slice that is not known to be part of the file, so that
+    /// the offset cannot be found
     fn from(e: nom::Err<nom::error::VerboseError<&[u8]>>) -> Self {
-        match e {
-            nom::Err::Incomplete(needed) => Self::Parse(format!("{:?}", needed)),
-            nom::Err::Error(e) | nom::Err::Failure(e) => {
-                let mut trace = String::from("\n");
-                for (_, reason) in e.errors {
-                    trace.extend(format!("    {:?}\n", reason).chars());
-                }
-                trace.pop();
-                Self::Parse(trace)
-            }
-        }
+        Self::from_nom(e, |_| None)
     }
 }
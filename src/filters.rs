@@ -0,0 +1,169 @@
+use crate::{parse, Error};
+use std::borrow::Cow;
+use std::io::Read;
+
+/// One stage of the filter pipeline that is applied to every chunk of a dataset
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Filter {
+    id: u16,
+    name: Option<String>,
+    optional: bool,
+    client_data: Vec<u32>,
+}
+
+impl Filter {
+    /// The identifier of the gzip/zlib compression filter
+    pub const DEFLATE: u16 = 1;
+    /// The identifier of the byte shuffle filter
+    pub const SHUFFLE: u16 = 2;
+    /// The identifier of the Fletcher32 checksum filter
+    pub const FLETCHER32: u16 = 3;
+    /// The identifier of the SZIP compression filter
+    pub const SZIP: u16 = 4;
+    /// The identifier of the N-bit compression filter
+    pub const NBIT: u16 = 5;
+    /// The identifier of the scale-offset compression filter
+    pub const SCALEOFFSET: u16 = 6;
+
+    pub(crate) fn from(parsed: parse::header::Filter) -> Self {
+        Self {
+            id: parsed.id,
+            name: parsed.name,
+            optional: parsed.flags & 0b1 != 0,
+            client_data: parsed.client_data,
+        }
+    }
+
+    /// The filter identifier, as registered with The HDF Group
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    /// The name that was stored with the filter, if any
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Whether the writer was permitted to skip this filter for a chunk
+    pub fn is_optional(&self) -> bool {
+        self.optional
+    }
+
+    /// The parameters that were passed to the filter when the dataset was created
+    pub fn client_data(&self) -> &[u32] {
+        &self.client_data
+    }
+}
+
+/// Undo the filter pipeline for one chunk
+///
+/// Filters are applied in order when writing, so they are reversed here. Bit `n` of
+/// `filter_mask` is set when filter `n` was skipped for this chunk. Decompression stops once the
+/// output is longer than `max_size`, so the caller must check the length of the result.
+pub(crate) fn decode<'a>(
+    filters: &[Filter],
+    filter_mask: u32,
+    mut data: Cow<'a, [u8]>,
+    element_size: usize,
+    max_size: usize,
+) -> Result<Cow<'a, [u8]>, Error> {
+    for (n, filter) in filters.iter().enumerate().rev() {
+        if n < 32 && filter_mask & (1 << n) != 0 {
+            continue;
+        }
+        data = Cow::Owned(match filter.id {
+            Filter::DEFLATE => inflate(&data, max_size)?,
+            Filter::SHUFFLE => {
+                let size = filter
+                    .client_data
+                    .first()
+                    .map(|s| *s as usize)
+                    .unwrap_or(element_size);
+                unshuffle(&data, size)
+            }
+            Filter::FLETCHER32 => verify_fletcher32(&data)?,
+            _ => {
+                return Err(Error::Unsupported {
+                    feature: match &filter.name {
+                        Some(name) => format!("filter {} ({})", filter.id, name),
+                        None => format!("filter {}", filter.id),
+                    },
+                })
+            }
+        });
+    }
+    Ok(data)
+}
+
+fn inflate(data: &[u8], max_size: usize) -> Result<Vec<u8>, Error> {
+    let mut decoded = Vec::with_capacity(std::cmp::min(data.len() * 2, max_size));
+    flate2::read::ZlibDecoder::new(data)
+        .take((max_size as u64).saturating_add(1))
+        .read_to_end(&mut decoded)?;
+    Ok(decoded)
+}
+
+/// The shuffle filter stores the first byte of every element, then the second byte of every
+/// element, and so on. Any bytes left over after the last whole element are stored as-is.
+fn unshuffle(data: &[u8], element_size: usize) -> Vec<u8> {
+    let elements = data.len() / element_size.max(1);
+    if element_size <= 1 || elements == 0 {
+        return data.to_vec();
+    }
+    let mut unshuffled = vec![0; data.len()];
+    for (byte, plane) in data.chunks_exact(elements).take(element_size).enumerate() {
+        for (element, value) in plane.iter().enumerate() {
+            unshuffled[element * element_size + byte] = *value;
+        }
+    }
+    let whole = elements * element_size;
+    unshuffled[whole..].copy_from_slice(&data[whole..]);
+    unshuffled
+}
+
+fn verify_fletcher32(data: &[u8]) -> Result<Vec<u8>, Error> {
+    if data.len() < 4 {
+        return Err(Error::parse(
+            "chunk is too small to contain a Fletcher32 checksum",
+        ));
+    }
+    let (data, stored) = data.split_at(data.len() - 4);
+    let stored = u32::from_le_bytes([stored[0], stored[1], stored[2], stored[3]]);
+    let computed = fletcher32(data);
+    // Versions of the reference library before 1.6.3 wrote the checksum with the bytes of each
+    // half swapped, and it still accepts either form
+    let reversed = ((computed & 0x00ff_00ff) << 8) | ((computed & 0xff00_ff00) >> 8);
+    if stored != computed && stored != reversed {
+        return Err(Error::parse(format!(
+            "Fletcher32 checksum mismatch: stored {:#010x}, computed {:#010x}",
+            stored, computed
+        )));
+    }
+    Ok(data.to_vec())
+}
+
+fn fletcher32(data: &[u8]) -> u32 {
+    let mut sum1: u32 = 0;
+    let mut sum2: u32 = 0;
+    let words = data.chunks_exact(2);
+    let trailing = words.remainder().first().copied();
+    let words = words.collect::<Vec<_>>();
+    // Reduce often enough that the sums cannot overflow
+    for block in words.chunks(360) {
+        for word in block {
+            sum1 += u32::from(word[0]) << 8 | u32::from(word[1]);
+            sum2 += sum1;
+        }
+        sum1 = (sum1 & 0xffff) + (sum1 >> 16);
+        sum2 = (sum2 & 0xffff) + (sum2 >> 16);
+    }
+    if let Some(byte) = trailing {
+        sum1 += u32::from(byte) << 8;
+        sum2 += sum1;
+        sum1 = (sum1 & 0xffff) + (sum1 >> 16);
+        sum2 = (sum2 & 0xffff) + (sum2 >> 16);
+    }
+    sum1 = (sum1 & 0xffff) + (sum1 >> 16);
+    sum2 = (sum2 & 0xffff) + (sum2 >> 16);
+    sum2 << 16 | sum1
+}
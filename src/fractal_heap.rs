@@ -0,0 +1,171 @@
+use crate::storage::Contents;
+use crate::{parse, Error};
+use std::convert::TryFrom;
+
+/// A fractal heap, which holds variable-sized objects that are found by their heap ID
+///
+/// Objects are either managed, in which case they live in the direct blocks of a doubling table,
+/// or tiny, in which case they are stored in the heap ID itself.
+pub(crate) struct FractalHeap<'a> {
+    contents: &'a Contents,
+    header: parse::FractalHeapHeader,
+}
+
+impl<'a> FractalHeap<'a> {
+    pub(crate) fn read(contents: &'a Contents, address: u64) -> Result<Self, Error> {
+        let header = parse::fractal_heap_header(contents.bytes_from(address)?)
+            .map_err(|e| Error::nom(contents, e))?
+            .1;
+        Ok(Self { contents, header })
+    }
+
+    /// Look up an object by its heap ID
+    pub(crate) fn object(&self, id: &'a [u8]) -> Result<&'a [u8], Error> {
+        let first = *id
+            .first()
+            .ok_or_else(|| Error::parse("empty fractal heap ID"))?;
+        if first >> 6 != 0 {
+            return Err(Error::Unsupported {
+                feature: format!("fractal heap ID version {}", first >> 6),
+            });
+        }
+        match first >> 4 & 0b11 {
+            0 => {
+                let offset_size = self.offset_size();
+                let length_size = std::cmp::min(
+                    (log2(self.header.max_direct_block_size) as usize).div_ceil(8),
+                    log2(u64::from(self.header.max_managed_object_size)) as usize / 8 + 1,
+                );
+                let field = |start: usize, len: usize| {
+                    id.get(start..start + len)
+                        .map(|bytes| bytes.iter().rev().fold(0, |v, b| v << 8 | u64::from(*b)))
+                        .ok_or_else(|| Error::parse("fractal heap ID is too short"))
+                };
+                let offset = field(1, offset_size)?;
+                let length = field(1 + offset_size, length_size)?;
+                self.managed(offset, length)
+            }
+            1 => Err(Error::Unsupported {
+                feature: String::from("huge fractal heap objects"),
+            }),
+            2 => {
+                // Tiny objects store their length minus one, in one more byte if the IDs are long
+                let (length, start) = if self.header.heap_id_length <= 18 {
+                    (usize::from(first & 0x0F) + 1, 1)
+                } else {
+                    let low = *id.get(1).unwrap_or(&0);
+                    ((usize::from(first & 0x0F) << 8 | usize::from(low)) + 1, 2)
+                };
+                id.get(start..start + length)
+                    .ok_or_else(|| Error::parse("tiny object is too long for its ID"))
+            }
+            _ => Err(Error::parse("unknown fractal heap ID type")),
+        }
+    }
+
+    /// The number of bytes that store an offset into the heap
+    fn offset_size(&self) -> usize {
+        usize::from(self.header.max_heap_size).div_ceil(8)
+    }
+
+    fn managed(&self, offset: u64, length: u64) -> Result<&'a [u8], Error> {
+        let header = &self.header;
+        if header.io_filters_length != 0 {
+            return Err(Error::Unsupported {
+                feature: String::from("filtered fractal heaps"),
+            });
+        }
+        let (address, block_offset) = if header.current_rows == 0 {
+            // The root is a single direct block
+            (header.root_block_address, 0)
+        } else {
+            self.find_direct_block(header.root_block_address, header.current_rows, 0, offset)?
+        };
+        parse::fractal_heap_direct_block(self.contents.bytes_from(address)?, self.offset_size())
+            .map_err(|e| Error::nom(self.contents, e))?;
+        // Offsets within a direct block count from the start of its header
+        let start = offset
+            .checked_sub(block_offset)
+            .and_then(|offset| address.checked_add(offset))
+            .ok_or(Error::OutOfBounds { address })?;
+        // Direct blocks are written whole, so objects are never split between member files
+        usize::try_from(length)
+            .ok()
+            .and_then(|length| self.contents.bytes_from(start).ok()?.get(..length))
+            .ok_or(Error::OutOfBounds { address: start })
+    }
+
+    /// Find the direct block that holds `offset` below the indirect block at `address`, which
+    /// has `rows` rows and begins at `block_offset` in the heap, and return the direct block's
+    /// address and offset
+    fn find_direct_block(
+        &self,
+        address: u64,
+        rows: u16,
+        block_offset: u64,
+        offset: u64,
+    ) -> Result<(u64, u64), Error> {
+        let header = &self.header;
+        let width = usize::from(header.table_width);
+        let starting_bits = log2(header.starting_block_size);
+        let max_direct_rows = (log2(header.max_direct_block_size) + 2)
+            .checked_sub(starting_bits)
+            .ok_or_else(|| {
+                Error::parse("fractal heap blocks start out larger than direct blocks can be")
+            })? as usize;
+        let direct_rows = std::cmp::min(usize::from(rows), max_direct_rows);
+        let indirect_rows = usize::from(rows) - direct_rows;
+        let children = parse::fractal_heap_indirect_block(
+            self.contents.bytes_from(address)?,
+            self.offset_size(),
+            direct_rows * width,
+            indirect_rows * width,
+        )
+        .map_err(|e| Error::nom(self.contents, e))?
+        .1;
+
+        let mut position = block_offset;
+        for (i, child) in children.into_iter().enumerate() {
+            let row = i / width;
+            // The first two rows have blocks of the starting size, and each row after that
+            // doubles it
+            let size = match row {
+                0 => Some(header.starting_block_size),
+                _ => 1u64
+                    .checked_shl(row as u32 - 1)
+                    .and_then(|scale| header.starting_block_size.checked_mul(scale)),
+            };
+            let end = size.and_then(|size| position.checked_add(size).map(|end| (size, end)));
+            let (size, end) = match end {
+                Some(end) => end,
+                None => break,
+            };
+            if offset < end {
+                if child == parse::UNDEFINED_ADDRESS {
+                    break;
+                }
+                if row < max_direct_rows {
+                    return Ok((child, position));
+                }
+                let child_rows = (log2(size) + 1)
+                    .checked_sub(starting_bits + log2(width as u64))
+                    .filter(|child_rows| *child_rows < u32::from(rows));
+                let child_rows = match child_rows {
+                    Some(child_rows) => child_rows,
+                    None => break,
+                };
+                return self.find_direct_block(child, child_rows as u16, position, offset);
+            }
+            position = end;
+        }
+        Err(Error::parse(format!(
+            "fractal heap offset {} is not in any block",
+            offset
+        )))
+    }
+}
+
+/// The base 2 logarithm, rounded down
+fn log2(n: u64) -> u32 {
+    63 - n.max(1).leading_zeros()
+}
@@ -0,0 +1,617 @@
+use crate::attribute::{Attributes, ObjectAttributes};
+use crate::btree;
+use crate::dataset::{Dataset, DatasetHandle};
+use crate::datatype::{CommittedDatatype, DatatypeHandle};
+use crate::fractal_heap::FractalHeap;
+use crate::link::{Link, LinkHandle, MAX_LINK_HOPS};
+use crate::storage::Contents;
+use crate::walk::{ObjectInfo, ObjectKind, Walk};
+use crate::{object_header_messages, parse, Error, Hdf5File};
+use std::collections::BTreeMap; // Currently use BTreeMap just to get sorted Debug output
+use std::sync::atomic::Ordering;
+use std::sync::OnceLock;
+
+#[derive(Debug)]
+pub(crate) struct Group {
+    pub(crate) address: u64,
+    /// The path that this group was first reached by, which errors refer to it by
+    path: String,
+    /// How many groups deep below the root group this group is
+    depth: usize,
+    pub(crate) attributes: ObjectAttributes,
+    /// Where the group lists its members, which are only read when they are first needed
+    symbol_table: Option<parse::header::SymbolTable>,
+    link_info: Option<parse::header::LinkInfo>,
+    link_messages: Vec<parse::header::Link>,
+    members: OnceLock<BTreeMap<String, Entry>>,
+}
+
+/// A member of a group, as it is named by the group
+#[derive(Debug)]
+pub(crate) enum Entry {
+    /// A hard link to the object whose header is at `address`, which is read when it is first
+    /// looked up
+    Object {
+        address: u64,
+        object: OnceLock<Box<Object>>,
+    },
+    /// A soft, external or user-defined link, which is only followed when it is looked up
+    Link(Link),
+}
+
+/// An object that has been read from its object header
+#[derive(Debug)]
+pub(crate) enum Object {
+    Group(Group),
+    Dataset(Dataset),
+    Datatype(CommittedDatatype),
+}
+
+impl Group {
+    /// Build the group at `path`, which is `depth` groups below the root group, from the messages
+    /// in its object header, which is at `address`
+    ///
+    /// Its attributes and members are read when they are first needed.
+    pub(crate) fn from(
+        path: String,
+        depth: usize,
+        address: u64,
+        messages: Vec<parse::header::Message>,
+    ) -> Result<Self, Error> {
+        use parse::header::Message;
+        let mut symbol_table = None;
+        let mut link_info = None;
+        let mut link_messages = Vec::new();
+        let mut attributes = Vec::new();
+        let mut attribute_info = None;
+        for message in messages {
+            match message {
+                Message::SymbolTable(m) => symbol_table = Some(m),
+                Message::LinkInfo(m) => link_info = Some(m),
+                Message::Link(m) => link_messages.push(m),
+                Message::Attribute(m) => attributes.push(m),
+                Message::AttributeInfo(m) => attribute_info = Some(m),
+                _ => {}
+            }
+        }
+        Ok(Self {
+            address,
+            path,
+            depth,
+            attributes: ObjectAttributes::new(attributes, attribute_info),
+            symbol_table,
+            link_info,
+            link_messages,
+            members: OnceLock::new(),
+        })
+    }
+
+    /// The path of the member of this group called `name`
+    fn member_path(&self, name: &str) -> String {
+        match self.path.as_str() {
+            "/" => format!("/{}", name),
+            path => format!("{}/{}", path, name),
+        }
+    }
+}
+
+/// Links have no attributes of their own
+static NO_ATTRIBUTES: ObjectAttributes = ObjectAttributes::new(Vec::new(), None);
+
+/// The names along a path, where empty and `.` components are skipped
+fn components(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/')
+        .filter(|name| !name.is_empty() && *name != ".")
+}
+
+/// A member of a group by name, or the error from reading it
+pub(crate) type MemberResult<'a> = (&'a str, Result<Member<'a>, Error>);
+
+/// A group in an opened HDF5 file, which holds datasets and other groups by name
+#[derive(Clone, Copy)]
+pub struct GroupHandle<'a> {
+    file: &'a Hdf5File,
+    group: &'a Group,
+}
+
+/// An object that is a member of a group
+#[derive(Clone, Copy)]
+pub enum Member<'a> {
+    Group(GroupHandle<'a>),
+    Dataset(DatasetHandle<'a>),
+    Datatype(DatatypeHandle<'a>),
+    /// A link that has not been followed, which is only produced when iterating over a group
+    Link(LinkHandle<'a>),
+}
+
+impl<'a> Member<'a> {
+    /// Whether this member is a group, a dataset, a committed datatype or a link
+    pub fn kind(&self) -> ObjectKind {
+        match self {
+            Self::Group(_) => ObjectKind::Group,
+            Self::Dataset(_) => ObjectKind::Dataset,
+            Self::Datatype(_) => ObjectKind::Datatype,
+            Self::Link(_) => ObjectKind::Link,
+        }
+    }
+
+    /// The attributes attached to this member, which is always empty for links
+    pub fn attrs(&self) -> Attributes<'a> {
+        match self {
+            Self::Group(group) => group.attrs(),
+            Self::Dataset(dataset) => dataset.attrs(),
+            Self::Datatype(datatype) => datatype.attrs(),
+            Self::Link(link) => Attributes::new(link.file(), &NO_ATTRIBUTES),
+        }
+    }
+
+    /// The address of this member's object header, which identifies it within the file, or
+    /// `None` for links
+    pub(crate) fn address(&self) -> Option<u64> {
+        match self {
+            Self::Group(group) => Some(group.address()),
+            Self::Dataset(dataset) => Some(dataset.address()),
+            Self::Datatype(datatype) => Some(datatype.address()),
+            Self::Link(_) => None,
+        }
+    }
+}
+
+impl<'a> GroupHandle<'a> {
+    pub(crate) fn new(file: &'a Hdf5File, group: &'a Group) -> Self {
+        Self { file, group }
+    }
+
+    pub(crate) fn address(&self) -> u64 {
+        self.group.address
+    }
+
+    pub(crate) fn file(&self) -> &'a Hdf5File {
+        self.file
+    }
+
+    /// The path that this group was first reached by
+    pub(crate) fn path(&self) -> &'a str {
+        &self.group.path
+    }
+
+    /// The attributes attached to this group
+    pub fn attrs(&self) -> Attributes<'a> {
+        Attributes::new(self.file, &self.group.attributes)
+    }
+
+    /// Paths that begin with `/` are looked up from the root group, and other paths from this one
+    fn start(&self, path: &str) -> Self {
+        if path.starts_with('/') {
+            self.file.root()
+        } else {
+            *self
+        }
+    }
+
+    /// The members of this group by name, which are read the first time they are needed
+    fn entries(&self) -> Result<&'a BTreeMap<String, Entry>, Error> {
+        let group = self.group;
+        if let Some(entries) = group.members.get() {
+            return Ok(entries);
+        }
+        let entries = read_entries(self.file, group).map_err(|e| e.in_object(&group.path))?;
+        Ok(group.members.get_or_init(|| entries))
+    }
+
+    /// The member that `entry` names, where links are followed if `hops` is given, and are
+    /// otherwise produced as they are
+    fn entry_member(
+        &self,
+        name: &str,
+        entry: &'a Entry,
+        hops: Option<&mut usize>,
+    ) -> Result<Member<'a>, Error> {
+        let (address, object) = match entry {
+            Entry::Object { address, object } => (*address, object),
+            Entry::Link(link) => {
+                let link = LinkHandle::new(*self, link);
+                return match hops {
+                    Some(hops) => link.follow_within(hops),
+                    None => Ok(Member::Link(link)),
+                };
+            }
+        };
+        let object = match object.get() {
+            Some(object) => object,
+            None => {
+                let loaded = read_object(self.file, self.group, name, address)?;
+                object.get_or_init(|| Box::new(loaded))
+            }
+        };
+        Ok(match &**object {
+            Object::Group(group) => Member::Group(Self::new(self.file, group)),
+            Object::Dataset(dataset) => Member::Dataset(DatasetHandle::new(self.file, dataset)),
+            Object::Datatype(datatype) => {
+                Member::Datatype(DatatypeHandle::new(self.file, datatype))
+            }
+        })
+    }
+
+    /// Look up the member called `name`, following it if it is a link
+    fn child(&self, name: &str, hops: &mut usize) -> Result<Option<Member<'a>>, Error> {
+        match self.entries()?.get(name) {
+            Some(entry) => self.entry_member(name, entry, Some(hops)).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Follow `names` from this group, where `path` is the whole path that is being looked up
+    fn follow<'p>(
+        &self,
+        names: impl IntoIterator<Item = &'p str>,
+        path: &str,
+        hops: &mut usize,
+    ) -> Result<Member<'a>, Error> {
+        let not_found = || Error::NotFound {
+            path: path.to_string(),
+        };
+        let mut member = Member::Group(*self);
+        for name in names {
+            member = match member {
+                Member::Group(group) => group.child(name, hops)?.ok_or_else(not_found)?,
+                _ => return Err(not_found()),
+            };
+        }
+        Ok(member)
+    }
+
+    /// Look up `path` from this group, where `hops` is how many more links may be followed in
+    /// the whole lookup, and is counted down for each one that is
+    pub(crate) fn resolve(&self, path: &str, hops: &mut usize) -> Result<Member<'a>, Error> {
+        self.start(path).follow(components(path), path, hops)
+    }
+
+    /// Look up the provided path to a group
+    pub fn group(&self, path: &str) -> Result<GroupHandle<'a>, Error> {
+        match self.member(path)? {
+            Member::Group(group) => Ok(group),
+            _ => Err(Error::NotFound {
+                path: path.to_string(),
+            }),
+        }
+    }
+
+    /// Look up the provided path to a dataset
+    pub fn dataset(&self, path: &str) -> Result<DatasetHandle<'a>, Error> {
+        match self.member(path)? {
+            Member::Dataset(dataset) => Ok(dataset),
+            _ => Err(Error::NotFound {
+                path: path.to_string(),
+            }),
+        }
+    }
+
+    /// Look up the provided path to a committed datatype
+    pub fn datatype(&self, path: &str) -> Result<DatatypeHandle<'a>, Error> {
+        match self.member(path)? {
+            Member::Datatype(datatype) => Ok(datatype),
+            _ => Err(Error::NotFound {
+                path: path.to_string(),
+            }),
+        }
+    }
+
+    /// Look up the provided path to a group, dataset or committed datatype
+    ///
+    /// Soft and external links along the path, including at its end, are followed.
+    pub fn member(&self, path: &str) -> Result<Member<'a>, Error> {
+        let mut hops = MAX_LINK_HOPS;
+        self.resolve(path, &mut hops)
+    }
+
+    /// Look up the provided path to a link, without following the link at its end
+    pub fn link(&self, path: &str) -> Result<LinkHandle<'a>, Error> {
+        let not_found = || Error::NotFound {
+            path: path.to_string(),
+        };
+        let mut names = components(path).collect::<Vec<_>>();
+        let name = names.pop().ok_or_else(not_found)?;
+        let mut hops = MAX_LINK_HOPS;
+        match self.start(path).follow(names, path, &mut hops)? {
+            Member::Group(parent) => match parent.entries()?.get(name) {
+                Some(Entry::Link(link)) => Ok(LinkHandle::new(parent, link)),
+                _ => Err(not_found()),
+            },
+            _ => Err(not_found()),
+        }
+    }
+
+    /// Whether the provided path leads to a group, dataset or committed datatype
+    pub fn contains(&self, path: &str) -> bool {
+        self.member(path).is_ok()
+    }
+
+    /// The groups, datasets, committed datatypes and links in this group, sorted by name
+    ///
+    /// Links are produced as they are, rather than followed. A member that cannot be read is
+    /// produced as an error in its place, so that one damaged object neither hides the rest nor
+    /// goes unnoticed. If the members cannot be listed at all, that error is the only item.
+    pub fn members(&self) -> impl Iterator<Item = Result<(&'a str, Member<'a>), Error>> {
+        let group = *self;
+        let (entries, error) = match self.entries() {
+            Ok(entries) => (Some(entries), None),
+            Err(e) => (None, Some(e)),
+        };
+        error.map(Err).into_iter().chain(
+            entries.into_iter().flatten().map(move |(name, entry)| {
+                Ok((name.as_str(), group.entry_member(name, entry, None)?))
+            }),
+        )
+    }
+
+    /// The members of this group as [`GroupHandle::members`] produces them, or the first error
+    /// from reading them
+    pub fn try_members(&self) -> Result<Vec<(&'a str, Member<'a>)>, Error> {
+        self.members().collect()
+    }
+
+    /// Every member of this group with the result of reading it, as links are produced by
+    /// [`GroupHandle::members`], or the error from listing them
+    pub(crate) fn member_results(&self) -> Result<Vec<MemberResult<'a>>, Error> {
+        Ok(self
+            .entries()?
+            .iter()
+            .map(|(name, entry)| (name.as_str(), self.entry_member(name, entry, None)))
+            .collect())
+    }
+
+    /// The groups in this group, sorted by name
+    ///
+    /// Members that cannot be read are produced as errors, as [`GroupHandle::members`] produces
+    /// them, since they may be groups.
+    pub fn groups(&self) -> impl Iterator<Item = Result<(&'a str, GroupHandle<'a>), Error>> {
+        self.members().filter_map(|member| match member {
+            Ok((name, Member::Group(group))) => Some(Ok((name, group))),
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        })
+    }
+
+    /// The datasets in this group, sorted by name
+    ///
+    /// Members that cannot be read are produced as errors, as for [`GroupHandle::groups`].
+    pub fn datasets(&self) -> impl Iterator<Item = Result<(&'a str, DatasetHandle<'a>), Error>> {
+        self.members().filter_map(|member| match member {
+            Ok((name, Member::Dataset(dataset))) => Some(Ok((name, dataset))),
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        })
+    }
+
+    /// The committed datatypes in this group, sorted by name
+    ///
+    /// Members that cannot be read are produced as errors, as for [`GroupHandle::groups`].
+    pub fn datatypes(&self) -> impl Iterator<Item = Result<(&'a str, DatatypeHandle<'a>), Error>> {
+        self.members().filter_map(|member| match member {
+            Ok((name, Member::Datatype(datatype))) => Some(Ok((name, datatype))),
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        })
+    }
+
+    /// The soft, external and user-defined links in this group, sorted by name
+    ///
+    /// Links are read without being followed, so the only errors are from listing the members.
+    pub fn links(&self) -> impl Iterator<Item = Result<(&'a str, LinkHandle<'a>), Error>> {
+        let (entries, error) = match self.entries() {
+            Ok(entries) => (Some(entries), None),
+            Err(e) => (None, Some(e)),
+        };
+        let group = *self;
+        error
+            .map(Err)
+            .into_iter()
+            .chain(
+                entries
+                    .into_iter()
+                    .flatten()
+                    .filter_map(move |(name, entry)| match entry {
+                        Entry::Link(link) => {
+                            Some(Ok((name.as_str(), LinkHandle::new(group, link))))
+                        }
+                        Entry::Object { .. } => None,
+                    }),
+            )
+    }
+
+    /// Iterate over every group, dataset and link below this group, depth first
+    ///
+    /// Links are produced without being followed, and objects that cannot be read are produced
+    /// as errors.
+    pub fn walk(&self) -> Walk<'a> {
+        Walk::new(*self)
+    }
+
+    /// Call `f` with every group, dataset and link below this group, depth first, stopping at
+    /// the first object that cannot be read
+    pub fn visit(&self, mut f: impl FnMut(ObjectInfo<'a>)) -> Result<(), Error> {
+        self.walk().try_for_each(|object| object.map(&mut f))
+    }
+}
+
+/// Read the names and targets of the members of `group`
+///
+/// Groups in the original format list their members in a symbol table, and later groups hold
+/// link messages, which move to a fractal heap once there are too many for the object header.
+fn read_entries(file: &Hdf5File, group: &Group) -> Result<BTreeMap<String, Entry>, Error> {
+    use parse::header::LinkTarget;
+    let contents = file.contents();
+    let mut links = group
+        .link_messages
+        .iter()
+        .map(|link| (link.name.clone(), link.target.clone()))
+        .collect::<Vec<_>>();
+    if let Some(symbol_table) = &group.symbol_table {
+        links.extend(symbol_table_links(contents, symbol_table)?);
+    }
+    if let Some(link_info) = &group.link_info {
+        links.extend(dense_links(contents, link_info)?);
+    }
+    let objects = file.objects.fetch_add(links.len(), Ordering::Relaxed);
+    file.options
+        .limits
+        .check_objects(objects.saturating_add(links.len()))?;
+
+    let mut entries = BTreeMap::new();
+    for (name, target) in links {
+        let entry = match target {
+            LinkTarget::Hard(address) => Entry::Object {
+                address,
+                object: OnceLock::new(),
+            },
+            LinkTarget::Soft(path) => Entry::Link(Link::Soft(path)),
+            LinkTarget::External { file, path } => Entry::Link(Link::External {
+                file,
+                path,
+                opened: OnceLock::new(),
+            }),
+            LinkTarget::UserDefined { ty, data } => Entry::Link(Link::UserDefined { ty, data }),
+        };
+        entries.insert(name, entry);
+    }
+    Ok(entries)
+}
+
+/// Read the object at `address`, which is the member of `parent` called `name`
+fn read_object(file: &Hdf5File, parent: &Group, name: &str, address: u64) -> Result<Object, Error> {
+    use parse::header::Message;
+    let path = parent.member_path(name);
+    let in_object = |e: Error| e.in_object(&path);
+    let messages =
+        object_header_messages(file.contents(), &file.shared, address).map_err(in_object)?;
+
+    // Only groups have a symbol table or link info, and only datasets have a layout, so an
+    // object with a datatype and neither is a committed datatype
+    let is_group = messages
+        .iter()
+        .any(|m| matches!(m, Message::SymbolTable(_) | Message::LinkInfo(_)));
+    let is_dataset = messages.iter().any(|m| matches!(m, Message::DataLayout(_)));
+    let is_datatype = messages.iter().any(|m| matches!(m, Message::DataType(_)));
+    if is_group {
+        let depth = parent.depth + 1;
+        file.options.limits.check_depth(depth)?;
+        Ok(Object::Group(Group::from(path, depth, address, messages)?))
+    } else if is_dataset {
+        Ok(Object::Dataset(
+            Dataset::from(address, messages).map_err(in_object)?,
+        ))
+    } else if is_datatype {
+        Ok(Object::Datatype(
+            CommittedDatatype::from(address, messages).map_err(in_object)?,
+        ))
+    } else {
+        Err(Error::Unsupported {
+            feature: format!("the kind of object at {:?}", path),
+        })
+    }
+}
+
+/// The name and target of each entry in a symbol table, where entries are hard links unless they
+/// cache a soft link's path
+fn symbol_table_links(
+    contents: &Contents,
+    symbol_table: &parse::header::SymbolTable,
+) -> Result<Vec<(String, parse::header::LinkTarget)>, Error> {
+    use parse::header::LinkTarget;
+    let name_heap = parse::local_heap(contents.bytes_from(symbol_table.local_heap_address)?, 8, 8)
+        .map_err(|e| Error::nom(contents, e))?
+        .1;
+    let heap_string = |offset: u64| {
+        let address = offset
+            .checked_add(name_heap.address_of_data_segment)
+            .ok_or(Error::OutOfBounds { address: offset })?;
+        let bytes = contents.bytes_from(address)?;
+        let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+        Ok::<_, Error>(String::from_utf8_lossy(&bytes[..end]).into_owned())
+    };
+
+    let mut entries = Vec::new();
+    for node_address in symbol_table_nodes(contents, symbol_table.btree_address)? {
+        let table = parse::symbol_table(contents.bytes_from(node_address)?, 8)
+            .map_err(|e| Error::nom(contents, e))?
+            .1;
+        for object in &table.entries {
+            let name = heap_string(object.link_name_offset)?;
+            // A soft link keeps the offset of its path in the name heap in the first four bytes
+            // of the scratch pad
+            let target = if object.cache_type == 2 {
+                LinkTarget::Soft(heap_string(object.address_of_btree & 0xffff_ffff)?)
+            } else {
+                LinkTarget::Hard(object.object_header_address)
+            };
+            entries.push((name, target));
+        }
+    }
+    Ok(entries)
+}
+
+/// Load the link messages that a group keeps in a fractal heap, indexed by a B-tree of names
+fn dense_links(
+    contents: &Contents,
+    info: &parse::header::LinkInfo,
+) -> Result<Vec<(String, parse::header::LinkTarget)>, Error> {
+    use parse::header::Message;
+    if info.fractal_heap_address == parse::UNDEFINED_ADDRESS {
+        return Ok(Vec::new());
+    }
+    let heap = FractalHeap::read(contents, info.fractal_heap_address)?;
+    let mut links = Vec::new();
+    for record in btree::records(contents, info.name_btree_address, btree::LINK_NAME_RECORD)? {
+        // Each record holds a hash of the name and then a heap ID
+        let id = record
+            .get(4..)
+            .filter(|id| !id.is_empty())
+            .ok_or_else(|| Error::parse("link name record is too short"))?;
+        if let (_, Message::Link(link)) =
+            parse::message(heap.object(id)?, 0x6).map_err(|e| Error::nom(contents, e))?
+        {
+            links.push((link.name, link.target));
+        }
+    }
+    Ok(links)
+}
+
+/// Walk a group B-tree and collect the addresses of the symbol table nodes at its leaves
+fn symbol_table_nodes(contents: &Contents, btree_address: u64) -> Result<Vec<u64>, Error> {
+    symbol_table_nodes_below(contents, btree_address, None)
+}
+
+/// Collect the symbol table nodes below the group B-tree node at `address`, which must be at a
+/// lower level than its parent's `parent_level` so that the walk always ends
+fn symbol_table_nodes_below(
+    contents: &Contents,
+    address: u64,
+    parent_level: Option<u8>,
+) -> Result<Vec<u64>, Error> {
+    let node = parse::hdf5_node(contents.bytes_from(address)?, 8)
+        .map_err(|e| Error::nom(contents, e))?
+        .1;
+    if parent_level.is_some_and(|level| node.node_level >= level) {
+        return Err(Error::parse(format!(
+            "group B-tree node at {:#x} is not below its parent",
+            address
+        )));
+    }
+    let children = node
+        .entries
+        .iter()
+        .map(|entry| entry.pointer_to_symbol_table);
+    if node.node_level == 0 {
+        return Ok(children.collect());
+    }
+    let mut addresses = Vec::new();
+    for child in children {
+        addresses.extend(symbol_table_nodes_below(
+            contents,
+            child,
+            Some(node.node_level),
+        )?);
+    }
+    Ok(addresses)
+}
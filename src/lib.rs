@@ -2,13 +2,58 @@
 //!
 //! This library does not intend to support all features of HDF5 either in the library or the
 //! specification.
-
-use std::collections::BTreeMap; // Currently use BTreeMap just to get sorted Debug output
-use std::path::Path;
-
+//!
+//! Files are parsed from memory: they are memory-mapped, held in a buffer, or kept in a
+//! [`Storage`]. There is no storage that reads from a `Read + Seek` source on demand, so
+//! [`Hdf5File::from_reader_buffered`] reads such a source into memory whole before parsing it, and
+//! a large file inside an archive takes as much memory as its size.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicUsize;
+use std::sync::{Arc, Mutex};
+
+mod attribute;
+mod btree;
+mod chunk_index;
+mod chunks;
+mod dataset;
+mod datatype;
+mod driver;
 mod error;
+mod filters;
+mod fractal_heap;
+mod group;
+mod link;
+mod options;
+// The parsed structures mirror the on-disk format, so not every field is consumed
+#[allow(dead_code)]
 mod parse;
+mod selection;
+mod storage;
+mod virtual_dataset;
+mod walk;
+pub use attribute::Attributes;
+pub use chunks::{Chunk, Chunks};
+pub use dataset::DatasetHandle;
+pub use datatype::DatatypeHandle;
 pub use error::Error;
+pub use filters::Filter;
+pub use group::{GroupHandle, Member};
+pub use link::{LinkHandle, LinkKind};
+pub use options::{Limits, OpenOptions};
+pub use selection::{Hyperslab, Indices};
+pub use storage::Storage;
+pub use walk::{ObjectInfo, ObjectKind, Walk};
+
+use dataset::Layout;
+use fractal_heap::FractalHeap;
+use group::Group;
+use parse::header::SharedMessageIndex;
+use storage::Contents;
 
 /// Convienence function for Hdf5File::read
 pub fn read<P: AsRef<Path>>(path: P) -> Result<Hdf5File, Error> {
@@ -18,160 +63,225 @@ pub fn read<P: AsRef<Path>>(path: P) -> Result<Hdf5File, Error> {
 /// An opened HDF5 file
 #[derive(Debug)]
 pub struct Hdf5File {
-    map: memmap::Mmap,
-    root_group: Group,
-}
-
-#[derive(Debug)]
-struct Group {
-    attributes: BTreeMap<String, Attribute>,
-    datasets: BTreeMap<String, Dataset>,
-    groups: BTreeMap<String, Group>,
+    // The member files that hold the file's bytes, and where the superblock is in them, which
+    // every address is relative to
+    contents: Contents,
+    // Groups below the root group, and the objects in them, are read when they are first looked
+    // up and kept from then on
+    root: Group,
+    shared: Vec<SharedMessageIndex>,
+    // The number of objects that have been found in groups so far, which is checked against the
+    // limit
+    objects: AtomicUsize,
+    // The files that virtual datasets in this file are mapped from, by where they were found,
+    // which are opened when they are first read from and kept from then on
+    virtual_sources: Mutex<HashMap<PathBuf, Arc<Hdf5File>>>,
+    // Where the file was opened from, which is where external links are looked for, unless it
+    // was opened from memory
+    path: Option<PathBuf>,
+    options: OpenOptions,
 }
 
-impl Group {
-    fn find_dataset(&self, dataset_path: &str) -> &Dataset {
-        let delim_index = dataset_path.find('/');
-        if let Some(i) = delim_index {
-            let (first, remaining) = dataset_path.split_at(i);
-            if let Some(d) = self.datasets.get(first) {
-                d
-            } else {
-                self.groups[first].find_dataset(&remaining[1..])
-            }
-        } else {
-            &self.datasets[dataset_path]
-        }
-    }
-
-    //fn find_group(&self, dataset_path: &str) -> &Dataset {}
+/// The kind of shape that a dataspace describes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataspaceKind {
+    /// Exactly one element, with no dimensions
+    Scalar,
+    /// No elements at all
+    Null,
+    /// A regular N-dimensional array of elements
+    Simple,
 }
 
-#[derive(Debug)]
-struct Attribute {
-    dtype: Hdf5Dtype,
+/// The shape of a dataset or attribute
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Dataspace {
+    kind: DataspaceKind,
     dimensions: Vec<u64>,
-    data: Vec<u8>,
+    max_dimensions: Vec<Option<u64>>,
 }
 
-impl Attribute {
-    fn from(parsed: parse::header::Attribute) -> Self {
+impl Dataspace {
+    fn from(parsed: parse::header::Dataspace) -> Self {
+        use parse::header::DataspaceKind as Kind;
+        let kind = match parsed.kind {
+            Kind::Scalar => DataspaceKind::Scalar,
+            Kind::Null => DataspaceKind::Null,
+            Kind::Simple => DataspaceKind::Simple,
+        };
+        // When no maximum is stored the maximum dimensions are the current dimensions, and
+        // u64::MAX is how the format spells an unlimited dimension
+        let dimensions = parsed.dimensions;
+        let max_dimensions = parsed
+            .max_dimensions
+            .unwrap_or_else(|| dimensions.clone())
+            .into_iter()
+            .map(|d| if d == u64::MAX { None } else { Some(d) })
+            .collect();
         Self {
-            dtype: Hdf5Dtype::from(parsed.datatype),
-            dimensions: parsed.dataspace.dimensions,
-            data: parsed.data,
+            kind,
+            dimensions,
+            max_dimensions,
         }
     }
-}
 
-#[derive(Debug)]
-struct Dataset {
-    dimensions: Vec<u64>,
-    dtype: Hdf5Dtype,
-    address: u64,
-    size: u64,
-    attributes: BTreeMap<String, Attribute>,
-}
+    /// Whether this dataspace is scalar, null, or simple
+    pub fn kind(&self) -> DataspaceKind {
+        self.kind
+    }
 
-impl Dataset {
-    fn from(messages: Vec<parse::header::Message>) -> Self {
-        use parse::header::Message;
-        let mut dimensions = None;
-        let mut dtype = None;
-        let mut address = None;
-        let mut size = None;
-        let mut attributes = BTreeMap::new();
-        for message in messages {
-            match message {
-                Message::DataLayout(m) => {
-                    address = Some(m.address);
-                    size = Some(m.size);
-                }
-                Message::DataType(m) => dtype = Some(Hdf5Dtype::from(m)),
-                Message::Dataspace(m) => dimensions = Some(m.dimensions),
-                Message::Attribute(m) => {
-                    attributes.insert(m.name.clone(), Attribute::from(m));
-                }
-                Message::DataStorageFillValue(_) => {}
-                Message::ObjectModificationTime(_) => {}
-                Message::Nil => {}
-                m => unimplemented!("Unexpected message for a Dataset {:?}", m),
-            }
+    /// The current size of each dimension, which is empty for scalar and null dataspaces
+    pub fn dims(&self) -> &[u64] {
+        &self.dimensions
+    }
+
+    /// The maximum size of each dimension, where `None` means the dimension is unlimited
+    pub fn max_dims(&self) -> &[Option<u64>] {
+        &self.max_dimensions
+    }
+
+    /// The total number of elements described by this dataspace
+    pub fn num_elements(&self) -> u64 {
+        match self.kind {
+            DataspaceKind::Scalar => 1,
+            DataspaceKind::Null => 0,
+            DataspaceKind::Simple => self.dimensions.iter().fold(1, |n, d| n.saturating_mul(*d)),
         }
+    }
 
-        let dimensions = dimensions.unwrap();
-        let dtype = dtype.unwrap();
-        let address = address.unwrap();
-        let size = size.unwrap();
+    /// The number of bytes that this dataspace's elements take up when each is `element_size`
+    /// bytes long
+    fn byte_size(&self, element_size: usize) -> Result<usize, Error> {
+        usize::try_from(self.num_elements())
+            .ok()
+            .and_then(|elements| elements.checked_mul(element_size))
+            .ok_or_else(|| {
+                Error::parse(format!(
+                    "{} elements of {} bytes do not fit in memory",
+                    self.num_elements(),
+                    element_size
+                ))
+            })
+    }
 
-        Self {
-            dimensions,
-            dtype,
-            address,
-            size,
-            attributes,
+    /// The shape of an array that holds this dataspace's elements, where a null dataspace is an
+    /// empty array, checked against the rank of `D`
+    #[cfg(feature = "ndarray")]
+    fn array_shape<D: ndarray::Dimension>(&self) -> Result<Vec<usize>, Error> {
+        let shape = match self.kind {
+            DataspaceKind::Null => vec![0],
+            _ => self.dimensions.iter().map(|d| *d as usize).collect(),
+        };
+        match D::NDIM {
+            Some(expected) if expected != shape.len() => Err(Error::RankMismatch {
+                expected,
+                found: shape.len(),
+            }),
+            _ => Ok(shape),
         }
     }
 }
 
 /// Identifies Rust types that this library can produce from HDF5 types
-pub trait FromHdf5: private::Sealed {
+pub trait FromHdf5: private::Sealed + Sized {
     fn from_types() -> &'static [Hdf5Dtype];
     // We need the first two fields for VlenString
     // so that we can convert the global heap ID
-    fn convert(file: &Hdf5File, dtype: Hdf5Dtype, data: &[u8]) -> Self;
+    fn convert(file: &Hdf5File, dtype: Hdf5Dtype, data: &[u8]) -> Result<Self, Error>;
 }
 
 mod private {
     pub trait Sealed {}
+    impl Sealed for i8 {}
+    impl Sealed for i16 {}
     impl Sealed for i32 {}
     impl Sealed for i64 {}
+    impl Sealed for u8 {}
+    impl Sealed for u16 {}
+    impl Sealed for u32 {}
+    impl Sealed for u64 {}
     impl Sealed for f32 {}
     impl Sealed for f64 {}
+    impl Sealed for bool {}
     impl Sealed for String {}
 }
 
-impl FromHdf5 for i32 {
-    fn from_types() -> &'static [Hdf5Dtype] {
-        &[Hdf5Dtype::I32]
-    }
-
-    fn convert(_: &Hdf5File, _: Hdf5Dtype, bytes: &[u8]) -> Self {
-        Self::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
-    }
-}
-
-impl FromHdf5 for i64 {
-    fn from_types() -> &'static [Hdf5Dtype] {
-        &[Hdf5Dtype::I64]
-    }
-
-    fn convert(_: &Hdf5File, _: Hdf5Dtype, data: &[u8]) -> Self {
-        Self::from_ne_bytes([
-            data[0], data[1], data[2], data[3], data[4], data[5], data[6], data[7],
-        ])
-    }
-}
-
-impl FromHdf5 for f32 {
-    fn from_types() -> &'static [Hdf5Dtype] {
-        &[Hdf5Dtype::F32]
-    }
+/// Identifies Rust types whose in-memory representation is identical to an HDF5 type, so that
+/// data can be used in place without conversion
+///
+/// The first entry of `from_types` for these types is the HDF5 type they are identical to.
+pub trait Hdf5Native: FromHdf5 + Copy {}
+
+/// Implements FromHdf5 and Hdf5Native for a number type, from every HDF5 type that converts to it
+/// without loss. The first HDF5 type must be the type's own representation.
+macro_rules! impl_from_hdf5_number {
+    ($target:ty, $($dtype:ident => $source:ty),+) => {
+        impl Hdf5Native for $target {}
+
+        impl FromHdf5 for $target {
+            fn from_types() -> &'static [Hdf5Dtype] {
+                &[$(Hdf5Dtype::$dtype),+]
+            }
 
-    fn convert(_: &Hdf5File, _: Hdf5Dtype, data: &[u8]) -> Self {
-        Self::from_ne_bytes([data[0], data[1], data[2], data[3]])
-    }
+            fn convert(_: &Hdf5File, dtype: Hdf5Dtype, data: &[u8]) -> Result<Self, Error> {
+                match dtype {
+                    $(Hdf5Dtype::$dtype => {
+                        const SIZE: usize = std::mem::size_of::<$source>();
+                        let mut bytes = [0; SIZE];
+                        bytes.copy_from_slice(data.get(..SIZE).ok_or_else(|| {
+                            Error::parse(format!("{:?} elements need {} bytes", dtype, SIZE))
+                        })?);
+                        Ok(Self::from(<$source>::from_ne_bytes(bytes)))
+                    })+
+                    _ => Err(Error::TypeMismatch {
+                        expected: Self::from_types(),
+                        found: dtype,
+                    }),
+                }
+            }
+        }
+    };
 }
 
-impl FromHdf5 for f64 {
+impl_from_hdf5_number!(i8, I8 => i8);
+impl_from_hdf5_number!(i16, I16 => i16, I8 => i8, U8 => u8);
+impl_from_hdf5_number!(i32, I32 => i32, I16 => i16, I8 => i8, U16 => u16, U8 => u8);
+impl_from_hdf5_number!(
+    i64,
+    I64 => i64,
+    I32 => i32,
+    I16 => i16,
+    I8 => i8,
+    U32 => u32,
+    U16 => u16,
+    U8 => u8
+);
+impl_from_hdf5_number!(u8, U8 => u8);
+impl_from_hdf5_number!(u16, U16 => u16, U8 => u8);
+impl_from_hdf5_number!(u32, U32 => u32, U16 => u16, U8 => u8);
+impl_from_hdf5_number!(u64, U64 => u64, U32 => u32, U16 => u16, U8 => u8);
+impl_from_hdf5_number!(f32, F32 => f32, I16 => i16, I8 => i8, U16 => u16, U8 => u8);
+impl_from_hdf5_number!(
+    f64,
+    F64 => f64,
+    F32 => f32,
+    I32 => i32,
+    I16 => i16,
+    I8 => i8,
+    U32 => u32,
+    U16 => u16,
+    U8 => u8
+);
+
+impl FromHdf5 for bool {
     fn from_types() -> &'static [Hdf5Dtype] {
-        &[Hdf5Dtype::F64]
+        &[Hdf5Dtype::Bool]
     }
 
-    fn convert(_: &Hdf5File, _: Hdf5Dtype, data: &[u8]) -> Self {
-        Self::from_ne_bytes([
-            data[0], data[1], data[2], data[3], data[4], data[5], data[6], data[7],
-        ])
+    fn convert(_: &Hdf5File, _: Hdf5Dtype, data: &[u8]) -> Result<Self, Error> {
+        data.first()
+            .map(|b| *b != 0)
+            .ok_or_else(|| Error::parse("booleans need one byte"))
     }
 }
 
@@ -180,45 +290,70 @@ impl FromHdf5 for String {
         &[Hdf5Dtype::String, Hdf5Dtype::VlenString]
     }
 
-    fn convert(file: &Hdf5File, dtype: Hdf5Dtype, data: &[u8]) -> Self {
+    fn convert(file: &Hdf5File, dtype: Hdf5Dtype, data: &[u8]) -> Result<Self, Error> {
         match dtype {
-            Hdf5Dtype::String => Self::from_utf8_lossy(data).into_owned(),
+            Hdf5Dtype::String => {
+                // Fixed-length strings are padded out with nulls
+                let len = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+                Ok(Self::from_utf8_lossy(&data[..len]).into_owned())
+            }
             Hdf5Dtype::VlenString => {
-                assert_eq!(data.len(), 16);
-                let data = &data[4..];
-                let heap_address = u64::from_ne_bytes([
-                    data[0], data[1], data[2], data[3], data[4], data[5], data[6], data[7],
-                ]) as usize;
-                let heap_index = u16::from_ne_bytes([data[8], data[9]]);
+                // A length, then the address of a global heap collection and an index into it
+                if data.len() != 16 {
+                    return Err(Error::parse(format!(
+                        "variable-length strings need 16 bytes but have {}",
+                        data.len()
+                    )));
+                }
+                let mut address = [0; 8];
+                address.copy_from_slice(&data[4..12]);
+                let heap_address = u64::from_ne_bytes(address);
+                let heap_index = u16::from_ne_bytes([data[12], data[13]]);
                 let heap_object =
-                    parse::global_heap_nth_item(&file.map[heap_address..], heap_index)
-                        .unwrap()
+                    parse::global_heap_nth_item(file.bytes_from(heap_address)?, heap_index)
+                        .map_err(|e| Error::nom(file.contents(), e))?
                         .1;
-                Self::from_utf8_lossy(heap_object).into_owned()
+                Ok(Self::from_utf8_lossy(heap_object).into_owned())
             }
-            _ => unreachable!(),
+            _ => Err(Error::TypeMismatch {
+                expected: Self::from_types(),
+                found: dtype,
+            }),
         }
     }
 }
 
-#[doc(hidden)]
+/// The types of data that this library can read
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Hdf5Dtype {
     F64,
     F32,
     I64,
     I32,
+    I16,
+    I8,
+    U64,
+    U32,
+    U16,
+    U8,
     String,
     VlenString,
     Bool,
 }
 
 impl Hdf5Dtype {
-    fn from(raw: parse::header::DataType) -> Self {
+    fn from(raw: &parse::header::DataType) -> Result<Self, Error> {
         use parse::header::DatatypeClass;
-        match (raw.class.clone(), raw.size) {
-            (DatatypeClass::FixedPoint, 4) => Self::I32,
-            (DatatypeClass::FixedPoint, 8) => Self::I64,
+        let signed = raw.class_bitfields & 0b1000 != 0;
+        Ok(match (raw.class.clone(), raw.size) {
+            (DatatypeClass::FixedPoint, 1) if signed => Self::I8,
+            (DatatypeClass::FixedPoint, 2) if signed => Self::I16,
+            (DatatypeClass::FixedPoint, 4) if signed => Self::I32,
+            (DatatypeClass::FixedPoint, 8) if signed => Self::I64,
+            (DatatypeClass::FixedPoint, 1) => Self::U8,
+            (DatatypeClass::FixedPoint, 2) => Self::U16,
+            (DatatypeClass::FixedPoint, 4) => Self::U32,
+            (DatatypeClass::FixedPoint, 8) => Self::U64,
             (DatatypeClass::FloatingPoint, 8) => Self::F64,
             (DatatypeClass::FloatingPoint, 4) => Self::F32,
             (DatatypeClass::String, _) => Self::String,
@@ -229,174 +364,457 @@ impl Hdf5Dtype {
                 },
                 _,
             ) => Self::VlenString,
-            _ => unimplemented!("dtype not supported yet {:?}", raw),
+            _ => {
+                return Err(Error::Unsupported {
+                    feature: format!("datatype {:?}", raw),
+                })
+            }
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ByteOrder {
+    LittleEndian,
+    BigEndian,
+}
+
+impl ByteOrder {
+    #[cfg(target_endian = "little")]
+    const NATIVE: Self = Self::LittleEndian;
+    #[cfg(target_endian = "big")]
+    const NATIVE: Self = Self::BigEndian;
+}
+
+/// Everything that is needed to decode the elements of a dataset or attribute
+#[derive(Clone, Debug)]
+struct Datatype {
+    class: Hdf5Dtype,
+    size: usize,
+    byte_order: ByteOrder,
+}
+
+impl Datatype {
+    fn from(raw: parse::header::DataType) -> Result<Self, Error> {
+        use parse::header::DatatypeClass;
+        let class = Hdf5Dtype::from(&raw)?;
+        if raw.size == 0 {
+            return Err(Error::parse("datatype has a size of zero"));
         }
+        // Only numbers have a byte order, and bit 0 of their class bitfields is set for big-endian
+        let byte_order = match raw.class {
+            DatatypeClass::FixedPoint | DatatypeClass::FloatingPoint
+                if raw.class_bitfields & 0b1 != 0 =>
+            {
+                ByteOrder::BigEndian
+            }
+            _ => ByteOrder::LittleEndian,
+        };
+        Ok(Self {
+            class,
+            size: raw.size as usize,
+            byte_order,
+        })
+    }
+
+    /// Swap the bytes of each element if they are not in the host's byte order
+    fn to_native<'a>(&self, data: Cow<'a, [u8]>) -> Cow<'a, [u8]> {
+        let is_number = !matches!(
+            self.class,
+            Hdf5Dtype::String | Hdf5Dtype::VlenString | Hdf5Dtype::Bool
+        );
+        if !is_number || self.byte_order == ByteOrder::NATIVE || self.size < 2 {
+            return data;
+        }
+        let mut data = data.into_owned();
+        for element in data.chunks_exact_mut(self.size) {
+            element.reverse();
+        }
+        Cow::Owned(data)
     }
 }
 
 impl Hdf5File {
     /// Open an HDF5 file
     ///
-    /// This function memory-maps the file and initializes a number of internal data structures to
-    /// make access to data trivial.
+    /// This function memory-maps the file and reads its superblock and root group. Other groups,
+    /// objects and attributes are read when they are first accessed and kept from then on. Use
+    /// [`OpenOptions`] to control how external links are followed.
     pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
-        let file = std::fs::File::open(path)?;
-        let contents = unsafe { memmap::Mmap::map(&file)? };
-        let superblock = parse::superblock(&contents)?.1;
-
-        let (mut remaining, object_header) = parse::object_header(
-            &contents[superblock
-                .root_group_symbol_table_entry
-                .object_header_address as usize..],
-        )?;
-
-        use std::ops::Deref;
-        let mut root_group = parse_group(
-            contents.deref(),
-            parse::header::SymbolTable {
-                btree_address: superblock.root_group_symbol_table_entry.address_of_btree,
-                local_heap_address: superblock
-                    .root_group_symbol_table_entry
-                    .address_of_name_heap,
-            },
-        )?;
-
-        use parse::header::Message;
-        use parse::header::ObjectHeaderContinuation;
-        let mut messages = Vec::new();
-        let mut resume_with_after_continuation = Vec::new();
-        for _ in 0..object_header.total_number_of_header_messages {
-            let (remaining_after_parse, message) = parse::header_message(remaining)?;
-            match message {
-                Message::Attribute(m) => {
-                    root_group
-                        .attributes
-                        .insert(m.name.clone(), Attribute::from(m));
-                    remaining = remaining_after_parse;
-                }
-                Message::ObjectHeaderContinuation(ObjectHeaderContinuation { offset, length }) => {
-                    resume_with_after_continuation.push(remaining_after_parse);
-                    remaining = &contents[offset as usize..offset as usize + length as usize];
-                }
-                _ => {
-                    messages.push(message.clone());
-                    remaining = remaining_after_parse;
-                    if remaining.is_empty() {
-                        remaining = resume_with_after_continuation
-                            .pop()
-                            .expect("Ran out of data to parse, and no contiuation to resume from");
-                    }
-                }
-            }
-        }
+        OpenOptions::new().open(path)
+    }
 
-        Ok(Self {
-            map: contents,
-            root_group,
-        })
+    /// Open an HDF5 file that is held in memory, such as a `Vec<u8>`, `&[u8]` or `Arc<[u8]>`
+    pub fn from_bytes<B: Into<Arc<[u8]>>>(bytes: B) -> Result<Self, Error> {
+        OpenOptions::new().open_bytes(bytes)
+    }
+
+    /// Open an HDF5 file by reading all of `reader` into memory, for files that are not on disk,
+    /// such as members of an archive
+    ///
+    /// The whole stream is buffered before anything is parsed, because the parsers work on the
+    /// file as one slice, so it has to fit in memory and within the allocation limit.
+    pub fn from_reader_buffered<R: Read + Seek>(reader: R) -> Result<Self, Error> {
+        OpenOptions::new().open_reader_buffered(reader)
     }
 
     /// Look up the provided path to a dataset, if one is found and its type correct, return a
     /// slice of the underlying file mapping.
     ///
-    /// Note that this discards any dimension information associated with the dataset.
-    pub fn view(&self, dataset_path: &str) -> &[u8] {
-        let dataset = self.root_group.find_dataset(dataset_path);
-        &self.map[dataset.address as usize..(dataset.address + dataset.size) as usize]
+    /// Note that this discards any dimension information associated with the dataset, and that
+    /// datasets stored in chunks cannot be viewed in place.
+    pub fn view(&self, dataset_path: &str) -> Result<&[u8], Error> {
+        let dataset = self.dataset(dataset_path)?;
+        match dataset.layout() {
+            Layout::Compact(data) => Ok(data),
+            Layout::Contiguous { address, .. } if *address == parse::UNDEFINED_ADDRESS => {
+                Err(Error::Unsupported {
+                    feature: format!("viewing dataset {:?} that was never written", dataset_path),
+                })
+            }
+            Layout::Contiguous { address, size } => match self.bytes(*address, *size)? {
+                Cow::Borrowed(data) => Ok(data),
+                Cow::Owned(_) => Err(Error::Unsupported {
+                    feature: format!("viewing dataset {:?} that spans member files", dataset_path),
+                }),
+            },
+            Layout::Chunked { .. } => Err(Error::Unsupported {
+                feature: format!("viewing chunked dataset {:?} in place", dataset_path),
+            }),
+            Layout::External { .. } => Err(Error::Unsupported {
+                feature: format!("viewing dataset {:?} in external files", dataset_path),
+            }),
+            Layout::Virtual { .. } => Err(Error::Unsupported {
+                feature: format!("viewing virtual dataset {:?}", dataset_path),
+            }),
+        }
+    }
+
+    /// The user block, which is the bytes that other applications keep at the start of the file
+    /// before the HDF5 data begins
+    ///
+    /// This is empty for files without a user block. MATLAB, for example, keeps a header of its
+    /// own here.
+    pub fn userblock(&self) -> &[u8] {
+        self.contents.userblock()
+    }
+
+    /// The root group of this file
+    pub fn root(&self) -> GroupHandle<'_> {
+        GroupHandle::new(self, &self.root)
+    }
+
+    /// Iterate over every group, dataset and link in this file, depth first
+    pub fn walk(&self) -> Walk<'_> {
+        self.root().walk()
+    }
+
+    /// Call `f` with every group, dataset and link in this file, depth first, stopping at the
+    /// first object that cannot be read
+    pub fn visit<'a>(&'a self, f: impl FnMut(ObjectInfo<'a>)) -> Result<(), Error> {
+        self.root().visit(f)
+    }
+
+    /// Look up the provided path to a group
+    pub fn group(&self, group_path: &str) -> Result<GroupHandle<'_>, Error> {
+        self.root().group(group_path)
+    }
+
+    /// Look up the provided path to a dataset
+    pub fn dataset(&self, dataset_path: &str) -> Result<DatasetHandle<'_>, Error> {
+        self.root().dataset(dataset_path)
+    }
+
+    /// Look up the provided path to a committed datatype
+    pub fn datatype(&self, datatype_path: &str) -> Result<DatatypeHandle<'_>, Error> {
+        self.root().datatype(datatype_path)
+    }
+
+    /// Look up the provided path to a soft, external or user-defined link, without following it
+    pub fn link(&self, link_path: &str) -> Result<LinkHandle<'_>, Error> {
+        self.root().link(link_path)
+    }
+
+    /// Look up the provided path to a dataset and return its dataspace
+    pub fn dataspace(&self, dataset_path: &str) -> Result<&Dataspace, Error> {
+        Ok(self.dataset(dataset_path)?.dataspace())
+    }
+
+    /// Look up the provided path to an attribute and return its dataspace
+    pub fn attr_dataspace(&self, attribute_path: &str) -> Result<&Dataspace, Error> {
+        self.find_attr(attribute_path)
+            .and_then(|(attrs, name)| attrs.dataspace(name))
     }
 
     /// Look up the provided path to an attribute, if one is found and its type correct,
     /// return a copy of the attribute's data.
     ///
-    /// Panics if the attribute cannot be found or the attribute is of the wrong type.
-    pub fn attr<T: FromHdf5>(&self, attribute_name: &str) -> T {
-        let attribute = self
-            .root_group
-            .attributes
-            .get(attribute_name)
-            .unwrap_or_else(|| panic!("attribute not found: {:?}", attribute_name));
-        if !T::from_types().contains(&attribute.dtype) {
-            panic!(
-                "Attribute {:?} is of type {:?}, which is not compatible with any of {:?}",
-                attribute_name,
-                attribute.dtype,
-                T::from_types()
-            );
+    /// The last component of the path is the name of the attribute, and the rest is the path to
+    /// the group or dataset that it is attached to, so `"units"` is an attribute of the root group
+    /// and `"LOS/SB/units"` is an attribute of `LOS/SB`.
+    ///
+    /// Fails if the attribute cannot be found, the attribute is of the wrong type, or it does not
+    /// hold exactly one element.
+    pub fn attr<T: FromHdf5>(&self, attribute_path: &str) -> Result<T, Error> {
+        self.find_attr(attribute_path)
+            .and_then(|(attrs, name)| attrs.get(name))
+    }
+
+    /// Look up the provided path to an attribute, if one is found and its type correct,
+    /// return a copy of all the attribute's elements in row-major order.
+    ///
+    /// Paths are resolved as they are by [`Hdf5File::attr`].
+    pub fn attr_vec<T: FromHdf5>(&self, attribute_path: &str) -> Result<Vec<T>, Error> {
+        self.find_attr(attribute_path)
+            .and_then(|(attrs, name)| attrs.get_vec(name))
+    }
+
+    /// Split an attribute path into the attributes of the object it leads to and the name
+    fn find_attr<'p>(&self, attribute_path: &'p str) -> Result<(Attributes<'_>, &'p str), Error> {
+        match attribute_path.rfind('/') {
+            Some(i) => Ok((
+                self.root().member(&attribute_path[..i])?.attrs(),
+                &attribute_path[i + 1..],
+            )),
+            None => Ok((self.root().attrs(), attribute_path)),
         }
-        T::convert(self, attribute.dtype, &attribute.data)
+    }
+
+    /// Where to find a file that holds external raw data, which is under the configured prefix
+    /// or else beside this file
+    ///
+    /// Names that are absolute or lead out of that directory are refused, so that a file cannot
+    /// read any other file that the process can.
+    fn external_file_path(&self, name: &str) -> Result<PathBuf, Error> {
+        use std::path::Component;
+        // Count how deep each component is below the directory, failing once it would go above
+        let confined = Path::new(name)
+            .components()
+            .try_fold(0usize, |depth, component| match component {
+                Component::Normal(_) => Some(depth + 1),
+                Component::CurDir => Some(depth),
+                Component::ParentDir => depth.checked_sub(1),
+                Component::RootDir | Component::Prefix(_) => None,
+            })
+            .is_some();
+        if !confined {
+            return Err(Error::ExternalFileDenied {
+                file: name.to_string(),
+            });
+        }
+        Ok(match (&self.options.external_file_prefix, &self.path) {
+            (Some(prefix), _) => prefix.join(name),
+            (None, Some(path)) => path
+                .parent()
+                .map_or_else(|| name.into(), |dir| dir.join(name)),
+            (None, None) => PathBuf::from(name),
+        })
+    }
+
+    /// All the bytes of the file, by address
+    fn contents(&self) -> &Contents {
+        &self.contents
+    }
+
+    /// The `size` bytes of the file that start at `address`
+    fn bytes(&self, address: u64, size: u64) -> Result<Cow<'_, [u8]>, Error> {
+        self.contents.bytes(address, size)
+    }
+
+    /// All the bytes of the file from `address` to the end of the member file that holds it
+    fn bytes_from(&self, address: u64) -> Result<&[u8], Error> {
+        self.contents.bytes_from(address)
     }
 }
 
-fn parse_group(contents: &[u8], symbol_table: parse::header::SymbolTable) -> Result<Group, Error> {
-    let node = parse::hdf5_node(&contents[symbol_table.btree_address as usize..], 8)?.1;
-
-    let name_heap =
-        parse::local_heap(&contents[symbol_table.local_heap_address as usize..], 8, 8)?.1;
-
-    let mut datasets = BTreeMap::new();
-    let mut groups = BTreeMap::new();
-
-    for group_entry in node.entries {
-        let table =
-            parse::symbol_table(&contents[group_entry.pointer_to_symbol_table as usize..], 8)?.1;
-
-        for object in &table.entries {
-            let name = &contents
-                [(object.link_name_offset + name_heap.address_of_data_segment) as usize..]
-                .iter()
-                .take_while(|b| **b != 0)
-                .map(|b| *b as char)
-                .collect::<String>();
-
-            use parse::header::Message;
-            use parse::header::ObjectHeaderContinuation;
-            let (mut remaining, object_header) =
-                parse::object_header(&contents[object.object_header_address as usize..])?;
-            let mut messages = Vec::new();
-
-            let mut resume_with_after_continuation = Vec::new();
-            for _ in 0..object_header.total_number_of_header_messages {
-                let (remaining_after_parse, message) = parse::header_message(remaining)?;
-                if let Message::ObjectHeaderContinuation(ObjectHeaderContinuation {
-                    offset,
-                    length,
-                }) = message
-                {
-                    resume_with_after_continuation.push(remaining_after_parse);
-                    remaining = &contents[offset as usize..offset as usize + length as usize];
-                } else {
-                    messages.push(message.clone());
-                    remaining = remaining_after_parse;
-                    if remaining.is_empty() {
-                        remaining = resume_with_after_continuation
-                            .pop()
-                            .expect("Ran out of data to parse, and no contiuation to resume from");
-                    }
-                }
+/// Parse all the messages in the object header at `address`, following any continuation blocks
+///
+/// Shared messages are replaced by the messages they refer to. The shared parts of attribute
+/// messages, and attributes that are kept in dense storage, are left for when the object's
+/// attributes are first read.
+fn object_header_messages(
+    contents: &Contents,
+    shared: &[SharedMessageIndex],
+    address: u64,
+) -> Result<Vec<parse::header::Message>, Error> {
+    use parse::header::{Message, Shared};
+    let mut messages = raw_object_header_messages(contents, address)?;
+
+    for message in &mut messages {
+        if let Message::Shared {
+            message_type,
+            location,
+        } = message
+        {
+            let message_type = *message_type;
+            resolve_shared(contents, shared, message_type, location, |m| {
+                Some(Box::new(m)).filter(|m| m.message_type() == message_type)
+            })?;
+            if let Message::Shared {
+                location: Shared::Here(resolved),
+                ..
+            } = std::mem::replace(message, Message::Nil)
+            {
+                *message = *resolved;
             }
+        }
+    }
 
-            match messages.first() {
-                None => {}
-                Some(Message::SymbolTable(table)) => {
-                    groups.insert(name.clone(), parse_group(contents, table.clone())?);
-                    let g = groups.get_mut(name).unwrap();
-                    for m in messages.into_iter().skip(1) {
-                        if let Message::Attribute(m) = m {
-                            g.attributes.insert(m.name.clone(), Attribute::from(m));
-                        }
-                    }
-                }
-                Some(Message::Dataspace(_)) => {
-                    datasets.insert(name.clone(), Dataset::from(messages));
+    Ok(messages)
+}
+
+/// Parse the messages in the object header at `address` as they are stored, following any
+/// continuation blocks
+fn raw_object_header_messages(
+    contents: &Contents,
+    address: u64,
+) -> Result<Vec<parse::header::Message>, Error> {
+    use parse::header::{Message, ObjectHeaderContinuation};
+    let (remaining, object_header) =
+        parse::object_header(contents.bytes_from(address)?).map_err(|e| Error::nom(contents, e))?;
+    let mut remaining = usize::try_from(object_header.object_header_size)
+        .ok()
+        .and_then(|size| remaining.get(..size))
+        .ok_or(Error::OutOfBounds { address })?;
+    let mut messages = Vec::new();
+    let mut resume_with_after_continuation = Vec::new();
+    // Continuation blocks that have been read, so that a block that continues into itself or an
+    // earlier block is not read forever
+    let mut continuations = std::collections::HashSet::new();
+    let mut parsed = 0;
+    loop {
+        // Version 1 headers count their messages, including continuation messages
+        if let Some(total) = object_header.total_number_of_header_messages {
+            if parsed == total {
+                break;
+            }
+        }
+        // Version 2 headers end each block with a gap that is too small to hold a message
+        if remaining.len() < object_header.message_prefix_size() {
+            match resume_with_after_continuation.pop() {
+                Some(resume) => {
+                    remaining = resume;
+                    continue;
                 }
-                m => unimplemented!("Unexpected first message in a message list: {:?}", m),
+                None => break,
+            }
+        }
+        let (remaining_after_parse, message) = parse::header_message(remaining, &object_header)
+            .map_err(|e| Error::nom(contents, e))?;
+        parsed += 1;
+        if let Message::ObjectHeaderContinuation(ObjectHeaderContinuation { offset, length }) =
+            message
+        {
+            resume_with_after_continuation.push(remaining_after_parse);
+            if !continuations.insert(offset) {
+                return Err(Error::parse(format!(
+                    "object header continuation block at {:#x} is read twice",
+                    offset
+                )));
+            }
+            let block = usize::try_from(length)
+                .ok()
+                .and_then(|length| contents.bytes_from(offset).ok()?.get(..length))
+                .ok_or(Error::OutOfBounds { address: offset })?;
+            // Version 2 continuation blocks have a signature and end with a checksum
+            remaining = if object_header.version == 1 {
+                block
+            } else {
+                parse::continuation_block(block)
+                    .map_err(|e| Error::nom(contents, e))?
+                    .1
             };
+        } else {
+            messages.push(message);
+            remaining = remaining_after_parse;
         }
     }
 
-    Ok(Group {
-        datasets,
-        groups,
-        attributes: BTreeMap::new(),
-    })
+    Ok(messages)
+}
+
+/// Replace a shared message of type `message_type` with the message that it refers to
+fn resolve_shared<T>(
+    contents: &Contents,
+    shared_indexes: &[SharedMessageIndex],
+    message_type: u16,
+    shared: &mut parse::header::Shared<T>,
+    pick: impl Fn(parse::header::Message) -> Option<T>,
+) -> Result<(), Error> {
+    use parse::header::Shared;
+    let message = match shared {
+        Shared::Here(_) => return Ok(()),
+        Shared::ObjectHeader(address) => {
+            let address = *address;
+            // Only the object's own messages are searched, so that objects whose shared messages
+            // refer to each other are not resolved forever
+            raw_object_header_messages(contents, address)?
+                .into_iter()
+                .find_map(pick)
+                .ok_or_else(|| {
+                    Error::parse(format!(
+                        "the object at {:#x} does not hold the shared message",
+                        address
+                    ))
+                })?
+        }
+        Shared::Heap(id) => {
+            let body = shared_message_body(contents, shared_indexes, message_type, id)?;
+            pick(
+                parse::message(body, message_type)
+                    .map_err(|e| Error::nom(contents, e))?
+                    .1,
+            )
+            .ok_or_else(|| {
+                Error::parse("the shared message heap holds a message of the wrong type")
+            })?
+        }
+    };
+    *shared = Shared::Here(message);
+    Ok(())
+}
+
+/// The body of the message of type `message_type` that the shared message heap holds with
+/// heap ID `id`
+///
+/// Each index of shared messages keeps the messages of the types it holds in its own fractal
+/// heap. The indexes themselves only serve to find an existing copy of a message by its hash
+/// when writing, so they are not read.
+fn shared_message_body<'a>(
+    contents: &'a Contents,
+    shared_indexes: &[SharedMessageIndex],
+    message_type: u16,
+    id: &'a [u8],
+) -> Result<&'a [u8], Error> {
+    let index = shared_indexes
+        .iter()
+        .find(|index| index.holds(message_type))
+        .ok_or_else(|| {
+            Error::parse(format!(
+                "no shared message index holds messages of type {:#x}",
+                message_type
+            ))
+        })?;
+    FractalHeap::read(contents, index.fractal_heap_address)?.object(id)
+}
+
+/// Read the indexes of shared messages from the superblock extension at `address`, if the file
+/// has one
+fn shared_message_indexes(
+    contents: &Contents,
+    address: u64,
+) -> Result<Vec<SharedMessageIndex>, Error> {
+    use parse::header::Message;
+    if address == parse::UNDEFINED_ADDRESS {
+        return Ok(Vec::new());
+    }
+    for message in object_header_messages(contents, &[], address)? {
+        if let Message::SharedMessageTable(table) = message {
+            let table_contents = contents.bytes_from(table.address)?;
+            return Ok(parse::shared_message_indexes(table_contents, table.indexes)
+                .map_err(|e| Error::nom(contents, e))?
+                .1);
+        }
+    }
+    Ok(Vec::new())
 }
@@ -0,0 +1,164 @@
+use crate::group::{GroupHandle, Member};
+use crate::{Error, Hdf5File};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// The most links that are followed while looking up one path, which stops soft links that lead
+/// back to themselves from being followed forever
+pub(crate) const MAX_LINK_HOPS: usize = 32;
+
+/// A link in a group that names an object by path rather than by address
+#[derive(Debug)]
+pub(crate) enum Link {
+    Soft(String),
+    External {
+        file: String,
+        path: String,
+        // The file is opened the first time the link is followed, and kept open for as long as
+        // this file is
+        opened: OnceLock<Box<Hdf5File>>,
+    },
+    UserDefined {
+        ty: u8,
+        data: Vec<u8>,
+    },
+}
+
+/// The kinds of link that a group can hold in place of an object
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkKind {
+    /// A path to an object in the same file
+    Soft,
+    /// A path to an object in another file
+    External,
+    /// A link of a type registered by an application, with its type number
+    UserDefined(u8),
+}
+
+/// A soft, external or user-defined link in a group, which has not been followed
+#[derive(Clone, Copy)]
+pub struct LinkHandle<'a> {
+    group: GroupHandle<'a>,
+    link: &'a Link,
+}
+
+impl<'a> LinkHandle<'a> {
+    pub(crate) fn new(group: GroupHandle<'a>, link: &'a Link) -> Self {
+        Self { group, link }
+    }
+
+    pub(crate) fn file(&self) -> &'a Hdf5File {
+        self.group.file()
+    }
+
+    /// Whether this is a soft, external or user-defined link
+    pub fn kind(&self) -> LinkKind {
+        match self.link {
+            Link::Soft(_) => LinkKind::Soft,
+            Link::External { .. } => LinkKind::External,
+            Link::UserDefined { ty, .. } => LinkKind::UserDefined(*ty),
+        }
+    }
+
+    /// The path to the object that this link leads to, or `None` for user-defined links
+    ///
+    /// Relative soft link paths are looked up from the group that holds the link, and external
+    /// link paths from the root group of the other file.
+    pub fn target(&self) -> Option<&'a str> {
+        match self.link {
+            Link::Soft(path) | Link::External { path, .. } => Some(path),
+            Link::UserDefined { .. } => None,
+        }
+    }
+
+    /// The name of the file that an external link leads to, as it is stored in the link
+    pub fn target_file(&self) -> Option<&'a str> {
+        match self.link {
+            Link::External { file, .. } => Some(file),
+            _ => None,
+        }
+    }
+
+    /// The data stored in a user-defined link
+    pub fn data(&self) -> Option<&'a [u8]> {
+        match self.link {
+            Link::UserDefined { data, .. } => Some(data),
+            _ => None,
+        }
+    }
+
+    /// Look up the object that this link leads to, opening the other file for external links
+    ///
+    /// Links that lead to other links are followed as well.
+    pub fn follow(&self) -> Result<Member<'a>, Error> {
+        let mut hops = MAX_LINK_HOPS;
+        self.follow_within(&mut hops)
+    }
+
+    /// Follow this link, where `hops` is how many more links the lookup that reached it may
+    /// follow, and is counted down for this link and every link after it
+    pub(crate) fn follow_within(&self, hops: &mut usize) -> Result<Member<'a>, Error> {
+        *hops = match hops.checked_sub(1) {
+            Some(hops) => hops,
+            None => {
+                return Err(Error::TooManyLinks {
+                    path: self.target().unwrap_or_default().to_string(),
+                })
+            }
+        };
+        match self.link {
+            Link::Soft(path) => self.group.resolve(path, hops),
+            Link::External { file, path, opened } => {
+                let other = match opened.get() {
+                    Some(other) => other,
+                    None => {
+                        let other = open_external(self.file(), file, path)?;
+                        opened.get_or_init(|| Box::new(other))
+                    }
+                };
+                other.root().resolve(path, hops)
+            }
+            Link::UserDefined { ty, .. } => Err(Error::Unsupported {
+                feature: format!("following user-defined links of type {}", ty),
+            }),
+        }
+    }
+}
+
+/// Find and open the file that an external link in `from` names, with the options that `from`
+/// was opened with
+///
+/// The file is looked for in each of the configured search directories, then beside `from`, and
+/// then as the name is written.
+pub(crate) fn open_external(from: &Hdf5File, file: &str, path: &str) -> Result<Hdf5File, Error> {
+    from.options.open(find_external(from, file, path)?)
+}
+
+/// Find the file that an external link in `from` names, as [`open_external`] does, and check
+/// that the link may be followed to the object at `path` in it
+pub(crate) fn find_external(from: &Hdf5File, file: &str, path: &str) -> Result<PathBuf, Error> {
+    let options = &from.options;
+    let mut candidates = options
+        .external_link_paths
+        .iter()
+        .map(|dir| dir.join(file))
+        .collect::<Vec<_>>();
+    if let Some(dir) = from.path.as_deref().and_then(Path::parent) {
+        candidates.push(dir.join(file));
+    }
+    candidates.push(PathBuf::from(file));
+    let found = candidates
+        .into_iter()
+        .find(|candidate| candidate.is_file())
+        .ok_or_else(|| Error::NotFound {
+            path: file.to_string(),
+        })?;
+    if let Some(filter) = &options.external_link_filter {
+        if !filter(&found, path) {
+            return Err(Error::ExternalLinkDenied {
+                file: file.to_string(),
+            });
+        }
+    }
+    Ok(found)
+}
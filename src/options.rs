@@ -0,0 +1,279 @@
+use crate::driver;
+use crate::group::Group;
+use crate::storage::{self, Contents, Storage};
+use crate::{object_header_messages, parse, shared_message_indexes, Error, Hdf5File};
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicUsize;
+use std::sync::{Arc, Mutex};
+
+type ExternalLinkFilter = dyn Fn(&Path, &str) -> bool + Send + Sync;
+
+/// Settings for opening an HDF5 file, in the style of `std::fs::OpenOptions`
+///
+/// Files that external links lead to are opened with the same settings.
+#[derive(Clone, Default)]
+pub struct OpenOptions {
+    pub(crate) external_link_paths: Vec<PathBuf>,
+    pub(crate) external_link_filter: Option<Arc<ExternalLinkFilter>>,
+    pub(crate) external_file_prefix: Option<PathBuf>,
+    pub(crate) limits: Limits,
+}
+
+impl OpenOptions {
+    /// The default settings, which are what [`Hdf5File::read`] uses
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a directory to look for the files that external links name in
+    ///
+    /// Directories are searched in the order they are added, before the directory of the file
+    /// that holds the link.
+    pub fn external_link_path<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.external_link_paths.push(dir.into());
+        self
+    }
+
+    /// Decide whether each external link may be followed
+    ///
+    /// `filter` is called with the path of the file that the link leads to and the path of the
+    /// object within it, and following the link fails with [`Error::ExternalLinkDenied`] if it
+    /// returns false. Returning false for everything forbids external links.
+    ///
+    /// The files that virtual datasets are mapped from are found and filtered in the same way.
+    /// A source that the filter refuses is treated like one that does not exist: reading the
+    /// virtual dataset does not fail, and the elements that the source would fill hold the fill
+    /// value.
+    pub fn external_link_filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&Path, &str) -> bool + Send + Sync + 'static,
+    {
+        self.external_link_filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// Set the directory that the files which hold datasets' external raw data are looked for in
+    ///
+    /// By default they are looked for beside the HDF5 file, or relative to the working directory
+    /// for files opened from memory. Names that are absolute or lead out of the directory with
+    /// `..` fail with [`Error::ExternalFileDenied`].
+    pub fn external_file_prefix<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.external_file_prefix = Some(dir.into());
+        self
+    }
+
+    /// Set the limits that opening and reading the file are subject to
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Open an HDF5 file with these settings
+    ///
+    /// This function memory-maps the file and reads its superblock and root group. Other groups,
+    /// objects and attributes are read when they are first accessed and kept from then on.
+    ///
+    /// Files that the family driver wrote are opened by the name of their first member, such as
+    /// `out_00000.h5`, or by the pattern of their members' names, such as `out_%05d.h5`. A name
+    /// is only taken for a pattern when no file exists by that name. Files that the split or
+    /// multi drivers wrote are opened by the name of the member with the superblock, such as
+    /// `out-m.h5`. The other members are looked for next to it.
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> Result<Hdf5File, Error> {
+        let path = path.as_ref();
+        let first = if driver::is_family_pattern(path) {
+            driver::family_member(path, 0).unwrap_or_else(|| path.to_path_buf())
+        } else {
+            path.to_path_buf()
+        };
+        let contents = storage::map(&first)?;
+        self.open_from(Box::new(contents), Some(path.to_path_buf()))
+    }
+
+    /// Open an HDF5 file that is held in memory with these settings
+    ///
+    /// External links are looked for in the configured search directories and then relative to
+    /// the working directory.
+    pub fn open_bytes<B: Into<Arc<[u8]>>>(&self, bytes: B) -> Result<Hdf5File, Error> {
+        self.open_storage(bytes.into())
+    }
+
+    /// Open an HDF5 file by reading all of `reader` into memory with these settings
+    ///
+    /// Nothing is read on demand: the stream is buffered whole, as long as it fits in
+    /// [`Limits::max_allocation`].
+    pub fn open_reader_buffered<R: Read + Seek>(&self, reader: R) -> Result<Hdf5File, Error> {
+        let contents = storage::buffer_reader(reader, &self.limits)?;
+        self.open_storage(contents)
+    }
+
+    /// Open an HDF5 file whose bytes are kept in `storage` with these settings
+    pub fn open_storage<S: Storage + 'static>(&self, storage: S) -> Result<Hdf5File, Error> {
+        self.open_from(Box::new(storage), None)
+    }
+
+    fn open_from(
+        &self,
+        storage: Box<dyn Storage>,
+        path: Option<PathBuf>,
+    ) -> Result<Hdf5File, Error> {
+        // Addresses count from the superblock, which follows the user block if there is one. The
+        // superblock also stores a base address, but the reference library uses the superblock's
+        // own position when the two differ, so that user blocks can be added to existing files.
+        let base = parse::superblock_offset(storage.contents()).ok_or_else(|| {
+            Error::parse("the HDF5 signature was not found at any user block size")
+        })?;
+        let mut contents = Contents::new(storage, base as u64);
+        let superblock = parse::superblock(contents.bytes_from(0)?)
+            .map_err(|e| Error::nom(&contents, e))?
+            .1;
+        // Everything after the superblock is parsed with offsets and lengths of eight bytes
+        for (what, size) in [
+            ("offsets", superblock.offset_size),
+            ("lengths", superblock.length_size),
+        ]
+        .iter()
+        {
+            if *size != 8 {
+                return Err(Error::Unsupported {
+                    feature: format!("{} of {} bytes", what, size),
+                });
+            }
+        }
+        // Files that are split between member files say how in the driver information block
+        let driver_address = superblock.driver_information_block_address;
+        if driver_address != parse::UNDEFINED_ADDRESS {
+            let info = parse::driver_info(contents.bytes_from(driver_address)?)
+                .map_err(|e| Error::nom(&contents, e))?
+                .1;
+            let end = superblock.end_of_file_address.saturating_add(base as u64);
+            driver::open_members(&mut contents, info, path.as_deref(), end)?;
+        }
+
+        let shared = shared_message_indexes(&contents, superblock.superblock_extension_address)?;
+
+        let root_address = superblock.root_group_object_header_address;
+        let messages = object_header_messages(&contents, &shared, root_address)
+            .map_err(|e| e.in_object("/"))?;
+        let root = Group::from(String::from("/"), 0, root_address, messages)?;
+
+        Ok(Hdf5File {
+            contents,
+            root,
+            shared,
+            objects: AtomicUsize::new(1),
+            virtual_sources: Mutex::new(HashMap::new()),
+            path,
+            options: self.clone(),
+        })
+    }
+}
+
+impl std::fmt::Debug for OpenOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("OpenOptions")
+            .field("external_link_paths", &self.external_link_paths)
+            .field(
+                "external_link_filter",
+                &self.external_link_filter.as_ref().map(|_| ".."),
+            )
+            .field("external_file_prefix", &self.external_file_prefix)
+            .field("limits", &self.limits)
+            .finish()
+    }
+}
+
+/// Limits on the resources that reading a file may use, which guard against files that are
+/// crafted to exhaust memory or the stack
+///
+/// Going over a limit fails with [`Error::LimitExceeded`]. The defaults allow any file that the
+/// reference library writes in practice.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    pub(crate) max_allocation: usize,
+    pub(crate) max_depth: usize,
+    pub(crate) max_objects: usize,
+    pub(crate) max_chunk_size: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_allocation: usize::MAX,
+            max_depth: 256,
+            max_objects: usize::MAX,
+            max_chunk_size: u32::MAX as usize,
+        }
+    }
+}
+
+impl Limits {
+    /// The default limits
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The most bytes that may be allocated at once, such as to hold the elements that are read
+    /// from a dataset or the output of decompressing a chunk
+    pub fn max_allocation(mut self, bytes: usize) -> Self {
+        self.max_allocation = bytes;
+        self
+    }
+
+    /// The deepest that groups may be nested below the root group, and that virtual datasets may
+    /// be mapped from other virtual datasets
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// The most groups, datasets, datatypes and links that may be found in a file, where the
+    /// members of a group are counted when the group is first read
+    pub fn max_objects(mut self, objects: usize) -> Self {
+        self.max_objects = objects;
+        self
+    }
+
+    /// The most bytes that a chunk may take up, either as stored or once it is decoded
+    pub fn max_chunk_size(mut self, bytes: usize) -> Self {
+        self.max_chunk_size = bytes;
+        self
+    }
+
+    /// Check that `bytes` may be allocated
+    pub(crate) fn check_allocation(&self, bytes: usize) -> Result<(), Error> {
+        check("allocation size", bytes, self.max_allocation)
+    }
+
+    /// Check that groups may be nested `depth` deep
+    pub(crate) fn check_depth(&self, depth: usize) -> Result<(), Error> {
+        check("group depth", depth, self.max_depth)
+    }
+
+    /// Check that a virtual dataset may be read for `depth` others that are mapped from it
+    pub(crate) fn check_virtual_depth(&self, depth: usize) -> Result<(), Error> {
+        check("virtual dataset depth", depth, self.max_depth)
+    }
+
+    /// Check that a file may hold `objects` objects
+    pub(crate) fn check_objects(&self, objects: usize) -> Result<(), Error> {
+        check("number of objects", objects, self.max_objects)
+    }
+
+    /// Check that a chunk may take up `bytes`
+    pub(crate) fn check_chunk_size(&self, bytes: usize) -> Result<(), Error> {
+        check("chunk size", bytes, self.max_chunk_size)
+    }
+}
+
+fn check(limit: &'static str, value: usize, maximum: usize) -> Result<(), Error> {
+    if value > maximum {
+        return Err(Error::LimitExceeded {
+            limit,
+            value: value as u64,
+            maximum: maximum as u64,
+        });
+    }
+    Ok(())
+}
@@ -1,8 +1,13 @@
 use nom::bytes::streaming::{tag, take};
+use nom::combinator::map;
 use nom::error::context;
 use nom::error::{make_error, ErrorKind};
 use nom::multi::count;
 use nom::number::streaming::{le_u16, le_u24, le_u32, le_u64, le_u8};
+use std::convert::TryFrom;
+
+/// The value that addresses take when they do not point anywhere
+pub const UNDEFINED_ADDRESS: u64 = u64::MAX;
 
 type Result<'a, O> =
     std::result::Result<(&'a [u8], O), nom::Err<nom::error::VerboseError<&'a [u8]>>>;
@@ -10,74 +15,240 @@ type Result<'a, O> =
 #[derive(Debug)]
 pub struct Hdf5Superblock {
     pub superblock_version: u8,
-    pub free_space_storage_version: u8,
-    pub root_group_symbol_table_entry_version: u8,
-    pub shared_header_message_format_version: u8,
     pub offset_size: u8,
     pub length_size: u8,
-    pub group_leaf_node_k: u16,
-    pub group_internal_node_k: u16,
     pub file_consistency_flags: u32,
     pub base_address: u64,
-    pub address_of_file_free_space_info: u64,
+    /// Only versions 2 and 3 have an extension, which holds messages about the whole file
+    pub superblock_extension_address: u64,
     pub end_of_file_address: u64,
     pub driver_information_block_address: u64,
-    pub root_group_symbol_table_entry: SymbolTableEntry,
+    pub root_group_object_header_address: u64,
 }
 
+/// An address that is stored in `len` bytes, where all bits set means that it is undefined
+///
+/// Only the superblock is parsed with the size that it gives for offsets; every other structure is
+/// parsed with eight-byte offsets, which files with other sizes are rejected for.
 fn address<'a>(len: u8) -> impl Fn(&'a [u8]) -> Result<u64> {
-    nom::combinator::map_parser(take(len), le_u64)
+    move |input| {
+        let (input, address) = uint(usize::from(len))(input)?;
+        let undefined = match len {
+            1..=7 => (1 << (8 * u32::from(len))) - 1,
+            _ => UNDEFINED_ADDRESS,
+        };
+        Ok((
+            input,
+            if address == undefined {
+                UNDEFINED_ADDRESS
+            } else {
+                address
+            },
+        ))
+    }
+}
+
+/// A little-endian unsigned integer that is stored in `len` bytes
+fn uint<'a>(len: usize) -> impl Fn(&'a [u8]) -> Result<u64> {
+    move |input| {
+        let (input, bytes) = take(len)(input)?;
+        if len > 8 {
+            return Err(nom::Err::Failure(make_error(input, ErrorKind::TooLarge)));
+        }
+        let value = bytes
+            .iter()
+            .rev()
+            .fold(0, |value, byte| value << 8 | u64::from(*byte));
+        Ok((input, value))
+    }
+}
+
+/// The bytes that every superblock begins with
+pub const SIGNATURE: &[u8] = b"\x89\x48\x44\x46\x0d\x0a\x1a\x0a";
+
+/// Find where the superblock begins, which is at 0, 512, or a power of two above 512 when the
+/// file starts with a user block
+pub fn superblock_offset(contents: &[u8]) -> Option<usize> {
+    std::iter::once(0)
+        .chain(std::iter::successors(Some(512usize), |offset| {
+            offset.checked_mul(2)
+        }))
+        .take_while(|offset| *offset < contents.len())
+        .find(|offset| contents[*offset..].starts_with(SIGNATURE))
 }
 
-pub fn superblock(input: &[u8]) -> Result<Hdf5Superblock> {
+pub fn superblock(input: &[u8]) -> Result<'_, Hdf5Superblock> {
     context("superblock", |input| {
-        let (input, _) = tag(b"\x89\x48\x44\x46\x0d\x0a\x1a\x0a")(input)?;
+        let (input, _) = tag(SIGNATURE)(input)?;
         let (input, superblock_version) = le_u8(input)?;
-        let (input, free_space_storage_version) = le_u8(input)?;
-        let (input, root_group_symbol_table_entry_version) = le_u8(input)?;
-        let (input, _) = tag([0])(input)?;
-        let (input, shared_header_message_format_version) = le_u8(input)?;
-        let (input, offset_size) = le_u8(input)?;
-        let (input, length_size) = le_u8(input)?;
-        let (input, _) = tag([0])(input)?;
-        let (input, group_leaf_node_k) = le_u16(input)?;
-        let (input, group_internal_node_k) = le_u16(input)?;
-        let (input, file_consistency_flags) = le_u32(input)?;
-        let (input, base_address) = address(offset_size)(input)?;
-        let (input, address_of_file_free_space_info) = address(offset_size)(input)?;
-        let (input, end_of_file_address) = address(offset_size)(input)?;
-        let (input, driver_information_block_address) = address(offset_size)(input)?;
-        let (input, root_group_symbol_table_entry) = symbol_table_entry(input, offset_size)?;
+        match superblock_version {
+            0 | 1 => superblock_v0(input, superblock_version),
+            2 | 3 => superblock_v2(input, superblock_version),
+            _ => Err(nom::Err::Failure(make_error(input, ErrorKind::Tag))),
+        }
+    })(input)
+}
 
-        Ok((
-            input,
-            Hdf5Superblock {
-                superblock_version,
-                free_space_storage_version,
-                root_group_symbol_table_entry_version,
-                shared_header_message_format_version,
-                offset_size,
-                length_size,
-                group_leaf_node_k,
-                group_internal_node_k,
-                file_consistency_flags,
-                base_address,
-                address_of_file_free_space_info,
-                end_of_file_address,
-                driver_information_block_address,
-                root_group_symbol_table_entry,
+/// The rest of a version 0 or 1 superblock, which refers to the root group by a symbol table
+/// entry
+fn superblock_v0(input: &[u8], superblock_version: u8) -> Result<'_, Hdf5Superblock> {
+    let (input, _free_space_storage_version) = le_u8(input)?;
+    let (input, _root_group_symbol_table_entry_version) = le_u8(input)?;
+    let (input, _) = tag([0])(input)?;
+    let (input, _shared_header_message_format_version) = le_u8(input)?;
+    let (input, offset_size) = le_u8(input)?;
+    let (input, length_size) = le_u8(input)?;
+    let (input, _) = tag([0])(input)?;
+    let (input, _group_leaf_node_k) = le_u16(input)?;
+    let (input, _group_internal_node_k) = le_u16(input)?;
+    let (input, file_consistency_flags) = le_u32(input)?;
+    // Version 1 adds the K of chunk B-trees, and padding
+    let (input, _) = if superblock_version == 1 {
+        take(4usize)(input)?
+    } else {
+        (input, &input[..0])
+    };
+    let (input, base_address) = address(offset_size)(input)?;
+    let (input, _address_of_file_free_space_info) = address(offset_size)(input)?;
+    let (input, end_of_file_address) = address(offset_size)(input)?;
+    let (input, driver_information_block_address) = address(offset_size)(input)?;
+    let (input, root_group_symbol_table_entry) = symbol_table_entry(input, offset_size)?;
+
+    Ok((
+        input,
+        Hdf5Superblock {
+            superblock_version,
+            offset_size,
+            length_size,
+            file_consistency_flags,
+            base_address,
+            superblock_extension_address: UNDEFINED_ADDRESS,
+            end_of_file_address,
+            driver_information_block_address,
+            root_group_object_header_address: root_group_symbol_table_entry.object_header_address,
+        },
+    ))
+}
+
+/// The rest of a version 2 or 3 superblock, which refers to the root group's object header
+/// directly and keeps everything else in its extension
+fn superblock_v2(input: &[u8], superblock_version: u8) -> Result<'_, Hdf5Superblock> {
+    let (input, offset_size) = le_u8(input)?;
+    let (input, length_size) = le_u8(input)?;
+    let (input, file_consistency_flags) = le_u8(input)?;
+    let (input, base_address) = address(offset_size)(input)?;
+    let (input, superblock_extension_address) = address(offset_size)(input)?;
+    let (input, end_of_file_address) = address(offset_size)(input)?;
+    let (input, root_group_object_header_address) = address(offset_size)(input)?;
+    let (input, _checksum) = le_u32(input)?;
+
+    Ok((
+        input,
+        Hdf5Superblock {
+            superblock_version,
+            offset_size,
+            length_size,
+            file_consistency_flags: u32::from(file_consistency_flags),
+            base_address,
+            superblock_extension_address,
+            end_of_file_address,
+            driver_information_block_address: UNDEFINED_ADDRESS,
+            root_group_object_header_address,
+        },
+    ))
+}
+
+/// How the driver that wrote a file split it between member files, from the driver information
+/// block of a version 0 or 1 superblock
+#[derive(Debug)]
+pub enum DriverInfo {
+    /// The family driver keeps consecutive runs of `member_size` bytes in numbered files
+    Family { member_size: u64 },
+    /// The multi driver, which the split driver is a case of, keeps each kind of data in its own
+    /// file
+    Multi { members: Vec<MultiMember> },
+    /// A driver that is not known, by its name
+    Other(String),
+}
+
+#[derive(Debug)]
+pub struct MultiMember {
+    /// The first address that is kept in this member, which holds the addresses up to the next
+    /// member's
+    pub address: u64,
+    pub end_of_address: u64,
+    /// The member's file name, where `%s` stands for the name that the file was created with
+    pub name: String,
+}
+
+pub fn driver_info(input: &[u8]) -> Result<'_, DriverInfo> {
+    context("driver information block", |input| {
+        let (input, _version) = tag([0])(input)?;
+        let (input, _) = take(3usize)(input)?;
+        let (input, size) = le_u32(input)?;
+        let (input, name) = take(8usize)(input)?;
+        let (input, info) = take(size)(input)?;
+        let info = match name {
+            b"NCSAfami" => DriverInfo::Family {
+                member_size: le_u64(info)?.1,
             },
-        ))
+            b"NCSAmult" => DriverInfo::Multi {
+                members: multi_members(info)?.1,
+            },
+            _ => DriverInfo::Other(String::from_utf8_lossy(name).into_owned()),
+        };
+        Ok((input, info))
     })(input)
 }
 
+/// The members of a multi driver file, which are listed in the order that the kinds of data that
+/// they hold are first mapped to them
+fn multi_members(input: &[u8]) -> Result<'_, Vec<MultiMember>> {
+    // Which member each of the six kinds of data is kept in, by the kind that it is mapped to,
+    // where 0 means the kind's own member
+    let (input, map) = take(6usize)(input)?;
+    let (mut input, _) = take(2usize)(input)?;
+    let mut kinds = Vec::new();
+    for (kind, mapped) in (1..=6).zip(map) {
+        let kind = if *mapped == 0 { kind } else { *mapped };
+        if !(1..=6).contains(&kind) {
+            return Err(nom::Err::Failure(make_error(input, ErrorKind::Tag)));
+        }
+        if !kinds.contains(&kind) {
+            kinds.push(kind);
+        }
+    }
+    let mut ranges = Vec::with_capacity(kinds.len());
+    for _ in &kinds {
+        let (rest, address) = le_u64(input)?;
+        let (rest, end_of_address) = le_u64(rest)?;
+        ranges.push((address, end_of_address));
+        input = rest;
+    }
+    let mut members = Vec::with_capacity(kinds.len());
+    for (address, end_of_address) in ranges {
+        let length = input
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or(nom::Err::Incomplete(nom::Needed::Unknown))?;
+        let name = String::from_utf8_lossy(&input[..length]).into_owned();
+        input = take(std::cmp::min(pad8(length + 1), input.len()))(input)?.0;
+        members.push(MultiMember {
+            address,
+            end_of_address,
+            name,
+        });
+    }
+    Ok((input, members))
+}
+
 #[derive(Debug)]
 pub struct SymbolTable {
     pub version: u8,
     pub entries: Vec<SymbolTableEntry>,
 }
 
-pub fn symbol_table(input: &[u8], offset_size: u8) -> Result<SymbolTable> {
+pub fn symbol_table(input: &[u8], offset_size: u8) -> Result<'_, SymbolTable> {
     context("symbol table", |input| {
         let (input, _) = tag(b"SNOD")(input)?;
         let (input, version) = le_u8(input)?;
@@ -100,7 +271,7 @@ pub struct SymbolTableEntry {
     pub address_of_name_heap: u64,
 }
 
-pub fn symbol_table_entry(input: &[u8], offset_size: u8) -> Result<SymbolTableEntry> {
+pub fn symbol_table_entry(input: &[u8], offset_size: u8) -> Result<'_, SymbolTableEntry> {
     context("symbol table entry", |input| {
         let (
             input,
@@ -140,7 +311,7 @@ pub struct GroupEntry {
     pub pointer_to_symbol_table: u64,
 }
 
-pub fn group_entry(input: &[u8]) -> Result<GroupEntry> {
+pub fn group_entry(input: &[u8]) -> Result<'_, GroupEntry> {
     context("group entry", |input| {
         let (input, byte_offset_into_local_heap) = le_u64(input)?;
         let (input, pointer_to_symbol_table) = le_u64(input)?;
@@ -163,7 +334,7 @@ pub struct GroupNode {
     pub entries: Vec<GroupEntry>,
 }
 
-pub fn hdf5_node(input: &[u8], offset_size: u8) -> Result<GroupNode> {
+pub fn hdf5_node(input: &[u8], offset_size: u8) -> Result<'_, GroupNode> {
     context("HDF5 node", |input| {
         let (input, _) = tag(b"TREE")(input)?;
         let (input, _) = tag([0])(input)?; // We only support group nodes
@@ -171,7 +342,7 @@ pub fn hdf5_node(input: &[u8], offset_size: u8) -> Result<GroupNode> {
     })(input)
 }
 
-pub fn group_node(input: &[u8], offset_size: u8) -> Result<GroupNode> {
+pub fn group_node(input: &[u8], offset_size: u8) -> Result<'_, GroupNode> {
     context("group node", |input| {
         let (input, node_level) = le_u8(input)?;
         let (input, entries_used) = le_u16(input)?;
@@ -192,6 +363,67 @@ pub fn group_node(input: &[u8], offset_size: u8) -> Result<GroupNode> {
     })(input)
 }
 
+#[derive(Debug)]
+pub struct ChunkEntry {
+    pub chunk_size: u64,
+    pub filter_mask: u32,
+    pub offsets: Vec<u64>,
+    pub address: u64,
+}
+
+#[derive(Debug)]
+pub struct ChunkNode {
+    pub node_level: u8,
+    pub entries_used: u16,
+    pub address_of_left_sibling: u64,
+    pub address_of_right_sibling: u64,
+    pub entries: Vec<ChunkEntry>,
+}
+
+/// Parses a version 1 B-tree node which indexes the chunks of a dataset
+///
+/// `dimensionality` is the number of dimensions in the dataset's layout message, which is one more
+/// than the rank of the dataset.
+pub fn chunk_node(input: &[u8], dimensionality: u8, offset_size: u8) -> Result<'_, ChunkNode> {
+    context("chunk node", |input| {
+        let (input, _) = tag(b"TREE")(input)?;
+        let (input, _) = tag([1])(input)?;
+        let (input, node_level) = le_u8(input)?;
+        let (input, entries_used) = le_u16(input)?;
+        let (input, address_of_left_sibling) = address(offset_size)(input)?;
+        let (input, address_of_right_sibling) = address(offset_size)(input)?;
+        let (input, entries) = count(
+            |input| {
+                let (input, chunk_size) = map(le_u32, u64::from)(input)?;
+                let (input, filter_mask) = le_u32(input)?;
+                let (input, offsets) = count(le_u64, dimensionality as usize)(input)?;
+                let (input, address) = address(offset_size)(input)?;
+                Ok((
+                    input,
+                    ChunkEntry {
+                        chunk_size,
+                        filter_mask,
+                        offsets,
+                        address,
+                    },
+                ))
+            },
+            entries_used as usize,
+        )(input)?;
+
+        Ok((
+            input,
+            ChunkNode {
+                node_level,
+                entries_used,
+                address_of_left_sibling,
+                address_of_right_sibling,
+                entries,
+            },
+        ))
+    })(input)
+}
+
 #[derive(Debug)]
 pub struct LocalHeap {
     pub version: u8,
@@ -200,7 +432,7 @@ pub struct LocalHeap {
     pub address_of_data_segment: u64,
 }
 
-pub fn local_heap(input: &[u8], offset_size: u8, length_size: u8) -> Result<LocalHeap> {
+pub fn local_heap(input: &[u8], offset_size: u8, length_size: u8) -> Result<'_, LocalHeap> {
     context("local heap", |input| {
         let (input, _) = tag(b"HEAP")(input)?;
         let (input, version) = le_u8(input)?;
@@ -221,17 +453,429 @@ pub fn local_heap(input: &[u8], offset_size: u8, length_size: u8) -> Result<Loca
     })(input)
 }
 
+#[derive(Debug)]
+pub struct FractalHeapHeader {
+    pub heap_id_length: u16,
+    pub io_filters_length: u16,
+    pub flags: u8,
+    pub max_managed_object_size: u32,
+    pub huge_object_btree_address: u64,
+    pub table_width: u16,
+    pub starting_block_size: u64,
+    pub max_direct_block_size: u64,
+    pub max_heap_size: u16,
+    pub starting_rows: u16,
+    pub root_block_address: u64,
+    pub current_rows: u16,
+}
+
+pub fn fractal_heap_header(input: &[u8]) -> Result<'_, FractalHeapHeader> {
+    context("fractal heap header", |input| {
+        let (input, _) = tag(b"FRHP")(input)?;
+        let (input, _) = tag([0])(input)?; // Only version 0 exists
+        let (input, heap_id_length) = le_u16(input)?;
+        let (input, io_filters_length) = le_u16(input)?;
+        let (input, flags) = le_u8(input)?;
+        let (input, max_managed_object_size) = le_u32(input)?;
+        let (input, _next_huge_object_id) = le_u64(input)?;
+        let (input, huge_object_btree_address) = address(8)(input)?;
+        // Free space, and the counts and sizes of each kind of object
+        let (input, _) = take(8 * 10usize)(input)?;
+        let (input, table_width) = le_u16(input)?;
+        let (input, starting_block_size) = le_u64(input)?;
+        let (input, max_direct_block_size) = le_u64(input)?;
+        let (input, max_heap_size) = le_u16(input)?;
+        let (input, starting_rows) = le_u16(input)?;
+        let (input, root_block_address) = address(8)(input)?;
+        let (input, current_rows) = le_u16(input)?;
+        Ok((
+            input,
+            FractalHeapHeader {
+                heap_id_length,
+                io_filters_length,
+                flags,
+                max_managed_object_size,
+                huge_object_btree_address,
+                table_width,
+                starting_block_size,
+                max_direct_block_size,
+                max_heap_size,
+                starting_rows,
+                root_block_address,
+                current_rows,
+            },
+        ))
+    })(input)
+}
+
+/// The addresses of the children of a fractal heap indirect block, direct blocks first
+pub fn fractal_heap_indirect_block(
+    input: &[u8],
+    block_offset_size: usize,
+    direct_children: usize,
+    indirect_children: usize,
+) -> Result<'_, Vec<u64>> {
+    context("fractal heap indirect block", |input| {
+        let (input, _) = tag(b"FHIB")(input)?;
+        let (input, _) = tag([0])(input)?; // Only version 0 exists
+        let (input, _heap_header_address) = address(8)(input)?;
+        let (input, _block_offset) = uint(block_offset_size)(input)?;
+        count(address(8), direct_children + indirect_children)(input)
+    })(input)
+}
+
+/// The offset into the heap of a fractal heap direct block
+pub fn fractal_heap_direct_block(input: &[u8], block_offset_size: usize) -> Result<'_, u64> {
+    context("fractal heap direct block", |input| {
+        let (input, _) = tag(b"FHDB")(input)?;
+        let (input, _) = tag([0])(input)?; // Only version 0 exists
+        let (input, _heap_header_address) = address(8)(input)?;
+        uint(block_offset_size)(input)
+    })(input)
+}
+
+#[derive(Debug)]
+pub struct BTreeV2Header {
+    pub ty: u8,
+    pub node_size: u32,
+    pub record_size: u16,
+    pub depth: u16,
+    pub root_address: u64,
+    pub root_records: u16,
+    pub total_records: u64,
+}
+
+pub fn btree_v2_header(input: &[u8]) -> Result<'_, BTreeV2Header> {
+    context("version 2 B-tree header", |input| {
+        let (input, _) = tag(b"BTHD")(input)?;
+        let (input, _) = tag([0])(input)?; // Only version 0 exists
+        let (input, ty) = le_u8(input)?;
+        let (input, node_size) = le_u32(input)?;
+        let (input, record_size) = le_u16(input)?;
+        let (input, depth) = le_u16(input)?;
+        let (input, _split_percent) = le_u8(input)?;
+        let (input, _merge_percent) = le_u8(input)?;
+        let (input, root_address) = address(8)(input)?;
+        let (input, root_records) = le_u16(input)?;
+        let (input, total_records) = le_u64(input)?;
+        Ok((
+            input,
+            BTreeV2Header {
+                ty,
+                node_size,
+                record_size,
+                depth,
+                root_address,
+                root_records,
+                total_records,
+            },
+        ))
+    })(input)
+}
+
+/// The records in a version 2 B-tree leaf node
+pub fn btree_v2_leaf(input: &[u8], record_size: u16, records: u16) -> Result<'_, Vec<&[u8]>> {
+    context("version 2 B-tree leaf node", |input| {
+        let (input, _) = tag(b"BTLF")(input)?;
+        let (input, _) = tag([0])(input)?; // Only version 0 exists
+        let (input, _ty) = le_u8(input)?;
+        count(take(record_size), records as usize)(input)
+    })(input)
+}
+
+#[derive(Debug)]
+pub struct BTreeV2Child {
+    pub address: u64,
+    pub records: u64,
+}
+
+/// The records in a version 2 B-tree internal node, followed by its children
+///
+/// The number of records in each child is stored in `records_size` bytes, and the total number of
+/// records below each child in `total_records_size` bytes, which is zero for nodes just above the
+/// leaves.
+pub fn btree_v2_internal(
+    input: &[u8],
+    record_size: u16,
+    records: u16,
+    records_size: usize,
+    total_records_size: usize,
+) -> Result<'_, (Vec<&[u8]>, Vec<BTreeV2Child>)> {
+    context("version 2 B-tree internal node", |input| {
+        let (input, _) = tag(b"BTIN")(input)?;
+        let (input, _) = tag([0])(input)?; // Only version 0 exists
+        let (input, _ty) = le_u8(input)?;
+        let (input, node_records) = count(take(record_size), records as usize)(input)?;
+        let child = |input| {
+            let (input, address) = address(8)(input)?;
+            let (input, records) = uint(records_size)(input)?;
+            let (input, _total_records) = take(total_records_size)(input)?;
+            Ok((input, BTreeV2Child { address, records }))
+        };
+        let (input, children) = count(child, records as usize + 1)(input)?;
+        Ok((input, (node_records, children)))
+    })(input)
+}
+
+/// `n` addresses one after another, whose bytes are taken before any is parsed so that a count
+/// from a malformed file is checked against the input before anything is allocated for it
+fn addresses<'a>(n: usize) -> impl Fn(&'a [u8]) -> Result<Vec<u64>> {
+    move |input| {
+        let size = n
+            .checked_mul(8)
+            .ok_or_else(|| nom::Err::Failure(make_error(input, ErrorKind::TooLarge)))?;
+        let (input, bytes) = take(size)(input)?;
+        let (_, addresses) = count(address(8), n)(bytes)?;
+        Ok((input, addresses))
+    }
+}
+
+/// A chunk that a fixed array, an extensible array, or a version 2 B-tree indexes
+#[derive(Debug)]
+pub struct IndexedChunk {
+    pub address: u64,
+    /// The stored size and filter mask of a filtered chunk
+    pub filtered: Option<(u64, u32)>,
+}
+
+/// The address of a chunk, followed by its stored size and filter mask if it is filtered, where
+/// the size takes up the bytes that the other fields leave of `size`
+fn indexed_chunk<'a>(size: usize, filtered: bool) -> impl Fn(&'a [u8]) -> Result<IndexedChunk> {
+    move |input| {
+        let (input, address) = address(8)(input)?;
+        if !filtered {
+            return Ok((
+                input,
+                IndexedChunk {
+                    address,
+                    filtered: None,
+                },
+            ));
+        }
+        let size_len = size
+            .checked_sub(8 + 4)
+            .ok_or_else(|| nom::Err::Failure(make_error(input, ErrorKind::Verify)))?;
+        let (input, chunk_size) = uint(size_len)(input)?;
+        let (input, filter_mask) = le_u32(input)?;
+        Ok((
+            input,
+            IndexedChunk {
+                address,
+                filtered: Some((chunk_size, filter_mask)),
+            },
+        ))
+    }
+}
+
+/// A chunk in an element of a fixed or an extensible array, which is the whole of `input`
+pub fn chunk_element(input: &[u8], filtered: bool) -> Result<'_, IndexedChunk> {
+    context("chunk index element", indexed_chunk(input.len(), filtered))(input)
+}
+
+/// A chunk and its position in the grid of chunks, from a record of a version 2 B-tree of type 10,
+/// or of type 11 if it is `filtered`
+pub fn chunk_record(
+    input: &[u8],
+    rank: usize,
+    filtered: bool,
+) -> Result<'_, (IndexedChunk, Vec<u64>)> {
+    context("chunk record", |input: &[u8]| {
+        let size = input
+            .len()
+            .checked_sub(8 * rank)
+            .ok_or_else(|| nom::Err::Failure(make_error(input, ErrorKind::Verify)))?;
+        let (input, chunk) = indexed_chunk(size, filtered)(input)?;
+        let (input, scaled) = count(le_u64, rank)(input)?;
+        Ok((input, (chunk, scaled)))
+    })(input)
+}
+
+#[derive(Debug)]
+pub struct FixedArrayHeader {
+    pub element_size: u8,
+    pub page_bits: u8,
+    pub elements: u64,
+    pub data_block_address: u64,
+}
+
+pub fn fixed_array_header(input: &[u8]) -> Result<'_, FixedArrayHeader> {
+    context("fixed array header", |input| {
+        let (input, _) = tag(b"FAHD")(input)?;
+        let (input, _) = tag([0])(input)?; // Only version 0 exists
+        let (input, _client_id) = le_u8(input)?;
+        let (input, element_size) = le_u8(input)?;
+        let (input, page_bits) = le_u8(input)?;
+        let (input, elements) = le_u64(input)?;
+        let (input, data_block_address) = address(8)(input)?;
+        Ok((
+            input,
+            FixedArrayHeader {
+                element_size,
+                page_bits,
+                elements,
+                data_block_address,
+            },
+        ))
+    })(input)
+}
+
+/// The page bitmap of a fixed array data block, which is empty if the block is not paged
+///
+/// The elements of a block that is not paged follow the bitmap, and the pages of a paged block
+/// follow its checksum.
+pub fn fixed_array_data_block(input: &[u8], page_bitmap_size: usize) -> Result<'_, &[u8]> {
+    context("fixed array data block", |input| {
+        let (input, _) = tag(b"FADB")(input)?;
+        let (input, _) = tag([0])(input)?; // Only version 0 exists
+        let (input, _client_id) = le_u8(input)?;
+        let (input, _header_address) = address(8)(input)?;
+        take(page_bitmap_size)(input)
+    })(input)
+}
+
+#[derive(Debug)]
+pub struct ExtensibleArrayHeader {
+    pub element_size: u8,
+    pub max_elements_bits: u8,
+    pub index_block_elements: u8,
+    pub data_block_min_elements: u8,
+    pub super_block_min_data_blocks: u8,
+    pub page_bits: u8,
+    /// One more than the greatest index of an element that has been set
+    pub max_index_set: u64,
+    pub index_block_address: u64,
+}
+
+pub fn extensible_array_header(input: &[u8]) -> Result<'_, ExtensibleArrayHeader> {
+    context("extensible array header", |input| {
+        let (input, _) = tag(b"EAHD")(input)?;
+        let (input, _) = tag([0])(input)?; // Only version 0 exists
+        let (input, _client_id) = le_u8(input)?;
+        let (input, element_size) = le_u8(input)?;
+        let (input, max_elements_bits) = le_u8(input)?;
+        let (input, index_block_elements) = le_u8(input)?;
+        let (input, data_block_min_elements) = le_u8(input)?;
+        let (input, super_block_min_data_blocks) = le_u8(input)?;
+        let (input, page_bits) = le_u8(input)?;
+        // The numbers and sizes of the blocks
+        let (input, _) = take(8 * 4usize)(input)?;
+        let (input, max_index_set) = le_u64(input)?;
+        let (input, _elements) = le_u64(input)?;
+        let (input, index_block_address) = address(8)(input)?;
+        Ok((
+            input,
+            ExtensibleArrayHeader {
+                element_size,
+                max_elements_bits,
+                index_block_elements,
+                data_block_min_elements,
+                super_block_min_data_blocks,
+                page_bits,
+                max_index_set,
+                index_block_address,
+            },
+        ))
+    })(input)
+}
+
+#[derive(Debug)]
+pub struct ExtensibleArrayIndexBlock<'a> {
+    pub elements: &'a [u8],
+    pub data_block_addresses: Vec<u64>,
+    pub super_block_addresses: Vec<u64>,
+}
+
+pub fn extensible_array_index_block(
+    input: &[u8],
+    elements_size: usize,
+    data_blocks: usize,
+    super_blocks: usize,
+) -> Result<'_, ExtensibleArrayIndexBlock<'_>> {
+    context("extensible array index block", |input| {
+        let (input, _) = tag(b"EAIB")(input)?;
+        let (input, _) = tag([0])(input)?; // Only version 0 exists
+        let (input, _client_id) = le_u8(input)?;
+        let (input, _header_address) = address(8)(input)?;
+        let (input, elements) = take(elements_size)(input)?;
+        let (input, data_block_addresses) = addresses(data_blocks)(input)?;
+        let (input, super_block_addresses) = addresses(super_blocks)(input)?;
+        Ok((
+            input,
+            ExtensibleArrayIndexBlock {
+                elements,
+                data_block_addresses,
+                super_block_addresses,
+            },
+        ))
+    })(input)
+}
+
+/// The page bitmap of an extensible array super block, which is empty if its data blocks are not
+/// paged, and the addresses of its data blocks
+pub fn extensible_array_super_block(
+    input: &[u8],
+    block_offset_size: usize,
+    page_bitmap_size: usize,
+    data_blocks: usize,
+) -> Result<'_, (&[u8], Vec<u64>)> {
+    context("extensible array super block", |input| {
+        let (input, _) = tag(b"EASB")(input)?;
+        let (input, _) = tag([0])(input)?; // Only version 0 exists
+        let (input, _client_id) = le_u8(input)?;
+        let (input, _header_address) = address(8)(input)?;
+        let (input, _block_offset) = uint(block_offset_size)(input)?;
+        let (input, page_bitmap) = take(page_bitmap_size)(input)?;
+        let (input, data_block_addresses) = addresses(data_blocks)(input)?;
+        Ok((input, (page_bitmap, data_block_addresses)))
+    })(input)
+}
+
+/// The elements of an extensible array data block that is not paged
+pub fn extensible_array_data_block(
+    input: &[u8],
+    block_offset_size: usize,
+    elements_size: usize,
+) -> Result<'_, &[u8]> {
+    context("extensible array data block", |input| {
+        let (input, _) = tag(b"EADB")(input)?;
+        let (input, _) = tag([0])(input)?; // Only version 0 exists
+        let (input, _client_id) = le_u8(input)?;
+        let (input, _header_address) = address(8)(input)?;
+        let (input, _block_offset) = uint(block_offset_size)(input)?;
+        take(elements_size)(input)
+    })(input)
+}
+
 #[derive(Debug)]
 pub struct ObjectHeader {
     pub version: u8,
-    pub total_number_of_header_messages: u16,
+    // Version 2 headers do not count their messages
+    pub total_number_of_header_messages: Option<u16>,
     pub object_reference_count: u32,
-    pub object_header_size: u32,
+    pub object_header_size: u64,
+    pub flags: u8,
 }
 
-pub fn object_header(input: &[u8]) -> Result<ObjectHeader> {
+impl ObjectHeader {
+    /// The size of the prefix that comes before the body of each message
+    pub fn message_prefix_size(&self) -> usize {
+        match self.version {
+            1 => 8,
+            _ if self.flags & 0b100 != 0 => 6,
+            _ => 4,
+        }
+    }
+}
+
+pub fn object_header(input: &[u8]) -> Result<'_, ObjectHeader> {
+    if input.starts_with(b"OHDR") {
+        return object_header_v2(input);
+    }
     context("object header", |input| {
         let (input, version) = le_u8(input)?;
+        if version != 1 {
+            return Err(nom::Err::Failure(make_error(input, ErrorKind::Tag)));
+        }
         let (input, _) = tag([0])(input)?;
         let (input, total_number_of_header_messages) = le_u16(input)?;
         let (input, object_reference_count) = le_u32(input)?;
@@ -242,19 +886,63 @@ pub fn object_header(input: &[u8]) -> Result<ObjectHeader> {
             input,
             ObjectHeader {
                 version,
-                total_number_of_header_messages,
+                total_number_of_header_messages: Some(total_number_of_header_messages),
                 object_reference_count,
+                object_header_size: u64::from(object_header_size),
+                flags: 0,
+            },
+        ))
+    })(input)
+}
+
+fn object_header_v2(input: &[u8]) -> Result<'_, ObjectHeader> {
+    context("version 2 object header", |input| {
+        let (input, _) = tag(b"OHDR")(input)?;
+        let (input, version) = le_u8(input)?;
+        if version != 2 {
+            return Err(nom::Err::Failure(make_error(input, ErrorKind::Tag)));
+        }
+        let (input, flags) = le_u8(input)?;
+        // Access, modification, change and birth times
+        let (input, _) = if flags & 0b10_0000 != 0 {
+            take(16usize)(input)?
+        } else {
+            (input, &input[..0])
+        };
+        // Maximum compact and minimum dense attribute counts
+        let (input, _) = if flags & 0b1_0000 != 0 {
+            take(4usize)(input)?
+        } else {
+            (input, &input[..0])
+        };
+        let (input, object_header_size) = uint(1 << (flags & 0b11))(input)?;
+        Ok((
+            input,
+            ObjectHeader {
+                version,
+                total_number_of_header_messages: None,
+                object_reference_count: 1,
                 object_header_size,
+                flags,
             },
         ))
     })(input)
 }
 
 pub mod header {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum DataspaceKind {
+        Scalar,
+        Simple,
+        Null,
+    }
+
     #[derive(Debug, Clone)]
     pub struct Dataspace {
+        pub version: u8,
         pub dimensionality: u8,
         pub flags: u8,
+        pub kind: DataspaceKind,
         pub dimensions: Vec<u64>,
         pub max_dimensions: Option<Vec<u64>>,
     }
@@ -296,16 +984,144 @@ pub mod header {
         pub fill_value: Vec<u8>,
     }
 
+    /// Where the raw data of a dataset is kept in other files
     #[derive(Debug, Clone)]
-    pub struct DataLayout {
-        pub address: u64,
+    pub struct ExternalDataFiles {
+        /// The local heap that holds the names of the files
+        pub heap_address: u64,
+        /// The segments of the data in order, each of which is part of one file
+        pub segments: Vec<ExternalSegment>,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct ExternalSegment {
+        /// The offset of the file's name in the local heap
+        pub name_offset: u64,
+        /// Where the segment starts in the file
+        pub offset: u64,
+        /// The length of the segment, which is `UNDEFINED_ADDRESS` for a last segment that goes
+        /// on to the end of the file
         pub size: u64,
     }
 
+    #[derive(Debug, Clone)]
+    pub enum DataLayout {
+        Compact {
+            data: Vec<u8>,
+        },
+        Contiguous {
+            address: u64,
+            // Versions 1 and 2 do not record the size of contiguous storage
+            size: Option<u64>,
+        },
+        Chunked {
+            index: ChunkIndex,
+            // The last dimension is the size of a dataset element
+            dimensions: Vec<u64>,
+            /// Whether chunks that reach past the upper edge of the dataset are stored without
+            /// filters
+            unfiltered_edges: bool,
+        },
+        /// The mappings of a virtual dataset, which are kept in a global heap object
+        Virtual {
+            heap_address: u64,
+            index: u32,
+        },
+    }
+
+    /// Where the chunks of a dataset are found
+    #[derive(Debug, Clone)]
+    pub enum ChunkIndex {
+        /// The version 1 B-tree that layouts before version 4 use
+        BTreeV1 { address: u64 },
+        /// The only chunk of a dataset whose chunks are as large as its maximum shape, with its
+        /// stored size and filter mask when it is filtered
+        Single {
+            address: u64,
+            filtered: Option<(u64, u32)>,
+        },
+        /// Chunks that are all allocated when the dataset is made, one after another
+        Implicit { address: u64 },
+        /// A fixed array, for datasets whose maximum shape is fixed
+        FixedArray { address: u64 },
+        /// An extensible array, for datasets with one unlimited dimension
+        ExtensibleArray { address: u64 },
+        /// A version 2 B-tree, for datasets with more than one unlimited dimension
+        BTreeV2 { address: u64 },
+    }
+
+    /// Part of a virtual dataset, which is taken from a selection of a dataset in another file
+    #[derive(Debug, Clone)]
+    pub struct VirtualMapping {
+        /// The name of the source file, which is `.` for the virtual dataset's own file, and may
+        /// contain `%b` to stand for the number of a block of an unlimited selection
+        pub file: String,
+        /// The path of the source dataset, which may also contain `%b`
+        pub dataset: String,
+        pub source: Selection,
+        /// The elements of the virtual dataset that the source's elements go to, in order
+        pub destination: Selection,
+    }
+
+    /// Elements of a dataspace, as a selection is stored
+    #[derive(Debug, Clone)]
+    pub enum Selection {
+        None,
+        All,
+        /// Single elements in the order that they were selected
+        Points(Vec<Vec<u64>>),
+        /// A regular hyperslab, whose count or block may be `UNLIMITED` along one dimension
+        Regular {
+            start: Vec<u64>,
+            stride: Vec<u64>,
+            count: Vec<u64>,
+            block: Vec<u64>,
+        },
+        /// Blocks by their first and last elements
+        Blocks(Vec<(Vec<u64>, Vec<u64>)>),
+    }
+
+    /// The count or block of an unlimited selection, which goes on to the end of the dataspace
+    pub const UNLIMITED: u64 = u64::MAX;
+
+    #[derive(Debug, Clone)]
+    pub struct Filter {
+        pub id: u16,
+        pub name: Option<String>,
+        pub flags: u16,
+        pub client_data: Vec<u32>,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct FilterPipeline {
+        pub version: u8,
+        pub filters: Vec<Filter>,
+    }
+
+    /// A message that is either stored in place, or is shared and stored elsewhere
+    #[derive(Debug, Clone)]
+    pub enum Shared<T> {
+        Here(T),
+        /// In the object header at this address, as committed datatypes are
+        ObjectHeader(u64),
+        /// In the shared message heap, with this heap ID
+        Heap(Vec<u8>),
+    }
+
+    impl<T> Shared<T> {
+        /// The message, once any sharing has been resolved
+        pub fn here(self) -> Option<T> {
+            match self {
+                Self::Here(message) => Some(message),
+                _ => None,
+            }
+        }
+    }
+
     #[derive(Debug, Clone)]
     pub struct Attribute {
-        pub datatype: DataType,
-        pub dataspace: Dataspace,
+        pub datatype: Shared<DataType>,
+        pub dataspace: Shared<Dataspace>,
         pub data: Vec<u8>,
         pub name: String,
     }
@@ -322,6 +1138,68 @@ pub mod header {
         pub local_heap_address: u64,
     }
 
+    #[derive(Debug, Clone)]
+    pub struct LinkInfo {
+        pub max_creation_index: Option<u64>,
+        pub fractal_heap_address: u64,
+        pub name_btree_address: u64,
+        pub creation_order_btree_address: Option<u64>,
+    }
+
+    #[derive(Debug, Clone)]
+    pub enum LinkTarget {
+        Hard(u64),
+        Soft(String),
+        External { file: String, path: String },
+        UserDefined { ty: u8, data: Vec<u8> },
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct Link {
+        pub name: String,
+        pub target: LinkTarget,
+    }
+
+    /// Where the indexes of messages that are shared through the shared message heap are
+    #[derive(Debug, Clone)]
+    pub struct SharedMessageTable {
+        pub address: u64,
+        pub indexes: u8,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct SharedMessageIndex {
+        /// 0 when the index is a list and 1 when it is a version 2 B-tree
+        pub index_type: u8,
+        /// Bit `n` is set when messages of type `n` are shared through this index
+        pub message_types: u16,
+        pub min_message_size: u32,
+        pub messages: u16,
+        pub index_address: u64,
+        pub fractal_heap_address: u64,
+    }
+
+    impl SharedMessageIndex {
+        /// Whether messages of `message_type` are shared through this index
+        pub fn holds(&self, message_type: u16) -> bool {
+            // Old fill value messages are shared as new ones
+            let message_type = if message_type == 0x4 {
+                0x5
+            } else {
+                message_type
+            };
+            message_type < 16 && self.message_types & 1 << message_type != 0
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct AttributeInfo {
+        pub max_creation_index: Option<u16>,
+        pub fractal_heap_address: u64,
+        pub name_btree_address: u64,
+        pub creation_order_btree_address: Option<u64>,
+    }
+
     #[derive(Debug, Clone)]
     pub struct ObjectModificationTime {
         pub seconds_after_unix_epoch: u32,
@@ -331,38 +1209,71 @@ pub mod header {
     pub enum Message {
         Nil,
         Dataspace(Dataspace),
-        //LinkInfo,
+        LinkInfo(LinkInfo),
         DataType(DataType),
+        /// The fill value of a dataset in the message that only old files use
+        FillValueOld(Vec<u8>),
         DataStorageFillValue(DataStorageFillValue),
-        /*
-        Link,
-        DataStorageExternal,
-        */
+        Link(Link),
+        ExternalDataFiles(ExternalDataFiles),
         DataLayout(DataLayout),
+        GroupInfo,
+        FilterPipeline(FilterPipeline),
         Attribute(Attribute),
-        /*
-        ObjectComment,
-        SharedMessageTable,
-        */
+        SharedMessageTable(SharedMessageTable),
         ObjectHeaderContinuation(ObjectHeaderContinuation),
         SymbolTable(SymbolTable),
         ObjectModificationTime(ObjectModificationTime),
-        /*
-        BtreeKValues,
-        DriverInfo,
-        AttributeInfo,
-        ObjectReferenceCount,
-        */
+        AttributeInfo(AttributeInfo),
+        /// A message of a type that this library does not read, with its type
+        Unknown(u16),
+        /// A message of any type that is stored elsewhere, which is replaced by the message it
+        /// refers to when the object header is read
+        Shared {
+            message_type: u16,
+            location: Shared<Box<Message>>,
+        },
+    }
+
+    impl Message {
+        /// The number that identifies this type of message in an object header
+        pub fn message_type(&self) -> u16 {
+            match self {
+                Self::Nil => 0x0,
+                Self::Dataspace(_) => 0x1,
+                Self::LinkInfo(_) => 0x2,
+                Self::DataType(_) => 0x3,
+                Self::FillValueOld(_) => 0x4,
+                Self::DataStorageFillValue(_) => 0x5,
+                Self::Link(_) => 0x6,
+                Self::ExternalDataFiles(_) => 0x7,
+                Self::DataLayout(_) => 0x8,
+                Self::GroupInfo => 0xA,
+                Self::FilterPipeline(_) => 0xB,
+                Self::Attribute(_) => 0xC,
+                Self::SharedMessageTable(_) => 0xF,
+                Self::ObjectHeaderContinuation(_) => 0x10,
+                Self::SymbolTable(_) => 0x11,
+                Self::ObjectModificationTime(_) => 0x12,
+                Self::AttributeInfo(_) => 0x15,
+                Self::Unknown(message_type) | Self::Shared { message_type, .. } => *message_type,
+            }
+        }
     }
 }
 
-fn datatype(input: &[u8], message_size: u16) -> Result<header::DataType> {
+fn datatype(input: &[u8], message_size: u16) -> Result<'_, header::DataType> {
     use header::DatatypeClass::*;
     context("datatype", |input| {
         let (input, class_and_version) = le_u8(input)?;
         let (input, class_bitfields) = le_u24(input)?;
         let (input, size) = le_u32(input)?;
-        let (input, properties) = count(le_u8, message_size as usize - 8)(input)?;
+        // The properties take up whatever the message has after the eight bytes above
+        let properties_size = match usize::from(message_size).checked_sub(8) {
+            Some(size) => size,
+            None => return Err(nom::Err::Failure(make_error(input, ErrorKind::Eof))),
+        };
+        let (input, properties) = count(le_u8, properties_size)(input)?;
 
         let version = class_and_version >> 4;
         let raw_class = class_and_version & 0b0000_1111;
@@ -398,30 +1309,57 @@ fn datatype(input: &[u8], message_size: u16) -> Result<header::DataType> {
     })(input)
 }
 
-fn dataspace(input: &[u8]) -> Result<header::Dataspace> {
+fn dataspace(input: &[u8]) -> Result<'_, header::Dataspace> {
+    use header::DataspaceKind;
     context("dataspace", |input| {
-        let (input, _) = tag([1])(input)?;
+        let (input, version) = le_u8(input)?;
         let (input, dimensionality) = le_u8(input)?;
         let (input, flags) = le_u8(input)?;
-        let (input, _ty) = le_u8(input)?;
-        // Eat the unused bytes in version 1
-        let (input, _) = nom::bytes::streaming::take(4usize)(input)?;
-        let (input, (dimensions, max_dimensions)) = if flags == 0 {
-            let (input, dimensions) = count(le_u64, dimensionality as usize)(input)?;
-            (input, (dimensions, None))
-        } else if flags == 1 {
-            let (input, dimensions) = count(le_u64, dimensionality as usize)(input)?;
+        let (input, kind) = match version {
+            1 => {
+                // Eat the reserved bytes in version 1
+                let (input, _) = take(5usize)(input)?;
+                let kind = if dimensionality == 0 {
+                    DataspaceKind::Scalar
+                } else {
+                    DataspaceKind::Simple
+                };
+                (input, kind)
+            }
+            2 => {
+                let (input, ty) = le_u8(input)?;
+                let kind = match ty {
+                    0 => DataspaceKind::Scalar,
+                    1 => DataspaceKind::Simple,
+                    2 => DataspaceKind::Null,
+                    _ => return Err(nom::Err::Failure(make_error(input, ErrorKind::Alt))),
+                };
+                (input, kind)
+            }
+            _ => return Err(nom::Err::Failure(make_error(input, ErrorKind::Tag))),
+        };
+        let (input, dimensions) = count(le_u64, dimensionality as usize)(input)?;
+        let (input, max_dimensions) = if flags & 0b1 != 0 {
             let (input, max_dimensions) = count(le_u64, dimensionality as usize)(input)?;
-            (input, (dimensions, Some(max_dimensions)))
+            (input, Some(max_dimensions))
+        } else {
+            (input, None)
+        };
+        // Permutation indices were specified but never implemented by the reference library, so
+        // they are parsed only to get past them
+        let (input, _) = if version == 1 && flags & 0b10 != 0 {
+            count(le_u64, dimensionality as usize)(input)?
         } else {
-            unimplemented!("Permutation indices are not supported");
+            (input, Vec::new())
         };
 
         Ok((
             input,
             header::Dataspace {
+                version,
                 dimensionality,
                 flags,
+                kind,
                 dimensions,
                 max_dimensions,
             },
@@ -429,85 +1367,331 @@ fn dataspace(input: &[u8]) -> Result<header::Dataspace> {
     })(input)
 }
 
-pub fn fill_value(input: &[u8]) -> Result<header::DataStorageFillValue> {
+fn external_data_files(input: &[u8]) -> Result<'_, header::ExternalDataFiles> {
+    context("external data files", |input| {
+        let (input, _version) = tag([1])(input)?;
+        let (input, _) = take(3usize)(input)?;
+        let (input, _allocated_slots) = le_u16(input)?;
+        let (input, used_slots) = le_u16(input)?;
+        let (input, heap_address) = address(8)(input)?;
+        let (input, segments) = count(
+            |input| {
+                let (input, name_offset) = le_u64(input)?;
+                let (input, offset) = le_u64(input)?;
+                let (input, size) = le_u64(input)?;
+                Ok((
+                    input,
+                    header::ExternalSegment {
+                        name_offset,
+                        offset,
+                        size,
+                    },
+                ))
+            },
+            usize::from(used_slots),
+        )(input)?;
+        Ok((
+            input,
+            header::ExternalDataFiles {
+                heap_address,
+                segments,
+            },
+        ))
+    })(input)
+}
+
+/// A fill value message, in any of its versions
+pub fn fill_value(input: &[u8]) -> Result<'_, header::DataStorageFillValue> {
     context("fill value", |input| {
-        let (input, version) = le_u8(input)?; // Only support version 1
-        if version == 2 {
-            let (input, space_allocation_time) = le_u8(input)?;
-            let (input, fill_value_write_time) = le_u8(input)?;
-            let (input, fill_value_defined) = le_u8(input)?;
-            let (input, size) = if fill_value_defined > 0 {
-                le_u32(input)?
-            } else {
-                (input, 0)
-            };
-            let (input, fill_value) = if fill_value_defined > 0 {
-                count(le_u8, size as usize)(input)?
-            } else {
-                (input, Vec::new())
+        let (input, version) = le_u8(input)?;
+        let (input, (space_allocation_time, fill_value_write_time, fill_value_defined)) =
+            match version {
+                1 | 2 => nom::sequence::tuple((le_u8, le_u8, le_u8))(input)?,
+                // Version 3 packs the times and whether a value is defined into one byte
+                3 => {
+                    let (input, flags) = le_u8(input)?;
+                    (input, (flags & 0b11, flags >> 2 & 0b11, flags >> 5 & 0b1))
+                }
+                _ => return Err(nom::Err::Failure(make_error(input, ErrorKind::Tag))),
             };
-            Ok((
-                input,
-                header::DataStorageFillValue {
-                    space_allocation_time,
-                    fill_value_write_time,
-                    fill_value_defined,
-                    size,
-                    fill_value,
-                },
-            ))
+        // Version 1 always has a size, which is zero when there is no value
+        let (input, size) = if version == 1 || fill_value_defined > 0 {
+            le_u32(input)?
         } else {
-            unimplemented!("Unsupported DataStorageFillValue version {}", version);
-        }
+            (input, 0)
+        };
+        let (input, fill_value) = take(size)(input)?;
+        Ok((
+            input,
+            header::DataStorageFillValue {
+                space_allocation_time,
+                fill_value_write_time,
+                fill_value_defined,
+                size,
+                fill_value: fill_value.to_vec(),
+            },
+        ))
+    })(input)
+}
+
+/// The old fill value message, which is only a value
+pub fn fill_value_old(input: &[u8]) -> Result<'_, Vec<u8>> {
+    context("old fill value", |input| {
+        let (input, size) = le_u32(input)?;
+        let (input, fill_value) = take(size)(input)?;
+        Ok((input, fill_value.to_vec()))
     })(input)
 }
 
-pub fn data_layout(input: &[u8]) -> Result<header::DataLayout> {
+pub fn data_layout(input: &[u8]) -> Result<'_, header::DataLayout> {
+    use header::DataLayout;
     context("data layout", |input| {
         let (input, version) = le_u8(input)?;
-        if version != 3 {
-            unimplemented!("Unsupported DataLayout version {}", version);
+        match version {
+            1 | 2 => {
+                let (input, dimensionality) = le_u8(input)?;
+                let (input, layout_class) = le_u8(input)?;
+                let (input, _) = take(5usize)(input)?;
+                let (input, data_address) = if layout_class == 0 {
+                    (input, None)
+                } else {
+                    let (input, data_address) = address(8)(input)?;
+                    (input, Some(data_address))
+                };
+                let (input, dimensions) = count(le_u32, dimensionality as usize)(input)?;
+                match (layout_class, data_address) {
+                    (0, _) => {
+                        let (input, size) = le_u32(input)?;
+                        let (input, data) = take(size)(input)?;
+                        let data = data.to_vec();
+                        Ok((input, DataLayout::Compact { data }))
+                    }
+                    (1, Some(address)) => Ok((
+                        input,
+                        DataLayout::Contiguous {
+                            address,
+                            size: None,
+                        },
+                    )),
+                    (2, Some(address)) => Ok((
+                        input,
+                        DataLayout::Chunked {
+                            index: header::ChunkIndex::BTreeV1 { address },
+                            dimensions: dimensions.into_iter().map(u64::from).collect(),
+                            unfiltered_edges: false,
+                        },
+                    )),
+                    _ => Err(nom::Err::Failure(make_error(input, ErrorKind::Alt))),
+                }
+            }
+            3 | 4 => {
+                let (input, layout_class) = le_u8(input)?;
+                match layout_class {
+                    0 => {
+                        let (input, size) = le_u16(input)?;
+                        let (input, data) = take(size)(input)?;
+                        let data = data.to_vec();
+                        Ok((input, DataLayout::Compact { data }))
+                    }
+                    1 => {
+                        let (input, data_address) = address(8)(input)?;
+                        let (input, size) = address(8)(input)?;
+                        Ok((
+                            input,
+                            DataLayout::Contiguous {
+                                address: data_address,
+                                size: Some(size),
+                            },
+                        ))
+                    }
+                    // Version 4 stores chunks with other kinds of index
+                    2 if version == 3 => {
+                        let (input, dimensionality) = le_u8(input)?;
+                        let (input, btree_address) = address(8)(input)?;
+                        let (input, dimensions) = count(le_u32, dimensionality as usize)(input)?;
+                        Ok((
+                            input,
+                            DataLayout::Chunked {
+                                index: header::ChunkIndex::BTreeV1 {
+                                    address: btree_address,
+                                },
+                                dimensions: dimensions.into_iter().map(u64::from).collect(),
+                                unfiltered_edges: false,
+                            },
+                        ))
+                    }
+                    2 => {
+                        let (input, flags) = le_u8(input)?;
+                        let (input, dimensionality) = le_u8(input)?;
+                        let (input, encoded_len) = le_u8(input)?;
+                        if !(1..=8).contains(&encoded_len) {
+                            return Err(nom::Err::Failure(make_error(input, ErrorKind::Verify)));
+                        }
+                        let (input, dimensions) =
+                            count(uint(encoded_len as usize), dimensionality as usize)(input)?;
+                        let (input, index_type) = le_u8(input)?;
+                        // Each type of index keeps its own parameters before the index address,
+                        // which are kept again in the index itself
+                        let (input, filtered) = match index_type {
+                            1 if flags & 0b10 != 0 => {
+                                let (input, size) = le_u64(input)?;
+                                let (input, filter_mask) = le_u32(input)?;
+                                (input, Some((size, filter_mask)))
+                            }
+                            1 | 2 => (input, None),
+                            3 => (take(1usize)(input)?.0, None),
+                            4 => (take(5usize)(input)?.0, None),
+                            5 => (take(6usize)(input)?.0, None),
+                            _ => return Err(nom::Err::Failure(make_error(input, ErrorKind::Alt))),
+                        };
+                        let (input, address) = address(8)(input)?;
+                        let index = match index_type {
+                            1 => header::ChunkIndex::Single { address, filtered },
+                            2 => header::ChunkIndex::Implicit { address },
+                            3 => header::ChunkIndex::FixedArray { address },
+                            4 => header::ChunkIndex::ExtensibleArray { address },
+                            _ => header::ChunkIndex::BTreeV2 { address },
+                        };
+                        Ok((
+                            input,
+                            DataLayout::Chunked {
+                                index,
+                                dimensions,
+                                unfiltered_edges: flags & 0b1 != 0,
+                            },
+                        ))
+                    }
+                    3 if version == 4 => {
+                        let (input, heap_address) = address(8)(input)?;
+                        let (input, index) = le_u32(input)?;
+                        Ok((
+                            input,
+                            DataLayout::Virtual {
+                                heap_address,
+                                index,
+                            },
+                        ))
+                    }
+                    _ => Err(nom::Err::Failure(make_error(input, ErrorKind::Alt))),
+                }
+            }
+            _ => Err(nom::Err::Failure(make_error(input, ErrorKind::Tag))),
         }
-        let (input, layout_class) = le_u8(input)?;
-        if layout_class != 1 {
-            unimplemented!("Unsupported DataLayout class {}", layout_class);
+    })(input)
+}
+
+pub fn filter_pipeline(input: &[u8]) -> Result<'_, header::FilterPipeline> {
+    context("filter pipeline", |input| {
+        let (input, version) = le_u8(input)?;
+        if version != 1 && version != 2 {
+            return Err(nom::Err::Failure(make_error(input, ErrorKind::Tag)));
         }
-        let (input, data_address) = address(8)(input)?;
-        let (input, size) = address(8)(input)?;
-        let (input, _) = take(6usize)(input)?;
+        let (input, number_of_filters) = le_u8(input)?;
+        let (input, _) = if version == 1 {
+            take(6usize)(input)?
+        } else {
+            (input, &input[..0])
+        };
+        let (input, filters) = count(|i| filter(i, version), number_of_filters as usize)(input)?;
+        Ok((input, header::FilterPipeline { version, filters }))
+    })(input)
+}
 
+fn filter(input: &[u8], version: u8) -> Result<'_, header::Filter> {
+    context("filter", |input| {
+        let (input, id) = le_u16(input)?;
+        // Version 2 omits the name for the filters that are predefined by the library
+        let (input, name_length) = if version == 1 || id >= 256 {
+            le_u16(input)?
+        } else {
+            (input, 0)
+        };
+        let (input, flags) = le_u16(input)?;
+        let (input, number_of_values) = le_u16(input)?;
+        let (input, name) = take(name_length)(input)?;
+        let name = if name.is_empty() {
+            None
+        } else {
+            let name = name
+                .iter()
+                .take_while(|b| **b != 0)
+                .copied()
+                .collect::<Vec<_>>();
+            Some(String::from_utf8_lossy(&name).into_owned())
+        };
+        let (input, client_data) = count(le_u32, number_of_values as usize)(input)?;
+        // Version 1 pads the client data to a multiple of eight bytes
+        let (input, _) = if version == 1 && number_of_values % 2 == 1 {
+            take(4usize)(input)?
+        } else {
+            (input, &input[..0])
+        };
         Ok((
             input,
-            header::DataLayout {
-                address: data_address,
-                size,
+            header::Filter {
+                id,
+                name,
+                flags,
+                client_data,
             },
         ))
     })(input)
 }
 
-fn attribute(input: &[u8], message_size: u16) -> Result<header::Attribute> {
+fn attribute(input: &[u8]) -> Result<'_, header::Attribute> {
     context("attribute", |input| {
-        let (input, _) = tag([1])(input)?;
-        let (input, _) = tag([0])(input)?;
+        let (input, version) = le_u8(input)?;
+        if !(1..=3).contains(&version) {
+            return Err(nom::Err::Failure(make_error(input, ErrorKind::Tag)));
+        }
+        // Version 1 reserves the byte that later versions use for flags
+        let (input, flags) = le_u8(input)?;
+        let flags = if version == 1 { 0 } else { flags };
         let (input, name_size) = le_u16(input)?;
         let (input, datatype_size) = le_u16(input)?;
         let (input, dataspace_size) = le_u16(input)?;
+        // Version 3 adds the character set of the name, which is ASCII or UTF-8 and so can
+        // always be decoded as UTF-8
+        let (input, _character_set) = if version == 3 {
+            le_u8(input)?
+        } else {
+            (input, 0)
+        };
+        // Version 1 pads each of the following fields to a multiple of eight bytes
+        let padded = |size: u16| {
+            if version == 1 {
+                pad8(size)
+            } else {
+                usize::from(size)
+            }
+        };
 
-        let (_, name) = nom::bytes::streaming::take(name_size)(input)?;
-        let name =
-            String::from_utf8(name.iter().take_while(|b| **b > 0).copied().collect()).unwrap();
-        let input = &input[pad8(name_size)..];
+        let (input, name) = take(padded(name_size))(input)?;
+        let name = name
+            .iter()
+            .take(name_size as usize)
+            .take_while(|b| **b > 0)
+            .copied()
+            .collect::<Vec<_>>();
+        let name = String::from_utf8_lossy(&name).into_owned();
 
-        let (_, datatype) = datatype(input, datatype_size)?;
-        let input = &input[pad8(datatype_size)..];
+        let (input, datatype_bytes) = take(padded(datatype_size))(input)?;
+        let datatype_bytes = &datatype_bytes[..datatype_size as usize];
+        let (_, datatype) = if flags & 0b1 != 0 {
+            shared_message(datatype_bytes)?
+        } else {
+            map(|i| datatype(i, datatype_size), header::Shared::Here)(datatype_bytes)?
+        };
 
-        let (_, dataspace) = dataspace(input)?;
-        let input = &input[pad8(dataspace_size)..];
+        let (input, dataspace_bytes) = take(padded(dataspace_size))(input)?;
+        let (_, dataspace) = if flags & 0b10 != 0 {
+            shared_message(dataspace_bytes)?
+        } else {
+            map(dataspace, header::Shared::Here)(dataspace_bytes)?
+        };
 
-        let data_len = message_size as usize
-            - (8 + pad8(name_size) + pad8(datatype_size) + pad8(dataspace_size));
-        let (input, data) = nom::bytes::streaming::take(data_len)(input)?;
+        // The message was framed by its size, so the data is everything that is left
+        let (input, data) = take(input.len())(input)?;
 
         Ok((
             input,
@@ -521,14 +1705,35 @@ fn attribute(input: &[u8], message_size: u16) -> Result<header::Attribute> {
     })(input)
 }
 
-pub fn object_header_continuation(input: &[u8]) -> Result<header::ObjectHeaderContinuation> {
+/// A reference to a message that is stored elsewhere, so that several objects can share it
+fn shared_message<T>(input: &[u8]) -> Result<'_, header::Shared<T>> {
+    context("shared message", |input| {
+        let (input, version) = le_u8(input)?;
+        let (input, ty) = le_u8(input)?;
+        match version {
+            1 => {
+                let (input, _) = take(6usize)(input)?;
+                map(address(8), header::Shared::ObjectHeader)(input)
+            }
+            2 => map(address(8), header::Shared::ObjectHeader)(input),
+            3 => match ty {
+                1 => map(take(8usize), |id: &[u8]| header::Shared::Heap(id.to_vec()))(input),
+                2 => map(address(8), header::Shared::ObjectHeader)(input),
+                _ => Err(nom::Err::Failure(make_error(input, ErrorKind::Alt))),
+            },
+            _ => Err(nom::Err::Failure(make_error(input, ErrorKind::Tag))),
+        }
+    })(input)
+}
+
+pub fn object_header_continuation(input: &[u8]) -> Result<'_, header::ObjectHeaderContinuation> {
     context("object header continuation", |input| {
         let (input, (offset, length)) = nom::sequence::tuple((address(8), address(8)))(input)?;
         Ok((input, header::ObjectHeaderContinuation { length, offset }))
     })(input)
 }
 
-pub fn symbol_table_message(input: &[u8]) -> Result<header::SymbolTable> {
+pub fn symbol_table_message(input: &[u8]) -> Result<'_, header::SymbolTable> {
     context("symbol table message", |input| {
         let (input, btree_address) = address(8)(input)?;
         let (input, local_heap_address) = address(8)(input)?;
@@ -543,7 +1748,7 @@ pub fn symbol_table_message(input: &[u8]) -> Result<header::SymbolTable> {
     })(input)
 }
 
-pub fn object_modification_time(input: &[u8]) -> Result<header::ObjectModificationTime> {
+pub fn object_modification_time(input: &[u8]) -> Result<'_, header::ObjectModificationTime> {
     context("object modification time", |input| {
         let (input, _) = tag([1])(input)?; // version 1 is the only allowed by the standard
         let (input, _) = tag([0, 0, 0])(input)?; // padding
@@ -557,51 +1762,418 @@ pub fn object_modification_time(input: &[u8]) -> Result<header::ObjectModificati
     })(input)
 }
 
-pub fn header_message(input: &[u8]) -> Result<header::Message> {
-    context("header message", |input| {
-        let (input, message_type) = le_u16(input)?;
-        let (input, message_size) = le_u16(input)?;
-        let (input, _flags) = le_u8(input)?;
-        let (input, _) = tag([0, 0, 0])(input)?;
-        use header::Message;
-        use nom::combinator::map;
-        match message_type {
-            0x0 => Ok((input, header::Message::Nil)),
-            0x1 => map(dataspace, Message::Dataspace)(input),
-            0x3 => datatype(input, message_size).map(|(i, dtype)| (i, Message::DataType(dtype))),
-            0x5 => map(fill_value, Message::DataStorageFillValue)(input),
-            0x8 => map(data_layout, Message::DataLayout)(input),
-            0xC => attribute(input, message_size).map(|(i, attr)| (i, Message::Attribute(attr))),
-            0x10 => map(
-                object_header_continuation,
-                Message::ObjectHeaderContinuation,
-            )(input),
-            0x11 => map(symbol_table_message, Message::SymbolTable)(input),
-            0x12 => map(object_modification_time, Message::ObjectModificationTime)(input),
+pub fn link_info(input: &[u8]) -> Result<'_, header::LinkInfo> {
+    context("link info", |input| {
+        let (input, _) = tag([0])(input)?;
+        let (input, flags) = le_u8(input)?;
+        let (input, max_creation_index) = if flags & 0b1 != 0 {
+            map(le_u64, Some)(input)?
+        } else {
+            (input, None)
+        };
+        let (input, fractal_heap_address) = address(8)(input)?;
+        let (input, name_btree_address) = address(8)(input)?;
+        let (input, creation_order_btree_address) = if flags & 0b10 != 0 {
+            map(address(8), Some)(input)?
+        } else {
+            (input, None)
+        };
+        Ok((
+            input,
+            header::LinkInfo {
+                max_creation_index,
+                fractal_heap_address,
+                name_btree_address,
+                creation_order_btree_address,
+            },
+        ))
+    })(input)
+}
+
+pub fn link(input: &[u8]) -> Result<'_, header::Link> {
+    use header::LinkTarget;
+    context("link", |input| {
+        let (input, _) = tag([1])(input)?;
+        let (input, flags) = le_u8(input)?;
+        let (input, ty) = if flags & 0b1000 != 0 {
+            le_u8(input)?
+        } else {
+            (input, 0)
+        };
+        let (input, _creation_order) = if flags & 0b100 != 0 {
+            take(8usize)(input)?
+        } else {
+            (input, &input[..0])
+        };
+        // The name is ASCII or UTF-8, so it can always be decoded as UTF-8
+        let (input, _character_set) = if flags & 0b1_0000 != 0 {
+            le_u8(input)?
+        } else {
+            (input, 0)
+        };
+        let (input, name_length) = uint(1 << (flags & 0b11))(input)?;
+        let (input, name) = take(name_length)(input)?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        let (input, target) = match ty {
+            0 => map(address(8), LinkTarget::Hard)(input)?,
+            1 => {
+                let (input, length) = le_u16(input)?;
+                let (input, path) = take(length)(input)?;
+                (
+                    input,
+                    LinkTarget::Soft(String::from_utf8_lossy(path).into_owned()),
+                )
+            }
+            64 => {
+                let (input, length) = le_u16(input)?;
+                let (input, value) = take(length)(input)?;
+                // A version and flags byte comes before the file name and the object path,
+                // which are both null terminated
+                let mut strings = value
+                    .get(1..)
+                    .unwrap_or_default()
+                    .split(|b| *b == 0)
+                    .map(|s| String::from_utf8_lossy(s).into_owned());
+                let file = strings.next().unwrap_or_default();
+                let path = strings.next().unwrap_or_default();
+                (input, LinkTarget::External { file, path })
+            }
             _ => {
-                unimplemented!("unknown header message {:04X}", message_type);
+                let (input, length) = le_u16(input)?;
+                let (input, data) = take(length)(input)?;
+                let data = data.to_vec();
+                (input, LinkTarget::UserDefined { ty, data })
+            }
+        };
+        Ok((input, header::Link { name, target }))
+    })(input)
+}
+
+pub fn shared_message_table(input: &[u8]) -> Result<'_, header::SharedMessageTable> {
+    context("shared message table", |input| {
+        let (input, _) = tag([0])(input)?;
+        let (input, address) = address(8)(input)?;
+        let (input, indexes) = le_u8(input)?;
+        Ok((input, header::SharedMessageTable { address, indexes }))
+    })(input)
+}
+
+/// The master table that the shared message table message points to, with one entry for each
+/// index
+pub fn shared_message_indexes(
+    input: &[u8],
+    indexes: u8,
+) -> Result<'_, Vec<header::SharedMessageIndex>> {
+    context("shared object header message table", |input| {
+        let (input, _) = tag(b"SMTB")(input)?;
+        let (input, indexes) = count(shared_message_index, usize::from(indexes))(input)?;
+        let (input, _checksum) = le_u32(input)?;
+        Ok((input, indexes))
+    })(input)
+}
+
+fn shared_message_index(input: &[u8]) -> Result<'_, header::SharedMessageIndex> {
+    let (input, _) = tag([0])(input)?;
+    let (input, index_type) = le_u8(input)?;
+    let (input, message_types) = le_u16(input)?;
+    let (input, min_message_size) = le_u32(input)?;
+    // The number of messages at which the index changes between a list and a B-tree
+    let (input, _list_cutoff) = le_u16(input)?;
+    let (input, _btree_cutoff) = le_u16(input)?;
+    let (input, messages) = le_u16(input)?;
+    let (input, index_address) = address(8)(input)?;
+    let (input, fractal_heap_address) = address(8)(input)?;
+    Ok((
+        input,
+        header::SharedMessageIndex {
+            index_type,
+            message_types,
+            min_message_size,
+            messages,
+            index_address,
+            fractal_heap_address,
+        },
+    ))
+}
+
+/// The messages in a version 2 continuation block, without its signature and checksum
+pub fn continuation_block(input: &[u8]) -> Result<'_, &[u8]> {
+    context("continuation block", |input: &'_ [u8]| {
+        let (input, _) = tag(b"OCHK")(input)?;
+        let len = input.len().saturating_sub(4);
+        take(len)(input)
+    })(input)
+}
+
+pub fn attribute_info(input: &[u8]) -> Result<'_, header::AttributeInfo> {
+    context("attribute info", |input| {
+        let (input, _) = tag([0])(input)?;
+        let (input, flags) = le_u8(input)?;
+        let (input, max_creation_index) = if flags & 0b1 != 0 {
+            map(le_u16, Some)(input)?
+        } else {
+            (input, None)
+        };
+        let (input, fractal_heap_address) = address(8)(input)?;
+        let (input, name_btree_address) = address(8)(input)?;
+        let (input, creation_order_btree_address) = if flags & 0b10 != 0 {
+            map(address(8), Some)(input)?
+        } else {
+            (input, None)
+        };
+        Ok((
+            input,
+            header::AttributeInfo {
+                max_creation_index,
+                fractal_heap_address,
+                name_btree_address,
+                creation_order_btree_address,
+            },
+        ))
+    })(input)
+}
+
+/// Parse one message from an object header, whose version and flags determine the message prefix
+pub fn header_message<'a>(
+    input: &'a [u8],
+    object_header: &ObjectHeader,
+) -> Result<'a, header::Message> {
+    context("header message", |input| {
+        let (input, message_type, message_size, flags) = if object_header.version == 1 {
+            let (input, message_type) = le_u16(input)?;
+            let (input, message_size) = le_u16(input)?;
+            let (input, flags) = le_u8(input)?;
+            let (input, _) = tag([0, 0, 0])(input)?;
+            (input, message_type, message_size, flags)
+        } else {
+            let (input, message_type) = le_u8(input)?;
+            let (input, message_size) = le_u16(input)?;
+            let (input, flags) = le_u8(input)?;
+            let (input, _creation_order) = take(object_header.message_prefix_size() - 4)(input)?;
+            (input, u16::from(message_type), message_size, flags)
+        };
+        // The message size includes any padding, so each message body is parsed on its own and
+        // whatever the body parser leaves behind is skipped
+        let (input, body) = take(message_size)(input)?;
+        // Bit 1 of the flags marks a shared message, whose body only says where the message is
+        let (_, message) = if flags & 0b10 != 0 {
+            map(shared_message, |location| header::Message::Shared {
+                message_type,
+                location,
+            })(body)?
+        } else {
+            message(body, message_type)?
+        };
+        Ok((input, message))
+    })(input)
+}
+
+/// Parse the body of a message of the given type
+pub fn message(body: &[u8], message_type: u16) -> Result<'_, header::Message> {
+    use header::Message;
+    let message_size = body.len() as u16;
+    match message_type {
+        0x0 => Ok((body, header::Message::Nil)),
+        0x1 => map(dataspace, Message::Dataspace)(body),
+        0x2 => map(link_info, Message::LinkInfo)(body),
+        0x3 => datatype(body, message_size).map(|(i, dtype)| (i, Message::DataType(dtype))),
+        0x4 => map(fill_value_old, Message::FillValueOld)(body),
+        0x5 => map(fill_value, Message::DataStorageFillValue)(body),
+        0x6 => map(link, Message::Link)(body),
+        0x7 => map(external_data_files, Message::ExternalDataFiles)(body),
+        0x8 => map(data_layout, Message::DataLayout)(body),
+        // Only the sizes that the library should expect are stored here, so it is not parsed
+        0xA => Ok((body, Message::GroupInfo)),
+        0xB => map(filter_pipeline, Message::FilterPipeline)(body),
+        0xC => map(attribute, Message::Attribute)(body),
+        0x10 => map(
+            object_header_continuation,
+            Message::ObjectHeaderContinuation,
+        )(body),
+        0x11 => map(symbol_table_message, Message::SymbolTable)(body),
+        0x12 => map(object_modification_time, Message::ObjectModificationTime)(body),
+        0xF => map(shared_message_table, Message::SharedMessageTable)(body),
+        0x15 => map(attribute_info, Message::AttributeInfo)(body),
+        // B-tree K values, driver info, object reference counts and file space info only matter
+        // to a library that writes the file
+        0x13 | 0x14 | 0x16 | 0x17 => Ok((body, Message::Nil)),
+        _ => Ok((body, Message::Unknown(message_type))),
+    }
+}
+
+/// The mappings of a virtual dataset, from the global heap object that its layout refers to
+pub fn virtual_mappings(input: &[u8]) -> Result<'_, Vec<header::VirtualMapping>> {
+    context("virtual dataset mappings", |input| {
+        let (input, _version) = tag([0])(input)?;
+        let (mut input, entries) = le_u64(input)?;
+        let mut mappings = Vec::new();
+        for _ in 0..entries {
+            let (rest, file) = c_string(input)?;
+            let (rest, dataset) = c_string(rest)?;
+            let (rest, source) = selection(rest)?;
+            let (rest, destination) = selection(rest)?;
+            mappings.push(header::VirtualMapping {
+                file,
+                dataset,
+                source,
+                destination,
+            });
+            input = rest;
+        }
+        let (input, _checksum) = le_u32(input)?;
+        Ok((input, mappings))
+    })(input)
+}
+
+/// A string that ends at a null byte
+fn c_string(input: &[u8]) -> Result<'_, String> {
+    let length = input
+        .iter()
+        .position(|b| *b == 0)
+        .ok_or(nom::Err::Incomplete(nom::Needed::Unknown))?;
+    let string = String::from_utf8_lossy(&input[..length]).into_owned();
+    Ok((&input[length + 1..], string))
+}
+
+/// The most dimensions that a dataspace can have
+const MAX_RANK: u32 = 32;
+
+/// A selection of elements of a dataspace, in any of the versions of each kind of selection
+fn selection(input: &[u8]) -> Result<'_, header::Selection> {
+    use header::Selection;
+    context("selection", |input| {
+        let (input, kind) = le_u32(input)?;
+        let (input, version) = le_u32(input)?;
+        let rank = |input| {
+            let (input, rank) = le_u32(input)?;
+            if rank > MAX_RANK {
+                return Err(nom::Err::Failure(make_error(input, ErrorKind::TooLarge)));
+            }
+            Ok((input, rank))
+        };
+        match (kind, version) {
+            (0, 1) | (3, 1) => {
+                let (input, _) = take(4usize)(input)?;
+                let (input, _length) = le_u32(input)?;
+                let selection = if kind == 0 {
+                    Selection::None
+                } else {
+                    Selection::All
+                };
+                Ok((input, selection))
+            }
+            // Version 1 stores every number in four bytes
+            (1, 1) | (2, 1) => {
+                let (input, _) = take(4usize)(input)?;
+                let (input, _length) = le_u32(input)?;
+                let (input, rank) = rank(input)?;
+                let (input, number) = le_u32(input)?;
+                selection_elements(input, kind, rank, u64::from(number), 4)
+            }
+            // Later versions say how many bytes numbers take
+            (1, 2) => {
+                let (input, size) = le_u8(input)?;
+                let (input, rank) = rank(input)?;
+                let (input, number) = uint(usize::from(size))(input)?;
+                selection_elements(input, kind, rank, number, size)
+            }
+            (2, 2) | (2, 3) => {
+                let (input, flags) = le_u8(input)?;
+                let (input, size) = if version == 2 {
+                    let (input, _length) = le_u32(input)?;
+                    (input, 8)
+                } else {
+                    le_u8(input)?
+                };
+                let (input, rank) = rank(input)?;
+                if flags & 0b1 == 0 {
+                    let (input, number) = uint(usize::from(size))(input)?;
+                    return selection_elements(input, kind, rank, number, size);
+                }
+                let (input, values) = count(uint(usize::from(size)), rank as usize * 4)(input)?;
+                // Numbers that are stored in fewer bytes are unlimited when every bit is set
+                let values = values
+                    .into_iter()
+                    .map(|v| match size {
+                        4 if v == u64::from(u32::MAX) => header::UNLIMITED,
+                        2 if v == u64::from(u16::MAX) => header::UNLIMITED,
+                        _ => v,
+                    })
+                    .collect::<Vec<_>>();
+                let dimension = |i: usize| values.iter().skip(i).step_by(4).copied().collect();
+                Ok((
+                    input,
+                    Selection::Regular {
+                        start: dimension(0),
+                        stride: dimension(1),
+                        count: dimension(2),
+                        block: dimension(3),
+                    },
+                ))
             }
+            _ => Err(nom::Err::Failure(make_error(input, ErrorKind::Tag))),
         }
     })(input)
 }
 
-pub fn global_heap_nth_item(input: &[u8], desired_index: u16) -> Result<&[u8]> {
+/// The points or blocks of a selection, whose numbers each take `size` bytes
+fn selection_elements(
+    input: &[u8],
+    kind: u32,
+    rank: u32,
+    number: u64,
+    size: u8,
+) -> Result<'_, header::Selection> {
+    let rank = rank as usize;
+    let coordinates = if kind == 1 { rank } else { rank * 2 };
+    // Check that the elements are there before allocating for them
+    let length = usize::try_from(number)
+        .ok()
+        .and_then(|n| n.checked_mul(coordinates)?.checked_mul(usize::from(size)))
+        .ok_or_else(|| nom::Err::Failure(make_error(input, ErrorKind::TooLarge)))?;
+    if input.len() < length {
+        return Err(nom::Err::Failure(make_error(input, ErrorKind::Eof)));
+    }
+    let (input, values) = count(uint(usize::from(size)), length / usize::from(size.max(1)))(input)?;
+    let elements = values.chunks(coordinates.max(1));
+    Ok((
+        input,
+        if kind == 1 {
+            header::Selection::Points(elements.map(<[u64]>::to_vec).collect())
+        } else {
+            header::Selection::Blocks(
+                elements
+                    .map(|block| (block[..rank].to_vec(), block[rank..].to_vec()))
+                    .collect(),
+            )
+        },
+    ))
+}
+
+pub fn global_heap_nth_item(input: &[u8], desired_index: u16) -> Result<'_, &[u8]> {
     context("global heap", |input| {
         let (input, _) = tag(b"GCOL")(input)?;
         let (input, _) = tag([1])(input)?; // Only version 1 exists
         let (input, _) = tag([0, 0, 0])(input)?; // Reserved zero bytes
-        let (input, _collection_size) = address(8)(input)?;
+        let (mut input, _collection_size) = address(8)(input)?;
 
         loop {
             // Parse the heap object and check if it's what we are looking for
-            let (input, heap_object_index) = le_u16(input)?;
-            let (input, _reference_count) = le_u16(input)?;
-            let (input, _) = tag([0, 0, 0, 0])(input)?;
-            let (input, object_size) = address(8)(input)?;
-            let (input, object_data) = take(object_size)(input)?;
+            let (rest, heap_object_index) = le_u16(input)?;
+            // Object 0 is the free space at the end of the collection
+            if heap_object_index == 0 {
+                break Err(nom::Err::Failure(make_error(input, ErrorKind::Eof)));
+            }
+            let (rest, _reference_count) = le_u16(rest)?;
+            let (rest, _) = tag([0, 0, 0, 0])(rest)?;
+            let (rest, object_size) = address(8)(rest)?;
+            let (rest, object_data) = take(object_size)(rest)?;
             if heap_object_index == desired_index {
-                break Ok((input, object_data));
+                break Ok((rest, object_data));
             }
+            // Objects are padded to a multiple of eight bytes
+            let padding = (8 - object_size % 8) % 8;
+            input = take(padding)(rest)?.0;
         }
     })(input)
 }
@@ -0,0 +1,332 @@
+use crate::{Error, Limits};
+use std::convert::TryFrom;
+
+/// The coordinates that are selected along one dimension, sorted by coordinate, each paired with
+/// the position along that dimension of the output where it is placed
+pub(crate) type Axis = Vec<(u64, usize)>;
+
+/// Check that an orthogonal selection with `lens` coordinates along each dimension can be read
+/// within `limits`, before any of its coordinates are listed
+///
+/// Both the elements that are read and the coordinates that are listed for each dimension must
+/// fit in the allocation limit, since a dimension can be long even when another one is empty.
+pub(crate) fn check_size(lens: &[u64], element_size: usize, limits: &Limits) -> Result<(), Error> {
+    let bytes = |n: Option<u64>, size: usize| {
+        n.and_then(|n| n.checked_mul(size as u64))
+            .and_then(|bytes| usize::try_from(bytes).ok())
+            .unwrap_or(usize::MAX)
+    };
+    let elements = lens.iter().try_fold(1u64, |n, len| n.checked_mul(*len));
+    limits.check_allocation(bytes(elements, element_size))?;
+    let coordinates = lens.iter().try_fold(0u64, |n, len| n.checked_add(*len));
+    limits.check_allocation(bytes(coordinates, std::mem::size_of::<(u64, usize)>()))
+}
+
+/// A regular pattern of blocks to read from a dataset, in the style of `H5Sselect_hyperslab`
+///
+/// Along each dimension, `count` blocks of `block` elements are selected. The first block begins
+/// at `start`, and each following block begins `stride` elements after the one before it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hyperslab {
+    start: Vec<u64>,
+    stride: Vec<u64>,
+    count: Vec<u64>,
+    block: Vec<u64>,
+}
+
+impl Hyperslab {
+    /// Select `count` consecutive elements beginning at `start` along each dimension
+    pub fn new(start: &[u64], count: &[u64]) -> Self {
+        Self {
+            start: start.to_vec(),
+            stride: vec![1; start.len()],
+            count: count.to_vec(),
+            block: vec![1; start.len()],
+        }
+    }
+
+    /// Set the distance between the beginning of each block along each dimension
+    pub fn stride(mut self, stride: &[u64]) -> Self {
+        self.stride = stride.to_vec();
+        self
+    }
+
+    /// Set the size of each block along each dimension
+    pub fn block(mut self, block: &[u64]) -> Self {
+        self.block = block.to_vec();
+        self
+    }
+
+    /// The shape of the data that this selection produces, or an error if it is too large to
+    /// count
+    pub fn shape(&self) -> Result<Vec<u64>, Error> {
+        self.count
+            .iter()
+            .zip(&self.block)
+            .enumerate()
+            .map(|(d, (count, block))| {
+                count
+                    .checked_mul(*block)
+                    .ok_or_else(|| Error::InvalidSelection {
+                        reason: format!(
+                            "{} blocks of {} elements are too many along dimension {}",
+                            count, block, d
+                        ),
+                    })
+            })
+            .collect()
+    }
+
+    /// Check the selection against the shape of a dataset and the limits on reading elements of
+    /// `element_size` bytes, and list the selected coordinates
+    pub(crate) fn axes(
+        &self,
+        shape: &[u64],
+        element_size: usize,
+        limits: &Limits,
+    ) -> Result<Vec<Axis>, Error> {
+        for len in &[self.stride.len(), self.count.len(), self.block.len()] {
+            if *len != self.start.len() {
+                return Err(Error::InvalidSelection {
+                    reason: String::from(
+                        "start, stride, count and block must have the same number of dimensions",
+                    ),
+                });
+            }
+        }
+        if self.start.len() != shape.len() {
+            return Err(Error::RankMismatch {
+                expected: shape.len(),
+                found: self.start.len(),
+            });
+        }
+
+        for (d, size) in shape.iter().enumerate() {
+            let (start, stride, count, block) =
+                (self.start[d], self.stride[d], self.count[d], self.block[d]);
+            if count > 1 && stride < block {
+                return Err(Error::InvalidSelection {
+                    reason: format!(
+                        "blocks overlap along dimension {}: stride {} is less than block {}",
+                        d, stride, block
+                    ),
+                });
+            }
+            if count > 0 && block > 0 {
+                let last = (count - 1)
+                    .checked_mul(stride)
+                    .and_then(|last| last.checked_add(start))
+                    .and_then(|last| last.checked_add(block - 1));
+                if !matches!(last, Some(last) if last < *size) {
+                    return Err(Error::InvalidSelection {
+                        reason: format!(
+                            "selection extends past the end of dimension {}, which has size {}",
+                            d, size
+                        ),
+                    });
+                }
+            }
+        }
+
+        check_size(&self.shape()?, element_size, limits)?;
+        let mut axes = Vec::with_capacity(shape.len());
+        for d in 0..shape.len() {
+            let (start, stride, count, block) =
+                (self.start[d], self.stride[d], self.count[d], self.block[d]);
+            let mut axis = Vec::new();
+            for c in 0..count {
+                for b in 0..block {
+                    axis.push((start + c * stride + b, axis.len()));
+                }
+            }
+            axes.push(axis);
+        }
+        Ok(axes)
+    }
+}
+
+/// The indices to select along one dimension of an index selection
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Indices {
+    /// Every index along the dimension, in order
+    All,
+    /// The listed indices, in the order given, which may repeat
+    List(Vec<u64>),
+}
+
+impl Indices {
+    /// The number of indices that are selected along a dimension of size `size`
+    pub fn len(&self, size: u64) -> u64 {
+        match self {
+            Self::All => size,
+            Self::List(list) => list.len() as u64,
+        }
+    }
+
+    /// Check the selection against the shape of a dataset and the limits on reading elements of
+    /// `element_size` bytes, and list the selected coordinates
+    pub(crate) fn axes(
+        indices: &[Indices],
+        shape: &[u64],
+        element_size: usize,
+        limits: &Limits,
+    ) -> Result<Vec<Axis>, Error> {
+        if indices.len() != shape.len() {
+            return Err(Error::RankMismatch {
+                expected: shape.len(),
+                found: indices.len(),
+            });
+        }
+        let lens = indices
+            .iter()
+            .zip(shape)
+            .map(|(indices, size)| indices.len(*size))
+            .collect::<Vec<_>>();
+        check_size(&lens, element_size, limits)?;
+        let mut axes = Vec::with_capacity(shape.len());
+        for (d, (indices, size)) in indices.iter().zip(shape).enumerate() {
+            let mut axis = match indices {
+                Self::All => (0..*size).zip(0..).collect::<Axis>(),
+                Self::List(list) => list.iter().copied().zip(0..).collect(),
+            };
+            if let Some((index, _)) = axis.iter().find(|(index, _)| index >= size) {
+                return Err(Error::InvalidSelection {
+                    reason: format!(
+                        "index {} is past the end of dimension {}, which has size {}",
+                        index, d, size
+                    ),
+                });
+            }
+            axis.sort_unstable();
+            axes.push(axis);
+        }
+        Ok(axes)
+    }
+}
+
+/// Check that every point lies inside a dataset of the given shape
+pub(crate) fn check_points<P: AsRef<[u64]>>(points: &[P], shape: &[u64]) -> Result<(), Error> {
+    for point in points {
+        let point = point.as_ref();
+        if point.len() != shape.len() {
+            return Err(Error::RankMismatch {
+                expected: shape.len(),
+                found: point.len(),
+            });
+        }
+        if point.iter().zip(shape).any(|(index, size)| index >= size) {
+            return Err(Error::InvalidSelection {
+                reason: format!("point {:?} is outside of shape {:?}", point, shape),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Call `f` with every index into an array of the given shape, in row-major order
+pub(crate) fn for_each_index(
+    shape: &[usize],
+    mut f: impl FnMut(&[usize]) -> Result<(), Error>,
+) -> Result<(), Error> {
+    if shape.contains(&0) {
+        return Ok(());
+    }
+    let mut index = vec![0; shape.len()];
+    loop {
+        f(&index)?;
+        // Advance like an odometer, and stop once the first dimension rolls over
+        let mut d = shape.len();
+        loop {
+            if d == 0 {
+                return Ok(());
+            }
+            d -= 1;
+            index[d] += 1;
+            if index[d] < shape[d] {
+                break;
+            }
+            index[d] = 0;
+        }
+    }
+}
+
+/// Copy an orthogonal selection out of a row-major source into a row-major output
+///
+/// The coordinates in `axes` are relative to the source, which has `source_shape`, and their
+/// positions are within the output, which has `output_shape`. `read` is called with a byte offset
+/// into the source and must fill the provided buffer from there. Runs of elements that are
+/// consecutive in both the source and output are read with a single call.
+pub(crate) fn copy_orthogonal(
+    output: &mut [u8],
+    output_shape: &[usize],
+    axes: &[Axis],
+    source_shape: &[u64],
+    element_size: usize,
+    mut read: impl FnMut(u64, &mut [u8]) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let rank = axes.len();
+    let source_strides = strides(source_shape)?;
+    let output_strides = strides(&output_shape.iter().map(|d| *d as u64).collect::<Vec<_>>())?;
+
+    let (last, outer) = match axes.split_last() {
+        Some(split) => split,
+        // A scalar has exactly one element
+        None => return read(0, &mut output[..element_size]),
+    };
+    let mut runs: Vec<(u64, usize, usize)> = Vec::new();
+    for (coordinate, position) in last {
+        match runs.last_mut() {
+            Some((c, p, len)) if *c + *len as u64 == *coordinate && *p + *len == *position => {
+                *len += 1
+            }
+            _ => runs.push((*coordinate, *position, 1)),
+        }
+    }
+
+    let outer_lens = outer.iter().map(Vec::len).collect::<Vec<_>>();
+    // The source may be too large to address in bytes even where the output is not
+    let too_far = || Error::InvalidSelection {
+        reason: format!(
+            "the selection reaches too far into shape {:?} to address",
+            source_shape
+        ),
+    };
+    for_each_index(&outer_lens, |index| {
+        let mut source = 0u64;
+        let mut destination = 0;
+        for d in 0..rank - 1 {
+            let (coordinate, position) = outer[d][index[d]];
+            source = coordinate
+                .checked_mul(source_strides[d])
+                .and_then(|offset| offset.checked_add(source))
+                .ok_or_else(too_far)?;
+            destination += position as u64 * output_strides[d];
+        }
+        for (coordinate, position, len) in &runs {
+            let start = (destination + *position as u64) as usize * element_size;
+            let offset = source
+                .checked_add(*coordinate)
+                .and_then(|element| element.checked_mul(element_size as u64))
+                .ok_or_else(too_far)?;
+            read(offset, &mut output[start..start + len * element_size])?;
+        }
+        Ok(())
+    })
+}
+
+/// The number of elements between consecutive indices along each dimension of a row-major array
+/// of `shape`, or an error if they are too many to count
+///
+/// The size of the first dimension is never needed, so it is not multiplied in, and a dataset
+/// whose elements are too many to count can still be read near its start.
+pub(crate) fn strides(shape: &[u64]) -> Result<Vec<u64>, Error> {
+    let mut strides = vec![0; shape.len()];
+    let mut stride = Some(1u64);
+    for (d, len) in shape.iter().enumerate().rev() {
+        strides[d] = stride.ok_or_else(|| Error::InvalidSelection {
+            reason: format!("the elements of shape {:?} are too many to address", shape),
+        })?;
+        stride = strides[d].checked_mul(*len);
+    }
+    Ok(strides)
+}
@@ -0,0 +1,216 @@
+use crate::{Error, Limits};
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+/// Where the bytes of an opened file are kept
+///
+/// The parsers work on each file as one slice, so a file is either memory-mapped or held in
+/// memory. No storage reads a `Read + Seek` source on demand: such a source is buffered in memory
+/// whole. Files that the family, split or multi drivers wrote have one storage per member file.
+/// This is implemented for memory maps and the common owned byte containers, and other kinds of
+/// buffer can implement it to be opened with [`crate::OpenOptions::open_storage`].
+pub trait Storage: Send + Sync {
+    /// All the bytes of the file
+    fn contents(&self) -> &[u8];
+}
+
+impl Storage for memmap::Mmap {
+    fn contents(&self) -> &[u8] {
+        self
+    }
+}
+
+impl Storage for Vec<u8> {
+    fn contents(&self) -> &[u8] {
+        self
+    }
+}
+
+impl Storage for Box<[u8]> {
+    fn contents(&self) -> &[u8] {
+        self
+    }
+}
+
+impl Storage for Arc<[u8]> {
+    fn contents(&self) -> &[u8] {
+        self
+    }
+}
+
+impl Storage for &'static [u8] {
+    fn contents(&self) -> &[u8] {
+        self
+    }
+}
+
+impl std::fmt::Debug for dyn Storage {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "<{} bytes>", self.contents().len())
+    }
+}
+
+/// How far a structure may run past the end of a member file into the next one
+///
+/// The family driver splits the file wherever the member size falls, so a structure can start at
+/// the end of one member and continue in the next. Parsers need each structure in one slice, so
+/// the end of each member is joined to the start of the next in a buffer of this many bytes each
+/// side, which is made when a structure in it is first read.
+const JOIN_SIZE: usize = 1 << 16;
+
+/// The bytes of a file by address, which are kept in one or more member files
+#[derive(Debug)]
+pub(crate) struct Contents {
+    /// Where the superblock is, which every address in the file is relative to
+    base: u64,
+    /// The member files, in order of the addresses that they hold
+    members: Vec<Member>,
+}
+
+#[derive(Debug)]
+struct Member {
+    /// The first address that this member holds, counting from the start of the first member
+    address: u64,
+    storage: Box<dyn Storage>,
+    /// The end of this member and the start of the next, when the next member carries on from
+    /// where this one ends
+    join: OnceLock<Vec<u8>>,
+}
+
+impl Member {
+    fn new(address: u64, storage: Box<dyn Storage>) -> Self {
+        Self {
+            address,
+            storage,
+            join: OnceLock::new(),
+        }
+    }
+}
+
+impl Contents {
+    /// A file whose addresses are all in `storage`, with the superblock at `base`
+    pub(crate) fn new(storage: Box<dyn Storage>, base: u64) -> Self {
+        Self {
+            base,
+            members: vec![Member::new(0, storage)],
+        }
+    }
+
+    /// Keep the addresses from `address` up to those of the next member in `storage`
+    ///
+    /// Addresses here count from the start of the first member rather than from the superblock.
+    pub(crate) fn add_member(&mut self, address: u64, storage: Box<dyn Storage>) {
+        let index = self.members.partition_point(|m| m.address < address);
+        if self.members.get(index).map(|m| m.address) == Some(address) {
+            self.members[index] = Member::new(address, storage);
+        } else {
+            self.members.insert(index, Member::new(address, storage));
+        }
+    }
+
+    /// The bytes before the superblock
+    pub(crate) fn userblock(&self) -> &[u8] {
+        let contents = self.members[0].storage.contents();
+        &contents[..std::cmp::min(self.base as usize, contents.len())]
+    }
+
+    /// All the bytes from `address` to the end of the member file that holds it, or for
+    /// addresses near the end of a member, into the start of the next
+    pub(crate) fn bytes_from(&self, address: u64) -> Result<&[u8], Error> {
+        let out_of_bounds = || Error::OutOfBounds { address };
+        let absolute = address.checked_add(self.base).ok_or_else(out_of_bounds)?;
+        let index = self
+            .members
+            .partition_point(|m| m.address <= absolute)
+            .saturating_sub(1);
+        let member = &self.members[index];
+        let contents = member.storage.contents();
+        let offset = usize::try_from(absolute - member.address)
+            .ok()
+            .filter(|offset| *offset <= contents.len())
+            .ok_or_else(out_of_bounds)?;
+        let join_start = contents.len().saturating_sub(JOIN_SIZE);
+        match self.members.get(index + 1) {
+            Some(next)
+                if offset >= join_start
+                    && member.address.checked_add(contents.len() as u64) == Some(next.address) =>
+            {
+                let join = member.join.get_or_init(|| {
+                    let next = next.storage.contents();
+                    let mut join = contents[join_start..].to_vec();
+                    join.extend_from_slice(&next[..std::cmp::min(JOIN_SIZE, next.len())]);
+                    join
+                });
+                Ok(&join[offset - join_start..])
+            }
+            _ => Ok(&contents[offset..]),
+        }
+    }
+
+    /// The `size` bytes that start at `address`, which are only copied if they are split between
+    /// member files
+    pub(crate) fn bytes(&self, address: u64, size: u64) -> Result<Cow<'_, [u8]>, Error> {
+        let out_of_bounds = || Error::OutOfBounds { address };
+        let size = usize::try_from(size).map_err(|_| out_of_bounds())?;
+        let first = self.bytes_from(address)?;
+        if let Some(bytes) = first.get(..size) {
+            return Ok(Cow::Borrowed(bytes));
+        }
+        let mut bytes = first.to_vec();
+        while bytes.len() < size {
+            let next = address
+                .checked_add(bytes.len() as u64)
+                .and_then(|next| self.bytes_from(next).ok())
+                .filter(|next| !next.is_empty())
+                .ok_or_else(out_of_bounds)?;
+            let len = std::cmp::min(next.len(), size - bytes.len());
+            bytes.extend_from_slice(&next[..len]);
+        }
+        Ok(Cow::Owned(bytes))
+    }
+
+    /// The address of the first byte of `bytes`, if it is part of the file
+    pub(crate) fn address_of(&self, bytes: &[u8]) -> Option<u64> {
+        let position = bytes.as_ptr() as usize;
+        let offset_in = |contents: &[u8]| {
+            let offset = position.checked_sub(contents.as_ptr() as usize)?;
+            Some(offset as u64).filter(|_| offset <= contents.len())
+        };
+        self.members.iter().find_map(|member| {
+            let contents = member.storage.contents();
+            let offset = offset_in(contents).or_else(|| {
+                let join_start = contents.len().saturating_sub(JOIN_SIZE) as u64;
+                Some(join_start + offset_in(member.join.get()?)?)
+            })?;
+            member.address.checked_add(offset)?.checked_sub(self.base)
+        })
+    }
+}
+
+/// Memory-map the file at `path`
+pub(crate) fn map(path: &Path) -> Result<memmap::Mmap, Error> {
+    let file = std::fs::File::open(path)?;
+    Ok(unsafe { memmap::Mmap::map(&file)? })
+}
+
+/// Buffer everything from `reader` in memory, starting at its beginning, as long as it fits in the
+/// allocation limit
+pub(crate) fn buffer_reader<R: Read + Seek>(
+    mut reader: R,
+    limits: &Limits,
+) -> Result<Vec<u8>, Error> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(0))?;
+    let len = usize::try_from(len).unwrap_or(usize::MAX);
+    limits.check_allocation(len)?;
+    let mut contents = Vec::with_capacity(len);
+    // The source may grow while it is read, so no more than the limit is read
+    reader
+        .take((limits.max_allocation as u64).saturating_add(1))
+        .read_to_end(&mut contents)?;
+    limits.check_allocation(contents.len())?;
+    Ok(contents)
+}
@@ -0,0 +1,771 @@
+//! Virtual datasets, whose elements are taken from selections of other datasets
+
+use crate::dataset::{self, DatasetHandle};
+use crate::options::Limits;
+use crate::parse::header::{Selection, VirtualMapping, UNLIMITED};
+use crate::selection::{self, copy_orthogonal, for_each_index, Axis};
+use crate::{link, parse, Error, Hdf5Dtype, Hdf5File};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+/// Read the elements of the virtual dataset `dataset` that `axes` select into `output`, in
+/// row-major order and in the file's byte order
+///
+/// The mappings are in the global heap object `index` of the collection at `heap_address`.
+/// Elements that no mapping covers, or whose source is unavailable, are left as they are in
+/// `output`, which holds the fill value as in the reference library. A source is unavailable when
+/// its file or dataset is not found, or when the external link filter refuses its file or a link
+/// on the way to its dataset; any other error from opening it is returned. The sources of a
+/// mapping with a `%b` pattern end at the first one that is unavailable, so the blocks after it
+/// are left filled too. Virtual datasets may be mapped from other virtual datasets only as deep
+/// as the depth limit allows, which also stops any cycle of them.
+pub(crate) fn read(
+    dataset: &DatasetHandle,
+    heap_address: u64,
+    index: u32,
+    axes: &[Axis],
+    output: &mut [u8],
+) -> Result<(), Error> {
+    if output.is_empty() {
+        return Ok(());
+    }
+    let mappings = mappings(dataset, heap_address, index)?;
+    read_mapped(dataset, &mappings, axes, output)
+}
+
+/// Read the elements of the virtual dataset `dataset` at `points` into `output`, in the order
+/// that the points are listed and in the file's byte order
+///
+/// Each point is read as a selection of its own, so only the sources that it is mapped from are
+/// opened and only its element is read from them. Elements are otherwise read as [`read`] reads
+/// them.
+pub(crate) fn read_points<P: AsRef<[u64]>>(
+    dataset: &DatasetHandle,
+    heap_address: u64,
+    index: u32,
+    points: &[P],
+    output: &mut [u8],
+) -> Result<(), Error> {
+    if output.is_empty() {
+        return Ok(());
+    }
+    let mappings = mappings(dataset, heap_address, index)?;
+    let element_size = dataset.datatype().size;
+    for (point, element) in points.iter().zip(output.chunks_exact_mut(element_size)) {
+        let axes = point
+            .as_ref()
+            .iter()
+            .map(|coordinate| vec![(*coordinate, 0)])
+            .collect::<Vec<_>>();
+        read_mapped(dataset, &mappings, &axes, element)?;
+    }
+    Ok(())
+}
+
+/// The mappings of the virtual dataset `dataset`, which are in the global heap object `index` of
+/// the collection at `heap_address`
+fn mappings(
+    dataset: &DatasetHandle,
+    heap_address: u64,
+    index: u32,
+) -> Result<Vec<VirtualMapping>, Error> {
+    let file = dataset.file();
+    dataset.limits().check_virtual_depth(dataset.depth())?;
+    let index = u16::try_from(index)
+        .map_err(|_| Error::parse("virtual dataset mappings are past the end of their heap"))?;
+    let object = parse::global_heap_nth_item(file.bytes_from(heap_address)?, index)
+        .map_err(|e| Error::nom(file.contents(), e))?
+        .1;
+    Ok(parse::virtual_mappings(object)
+        .map_err(|e| Error::nom(file.contents(), e))?
+        .1)
+}
+
+/// Read the elements that `axes` select from the sources of `mappings` into `output`, which is
+/// not empty
+fn read_mapped(
+    dataset: &DatasetHandle,
+    mappings: &[VirtualMapping],
+    axes: &[Axis],
+    output: &mut [u8],
+) -> Result<(), Error> {
+    let file = dataset.file();
+    let limits = dataset.limits();
+    let element_size = dataset.datatype().size;
+    let output_shape = axes.iter().map(Vec::len).collect::<Vec<_>>();
+
+    // Where each coordinate along each dimension goes in the output
+    let positions = axes
+        .iter()
+        .map(|axis| {
+            let mut positions = HashMap::<u64, Vec<usize>>::new();
+            for (coordinate, position) in axis {
+                positions.entry(*coordinate).or_default().push(*position);
+            }
+            positions
+        })
+        .collect::<Vec<_>>();
+    let strides = selection::strides(
+        &output_shape
+            .iter()
+            .map(|len| *len as u64)
+            .collect::<Vec<_>>(),
+    )?;
+
+    for mapping in mappings {
+        let pattern = is_pattern(mapping);
+        for (source_file, source_path, destination) in sources(mapping, dataset.shape(), &positions)
+        {
+            // How much of the virtual dataset an unlimited mapping covers depends on the
+            // source, so only the others can be skipped before it is opened
+            let covered = if is_unlimited(&destination) {
+                None
+            } else {
+                Some(Elements::new(&destination, dataset.shape(), None, limits)?)
+            };
+            let meets = covered
+                .as_ref()
+                .is_none_or(|covered| covered.meets(&positions));
+            // The sources of a pattern are opened up to the ones that are read, to find where
+            // they end
+            if !meets && !pattern {
+                continue;
+            }
+            let mut opened = None;
+            let found = match source_file.as_str() {
+                "." => Ok(file),
+                name => open_source(file, name, &source_path).map(|other| &**opened.insert(other)),
+            };
+            let source = match found.and_then(|found| Ok((found, found.dataset(&source_path)?))) {
+                Ok((found, source)) => {
+                    if std::ptr::eq(found, file) && source.address() == dataset.address() {
+                        return Err(Error::parse("a virtual dataset is mapped from itself"));
+                    }
+                    source.mapped_into(dataset)
+                }
+                Err(e) if is_unavailable(&e) && pattern => break,
+                Err(e) if is_unavailable(&e) => continue,
+                Err(e) => return Err(e),
+            };
+            if !meets {
+                continue;
+            }
+            check_types(dataset, &source)?;
+
+            let selected = Elements::new(&mapping.source, source.shape(), None, limits)?;
+            let covered = match covered {
+                Some(covered) => covered,
+                None => Elements::new(&destination, dataset.shape(), Some(selected.len()), limits)?,
+            };
+            match (&covered, &selected) {
+                (Elements::Product(to), Elements::Product(from)) if aligned(to, from) => {
+                    copy_regular(&source, to, from, &positions, &output_shape, output)?
+                }
+                _ => {
+                    // A source that is smaller than the mapping, such as one that is still being
+                    // written, leaves the rest of the mapping filled
+                    let (points, offsets): (Vec<_>, Vec<_>) = covered
+                        .pairs(&positions, &strides)?
+                        .into_iter()
+                        .filter(|(rank, _)| *rank < selected.len())
+                        .map(|(rank, offset)| (selected.nth(rank), offset))
+                        .unzip();
+                    let data = source.read_points_raw(&points)?;
+                    for (element, offset) in data.chunks_exact(element_size).zip(offsets) {
+                        let start = offset * element_size;
+                        output[start..start + element_size].copy_from_slice(element);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether an error from opening a source means that it is unavailable, so that its elements
+/// are left filled rather than the read failing
+fn is_unavailable(e: &Error) -> bool {
+    matches!(e, Error::NotFound { .. } | Error::ExternalLinkDenied { .. })
+}
+
+/// Open the file called `name` that the source dataset at `path` is in, or take it from the
+/// files that `file` has already opened for its virtual datasets
+///
+/// The file is found as the file of an external link would be, and the external link filter
+/// decides whether it may be read.
+fn open_source(file: &Hdf5File, name: &str, path: &str) -> Result<Arc<Hdf5File>, Error> {
+    let found = link::find_external(file, name, path)?;
+    // A panic while the lock was held cannot leave the map half changed
+    let lock = || {
+        file.virtual_sources
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    };
+    if let Some(opened) = lock().get(&found) {
+        return Ok(Arc::clone(opened));
+    }
+    // The lock is not held while the file is opened, which may take a while
+    let opened = Arc::new(file.options.open(&found)?);
+    Ok(Arc::clone(lock().entry(found).or_insert(opened)))
+}
+
+/// Whether regular selections of a virtual dataset and a source pair their elements dimension
+/// by dimension, so that they can be read as hyperslabs
+///
+/// Elements are paired in row-major order, which keeps to dimensions when the selections have
+/// the same lengths along all but their first dimension, not counting dimensions where they
+/// select a single coordinate. The first may be shorter in the source, which then leaves the
+/// rest of the mapping filled.
+fn aligned(to: &[Pattern], from: &[Pattern]) -> bool {
+    let lengths = |patterns: &[Pattern]| {
+        patterns
+            .iter()
+            .map(Pattern::len)
+            .filter(|len| *len != 1)
+            .collect::<Vec<_>>()
+    };
+    let (to, from) = (lengths(to), lengths(from));
+    to.len() == from.len() && to.iter().skip(1).eq(from.iter().skip(1))
+}
+
+/// Copy the elements of the regular selection `from` of `source` into the output, where the
+/// regular selection `to` of the virtual dataset puts them, reading them as one hyperslab
+fn copy_regular(
+    source: &DatasetHandle,
+    to: &[Pattern],
+    from: &[Pattern],
+    positions: &[HashMap<u64, Vec<usize>>],
+    output_shape: &[usize],
+    output: &mut [u8],
+) -> Result<(), Error> {
+    let spread = |patterns: &[Pattern]| {
+        (0..patterns.len())
+            .filter(|d| patterns[*d].len() != 1)
+            .collect::<Vec<_>>()
+    };
+    let (spread_to, spread_from) = (spread(to), spread(from));
+    // Only as much of the first dimension is read as the source has
+    let first = spread_to.first().zip(spread_from.first());
+    // Along each dimension of the virtual dataset, the rank of each selected coordinate that is
+    // read and where it goes in the output
+    let mut selected = Vec::with_capacity(to.len());
+    for (d, (pattern, positions)) in to.iter().zip(positions).enumerate() {
+        let end = match first {
+            Some((to_first, from_first)) if *to_first == d => from[*from_first].len(),
+            _ => u64::MAX,
+        };
+        let mut axis = positions
+            .iter()
+            .filter_map(|(coordinate, positions)| {
+                let rank = pattern.rank_of(*coordinate).filter(|rank| *rank < end)?;
+                Some(positions.iter().map(move |position| (rank, *position)))
+            })
+            .flatten()
+            .collect::<Vec<_>>();
+        if axis.is_empty() {
+            return Ok(());
+        }
+        axis.sort_unstable();
+        selected.push(axis);
+    }
+
+    // The source is read as a block whose dimensions are the spread ones of both selections
+    let mut source_axes = from
+        .iter()
+        .map(|pattern| vec![(pattern.nth(0), 0)])
+        .collect::<Vec<_>>();
+    for (d, e) in spread_to.iter().zip(&spread_from) {
+        source_axes[*e] = selected[*d]
+            .iter()
+            .enumerate()
+            .map(|(i, (rank, _))| (from[*e].nth(*rank), i))
+            .collect();
+    }
+    let block = source.read_orthogonal(&source_axes)?;
+    let block_shape = (0..to.len())
+        .map(|d| {
+            if spread_to.contains(&d) {
+                selected[d].len() as u64
+            } else {
+                1
+            }
+        })
+        .collect::<Vec<_>>();
+    let output_axes = selected
+        .iter()
+        .zip(&block_shape)
+        .map(|(axis, len)| {
+            axis.iter()
+                .enumerate()
+                .map(|(i, (_, position))| (if *len == 1 { 0 } else { i as u64 }, *position))
+                .collect()
+        })
+        .collect::<Vec<Axis>>();
+    copy_orthogonal(
+        output,
+        output_shape,
+        &output_axes,
+        &block_shape,
+        source.datatype().size,
+        |offset, destination| dataset::copy_from(&block, offset, destination),
+    )
+}
+
+/// Whether a mapping's names contain `%b`, so that it stands for many sources
+fn is_pattern(mapping: &VirtualMapping) -> bool {
+    mapping.file.contains("%b") || mapping.dataset.contains("%b")
+}
+
+/// The sources that a mapping stands for, by their file name, dataset path and the selection
+/// of the virtual dataset that each fills
+///
+/// A mapping whose names contain `%b` stands for a source for each block of its unlimited
+/// selection of the virtual dataset, whose number takes the place of `%b`. Only the blocks that
+/// begin within `shape` and no later than the last coordinate in `positions` are produced, so
+/// there are never more of them than elements that are read.
+fn sources<'m>(
+    mapping: &'m VirtualMapping,
+    shape: &[u64],
+    positions: &[HashMap<u64, Vec<usize>>],
+) -> impl Iterator<Item = (String, String, Selection)> + 'm {
+    let unlimited = match &mapping.destination {
+        Selection::Regular {
+            start,
+            stride,
+            count,
+            block,
+        } if is_pattern(mapping) => count
+            .iter()
+            .position(|count| *count == UNLIMITED)
+            .map(|d| (d, start, stride, count, block)),
+        _ => None,
+    };
+    let sources = match unlimited {
+        None => 1,
+        Some((d, start, stride, ..)) => {
+            let end = positions
+                .get(d)
+                .and_then(|positions| positions.keys().max())
+                .map_or(0, |last| last + 1)
+                .min(shape.get(d).copied().unwrap_or(0));
+            match end.checked_sub(start[d]) {
+                None | Some(0) => 0,
+                Some(_) if stride[d] == 0 => 1,
+                Some(len) => (len - 1) / stride[d] + 1,
+            }
+        }
+    };
+    (0..sources).map(move |number| match unlimited {
+        None => (
+            substitute(&mapping.file, 0),
+            substitute(&mapping.dataset, 0),
+            mapping.destination.clone(),
+        ),
+        Some((d, start, stride, count, block)) => {
+            let mut start = start.clone();
+            let mut count = count.clone();
+            // The blocks that are produced all begin within the dataspace
+            start[d] += number * stride[d];
+            count[d] = 1;
+            (
+                substitute(&mapping.file, number),
+                substitute(&mapping.dataset, number),
+                Selection::Regular {
+                    start,
+                    stride: stride.clone(),
+                    count,
+                    block: block.clone(),
+                },
+            )
+        }
+    })
+}
+
+/// Whether `selection` has an unlimited count or block
+fn is_unlimited(selection: &Selection) -> bool {
+    match selection {
+        Selection::Regular { count, block, .. } => {
+            count.iter().chain(block).any(|n| *n == UNLIMITED)
+        }
+        _ => false,
+    }
+}
+
+/// Fill in `%b` in a source name with the number of a block, and `%%` with `%`
+fn substitute(pattern: &str, number: u64) -> String {
+    let mut name = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('%', Some('b')) => {
+                chars.next();
+                name.push_str(&number.to_string());
+            }
+            ('%', Some('%')) => {
+                chars.next();
+                name.push('%');
+            }
+            _ => name.push(c),
+        }
+    }
+    name
+}
+
+/// Check that the elements of `source` can be copied into `dataset` as they are stored
+fn check_types(dataset: &DatasetHandle, source: &DatasetHandle) -> Result<(), Error> {
+    let (to, from) = (dataset.datatype(), source.datatype());
+    // Variable-length strings refer to the heaps of the file that holds them
+    if to.class != from.class
+        || to.size != from.size
+        || to.byte_order != from.byte_order
+        || to.class == Hdf5Dtype::VlenString
+    {
+        return Err(Error::Unsupported {
+            feature: format!(
+                "virtual datasets of {:?} mapped from datasets of {:?}",
+                to.class, from.class
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// The elements of a selection in the order that they are paired with the elements of another
+/// selection, which is row-major order for everything but lists of points
+enum Elements {
+    /// The elements whose coordinate along each dimension is any that the pattern selects
+    Product(Vec<Pattern>),
+    List(Vec<Vec<u64>>),
+}
+
+/// The coordinates that a regular selection takes along one dimension: `count` runs of `block`
+/// coordinates, the first beginning at `start` and each next one `stride` after it
+#[derive(Clone, Copy)]
+struct Pattern {
+    start: u64,
+    stride: u64,
+    count: u64,
+    block: u64,
+}
+
+impl Pattern {
+    fn len(&self) -> u64 {
+        self.count.saturating_mul(self.block)
+    }
+
+    /// The rank among the selected coordinates of `coordinate`, if it is selected
+    fn rank_of(&self, coordinate: u64) -> Option<u64> {
+        let offset = coordinate.checked_sub(self.start)?;
+        if self.block == 0 {
+            return None;
+        }
+        let (run, within) = if self.count > 1 {
+            (offset / self.stride, offset % self.stride)
+        } else {
+            (0, offset)
+        };
+        if run < self.count && within < self.block {
+            Some(run * self.block + within)
+        } else {
+            None
+        }
+    }
+
+    /// The selected coordinate of rank `rank`
+    fn nth(&self, rank: u64) -> u64 {
+        self.start + rank / self.block * self.stride + rank % self.block
+    }
+}
+
+impl Elements {
+    /// The elements of `selection` of a dataspace of `shape`
+    ///
+    /// An unlimited count or block goes as far as `shape` allows, or when `fit` is given, as far
+    /// as it takes for the selection to have `fit` elements.
+    fn new(
+        selection: &Selection,
+        shape: &[u64],
+        fit: Option<u64>,
+        limits: &Limits,
+    ) -> Result<Self, Error> {
+        let rank = shape.len();
+        let wrong_rank = || Error::RankMismatch {
+            expected: rank,
+            found: match selection {
+                Selection::Points(points) => points.first().map_or(rank, Vec::len),
+                Selection::Blocks(blocks) => blocks.first().map_or(rank, |b| b.0.len()),
+                Selection::Regular { start, .. } => start.len(),
+                _ => rank,
+            },
+        };
+        let elements = match selection {
+            Selection::None => Self::List(Vec::new()),
+            Selection::All => Self::Product(
+                shape
+                    .iter()
+                    .map(|len| Pattern {
+                        start: 0,
+                        stride: 1,
+                        count: 1,
+                        block: *len,
+                    })
+                    .collect(),
+            ),
+            Selection::Points(points) => {
+                if points.iter().any(|point| point.len() != rank) {
+                    return Err(wrong_rank());
+                }
+                Self::List(points.clone())
+            }
+            Selection::Blocks(blocks) => {
+                if blocks
+                    .iter()
+                    .any(|(start, end)| start.len() != rank || end.len() != rank)
+                {
+                    return Err(wrong_rank());
+                }
+                // Blocks may not overlap, so they have no more elements than their dataspace
+                let available = shape
+                    .iter()
+                    .try_fold(1u64, |n, len| n.checked_mul(*len))
+                    .unwrap_or(u64::MAX);
+                let mut elements = Vec::new();
+                for (start, end) in blocks {
+                    if end.iter().zip(shape).any(|(end, len)| end >= len) {
+                        return Err(Error::parse("a selected block is outside its dataspace"));
+                    }
+                    let block_shape = start
+                        .iter()
+                        .zip(end)
+                        .map(|(start, end)| {
+                            let len = end.checked_sub(*start)?.checked_add(1)?;
+                            usize::try_from(len).ok()
+                        })
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(|| Error::parse("a selected block ends before it starts"))?;
+                    let size = block_shape
+                        .iter()
+                        .try_fold(1usize, |n, len| n.checked_mul(*len))
+                        .and_then(|n| n.checked_add(elements.len()))
+                        .unwrap_or(usize::MAX);
+                    if size as u64 > available {
+                        return Err(Error::parse("selected blocks overlap"));
+                    }
+                    // Each element is kept as a vector of its coordinates
+                    let element_size = std::mem::size_of::<Vec<u64>>() + rank * 8;
+                    limits.check_allocation(size.saturating_mul(element_size))?;
+                    for_each_index(&block_shape, |index| {
+                        elements.push(
+                            start
+                                .iter()
+                                .zip(index)
+                                .map(|(s, i)| s + *i as u64)
+                                .collect(),
+                        );
+                        Ok(())
+                    })?;
+                }
+                // Blocks are read in row-major order of their elements, wherever they are listed
+                elements.sort_unstable();
+                elements.dedup();
+                Self::List(elements)
+            }
+            Selection::Regular {
+                start,
+                stride,
+                count,
+                block,
+            } => {
+                if [stride.len(), count.len(), block.len(), start.len()]
+                    .iter()
+                    .any(|len| *len != rank)
+                {
+                    return Err(wrong_rank());
+                }
+                let unlimited =
+                    (0..rank).find(|d| count[*d] == UNLIMITED || block[*d] == UNLIMITED);
+                let others = (0..rank)
+                    .filter(|d| Some(*d) != unlimited)
+                    .try_fold(1u64, |n, d| n.checked_mul(count[d].checked_mul(block[d])?))
+                    .unwrap_or(u64::MAX);
+                let mut patterns = Vec::with_capacity(rank);
+                for d in 0..rank {
+                    let (start, stride) = (start[d], stride[d]);
+                    let (mut count, mut block) = (count[d], block[d]);
+                    if Some(d) == unlimited {
+                        let available = match fit {
+                            // The selection takes as many elements along this dimension as
+                            // the other selection has for each of its elements along the rest
+                            Some(fit) => fit / others.max(1),
+                            // Otherwise it ends where the dataspace does
+                            None => shape[d].saturating_sub(start),
+                        };
+                        if block == UNLIMITED {
+                            block = available;
+                            count = count.min(1);
+                        } else if fit.is_some() {
+                            count = available.checked_div(block).unwrap_or(0);
+                        } else {
+                            count = match available.checked_sub(block) {
+                                Some(rest) if stride > 0 => rest / stride + 1,
+                                Some(_) => 1,
+                                None => 0,
+                            };
+                        }
+                    }
+                    let pattern = Pattern {
+                        start,
+                        stride,
+                        count,
+                        block,
+                    };
+                    // Runs of coordinates may not overlap, and must all be within reach
+                    let last = (count > 0 && block > 0).then(|| {
+                        (count - 1)
+                            .checked_mul(stride)?
+                            .checked_add(start)?
+                            .checked_add(block - 1)
+                    });
+                    if (count > 1 && stride < block) || last == Some(None) {
+                        return Err(Error::parse(
+                            "a selection has overlapping or unreachable blocks",
+                        ));
+                    }
+                    patterns.push(pattern);
+                }
+                Self::Product(patterns)
+            }
+        };
+        // Ranks of elements are numbered with u64, and the count of them saturates
+        if elements.len() == u64::MAX {
+            return Err(Error::parse("a selection has too many elements"));
+        }
+        Ok(elements)
+    }
+
+    /// Whether any element is at coordinates that are all in `positions`
+    fn meets(&self, positions: &[HashMap<u64, Vec<usize>>]) -> bool {
+        match self {
+            Self::Product(patterns) => {
+                patterns.len() == positions.len()
+                    && patterns.iter().zip(positions).all(|(pattern, positions)| {
+                        positions
+                            .keys()
+                            .any(|coordinate| pattern.rank_of(*coordinate).is_some())
+                    })
+            }
+            Self::List(elements) => elements.iter().any(|element| {
+                element.len() == positions.len()
+                    && element
+                        .iter()
+                        .zip(positions)
+                        .all(|(coordinate, positions)| positions.contains_key(coordinate))
+            }),
+        }
+    }
+
+    /// The number of elements
+    fn len(&self) -> u64 {
+        match self {
+            Self::Product(patterns) => patterns
+                .iter()
+                .try_fold(1u64, |n, pattern| n.checked_mul(pattern.len()))
+                .unwrap_or(u64::MAX),
+            Self::List(elements) => elements.len() as u64,
+        }
+    }
+
+    /// The coordinates of the element of rank `rank`, which is less than the number of elements
+    fn nth(&self, mut rank: u64) -> Vec<u64> {
+        match self {
+            Self::Product(patterns) => {
+                let mut point = vec![0; patterns.len()];
+                for (coordinate, pattern) in point.iter_mut().zip(patterns).rev() {
+                    let len = pattern.len();
+                    *coordinate = pattern.nth(rank % len);
+                    rank /= len;
+                }
+                point
+            }
+            Self::List(elements) => elements[rank as usize].clone(),
+        }
+    }
+
+    /// The rank of each element that `positions` selects, with its offset in the output, whose
+    /// dimensions have `strides`
+    fn pairs(
+        &self,
+        positions: &[HashMap<u64, Vec<usize>>],
+        strides: &[u64],
+    ) -> Result<Vec<(u64, usize)>, Error> {
+        let mut pairs = Vec::new();
+        match self {
+            Self::Product(patterns) => {
+                if patterns.len() != positions.len() {
+                    return Ok(pairs);
+                }
+                // Along each dimension, the rank of each coordinate that is read and selected,
+                // and where it goes in the output
+                let selected = patterns
+                    .iter()
+                    .zip(positions)
+                    .zip(strides)
+                    .map(|((pattern, positions), stride)| {
+                        let mut selected = Vec::new();
+                        for (coordinate, positions) in positions {
+                            if let Some(rank) = pattern.rank_of(*coordinate) {
+                                for position in positions {
+                                    selected.push((rank, *position as u64 * stride));
+                                }
+                            }
+                        }
+                        selected
+                    })
+                    .collect::<Vec<_>>();
+                let lens = patterns.iter().map(Pattern::len).collect::<Vec<_>>();
+                let rank_strides = selection::strides(&lens)?;
+                let shape = selected.iter().map(Vec::len).collect::<Vec<_>>();
+                let _ = for_each_index(&shape, |index| {
+                    let (rank, offset) = index.iter().zip(&selected).zip(&rank_strides).fold(
+                        (0, 0),
+                        |(rank, offset), ((i, selected), stride)| {
+                            (rank + selected[*i].0 * stride, offset + selected[*i].1)
+                        },
+                    );
+                    pairs.push((rank, offset as usize));
+                    Ok(())
+                });
+            }
+            Self::List(elements) => {
+                for (rank, element) in elements.iter().enumerate() {
+                    if element.len() != positions.len() {
+                        continue;
+                    }
+                    // An element may be read into several places if the output repeats it
+                    let choices = element
+                        .iter()
+                        .zip(positions)
+                        .map(|(coordinate, positions)| positions.get(coordinate))
+                        .collect::<Option<Vec<_>>>();
+                    let choices = match choices {
+                        Some(choices) => choices,
+                        None => continue,
+                    };
+                    let shape = choices.iter().map(|c| c.len()).collect::<Vec<_>>();
+                    let _ = for_each_index(&shape, |index| {
+                        let offset = index
+                            .iter()
+                            .zip(&choices)
+                            .zip(strides)
+                            .map(|((i, positions), stride)| positions[*i] as u64 * stride)
+                            .sum::<u64>();
+                        pairs.push((rank as u64, offset as usize));
+                        Ok(())
+                    });
+                }
+            }
+        }
+        Ok(pairs)
+    }
+}
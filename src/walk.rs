@@ -0,0 +1,137 @@
+use crate::group::{GroupHandle, Member, MemberResult};
+use crate::{Error, Hdf5Dtype};
+use std::collections::HashSet;
+
+/// The kinds of object that a group can hold
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ObjectKind {
+    Group,
+    Dataset,
+    /// A datatype stored as an object of its own
+    Datatype,
+    /// A soft, external or user-defined link, which is not followed
+    Link,
+}
+
+/// An object found while walking a group, along with a summary of what it holds
+#[derive(Clone)]
+pub struct ObjectInfo<'a> {
+    path: String,
+    member: Member<'a>,
+}
+
+impl<'a> ObjectInfo<'a> {
+    /// The full path to this object, such as `/a/b/data`, which begins with the path that the
+    /// group the walk started from was first reached by
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Whether this object is a group, a dataset, a committed datatype or a link
+    pub fn kind(&self) -> ObjectKind {
+        self.member.kind()
+    }
+
+    /// The object itself
+    pub fn member(&self) -> Member<'a> {
+        self.member
+    }
+
+    /// The size of each dimension, if this object is a dataset
+    pub fn shape(&self) -> Option<&'a [u64]> {
+        match self.member {
+            Member::Dataset(dataset) => Some(dataset.shape()),
+            Member::Group(_) | Member::Datatype(_) | Member::Link(_) => None,
+        }
+    }
+
+    /// The type of each element, if this object is a dataset, or the type itself if it is a
+    /// committed datatype of a class that this library can decode
+    pub fn dtype(&self) -> Option<Hdf5Dtype> {
+        match self.member {
+            Member::Dataset(dataset) => Some(dataset.dtype()),
+            Member::Datatype(datatype) => datatype.dtype().ok(),
+            Member::Group(_) | Member::Link(_) => None,
+        }
+    }
+
+    /// The number of attributes attached to this object
+    pub fn attr_count(&self) -> Result<usize, Error> {
+        self.member.attrs().len()
+    }
+}
+
+/// An iterator over every object below a group, depth first and in order of name
+///
+/// Created by [`GroupHandle::walk`]. Each object is produced once, even if hard links make it
+/// reachable by several paths or from inside itself, in which case the first path is used. Soft
+/// and external links are produced as links and never followed. An object that cannot be read,
+/// or a group whose members cannot be listed, is produced as an error in its place, and the walk
+/// goes on with the objects after it.
+pub struct Walk<'a> {
+    // The path to each group that is being walked, and its members that are still to come
+    stack: Vec<(String, Members<'a>)>,
+    visited: HashSet<u64>,
+    // The error from listing the members of the group that was produced last
+    pending: Option<Error>,
+}
+
+type Members<'a> = std::vec::IntoIter<MemberResult<'a>>;
+
+impl<'a> Walk<'a> {
+    pub(crate) fn new(group: GroupHandle<'a>) -> Self {
+        let mut visited = HashSet::new();
+        visited.insert(group.address());
+        let mut walk = Self {
+            stack: Vec::new(),
+            visited,
+            pending: None,
+        };
+        walk.push(group.path().to_string(), group);
+        walk
+    }
+
+    fn push(&mut self, path: String, group: GroupHandle<'a>) {
+        match group.member_results() {
+            Ok(members) => self.stack.push((path, members.into_iter())),
+            Err(e) => self.pending = Some(e),
+        }
+    }
+}
+
+impl<'a> Iterator for Walk<'a> {
+    type Item = Result<ObjectInfo<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(e) = self.pending.take() {
+            return Some(Err(e));
+        }
+        loop {
+            let (prefix, members_left) = self.stack.last_mut()?;
+            let (name, member) = match members_left.next() {
+                Some(next) => next,
+                None => {
+                    self.stack.pop();
+                    continue;
+                }
+            };
+            let member = match member {
+                Ok(member) => member,
+                Err(e) => return Some(Err(e)),
+            };
+            if let Some(address) = member.address() {
+                if !self.visited.insert(address) {
+                    continue;
+                }
+            }
+            let path = match prefix.as_str() {
+                "/" => format!("/{}", name),
+                prefix => format!("{}/{}", prefix, name),
+            };
+            if let Member::Group(group) = member {
+                self.push(path.clone(), group);
+            }
+            return Some(Ok(ObjectInfo { path, member }));
+        }
+    }
+}
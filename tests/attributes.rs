@@ -1,6 +1,8 @@
 extern crate hdf5;
 
-static PROGRAM: &'static str = "\
+use hdf5::Hdf5Dtype;
+
+static PROGRAM: &str = "\
 import numpy as np
 import h5py
 with h5py.File('attributes.hdf5', 'w') as f:
@@ -8,6 +10,18 @@ with h5py.File('attributes.hdf5', 'w') as f:
     f.attrs['i64_attribute'] = np.int64(12345)
     f.attrs['f32_attribute'] = np.float32(1.2345)
     f.attrs['f64_attribute'] = np.float64(1.2345)
+    f.attrs['température'] = np.int32(21)
+    f.attrs['array_attribute'] = np.arange(6, dtype=np.int16).reshape((2, 3))
+    # Compound types cannot be decoded
+    f.attrs['pair'] = np.zeros((), dtype=[('x', '<i4'), ('y', '<f8')])
+    sb = f.create_group('LOS/SB')
+    sb.attrs['units'] = np.string_('erg/s')
+    data = sb.create_dataset('data', data=np.arange(3, dtype=np.int32))
+    data.attrs['scale'] = np.float64(0.5)
+    # Tracking creation order and having more than eight attributes moves them to dense storage
+    dense = f.create_dataset('dense', data=np.arange(3), track_order=True)
+    for i in range(20):
+        dense.attrs['attr%02d' % i] = np.int64(i)
 ";
 
 #[test]
@@ -22,8 +36,124 @@ fn can_parse_attribute() {
     let file = hdf5::read("attributes.hdf5").expect("Unable to open the file");
     println!("{:#?}", file);
 
-    assert_eq!(file.attr::<i32>("i32_attribute"), 12345);
-    assert_eq!(file.attr::<i64>("i64_attribute"), 12345);
-    assert_eq!(file.attr::<f32>("f32_attribute"), 1.2345);
-    assert_eq!(file.attr::<f64>("f64_attribute"), 1.2345);
+    assert_eq!(file.attr::<i32>("i32_attribute").unwrap(), 12345);
+    assert_eq!(file.attr::<i64>("i64_attribute").unwrap(), 12345);
+    assert_eq!(file.attr::<f32>("f32_attribute").unwrap(), 1.2345);
+    assert_eq!(file.attr::<f64>("f64_attribute").unwrap(), 1.2345);
+
+    // Array attributes are read whole, and are not mistaken for single values
+    assert_eq!(
+        file.attr_vec::<i32>("array_attribute").unwrap(),
+        [0, 1, 2, 3, 4, 5]
+    );
+    assert!(file.root().attrs().get::<i32>("array_attribute").is_err());
+    assert_eq!(file.attr_vec::<f64>("f64_attribute").unwrap(), [1.2345]);
+    let listed = file
+        .root()
+        .attrs()
+        .iter()
+        .filter_map(Result::ok)
+        .map(|(name, dtype, dataspace)| (name, dtype, dataspace.dims().to_vec()))
+        .collect::<Vec<_>>();
+    assert_eq!(listed[0], ("array_attribute", Hdf5Dtype::I16, vec![2, 3]));
+    assert_eq!(listed[1], ("f32_attribute", Hdf5Dtype::F32, vec![]));
+
+    // An attribute of a type that cannot be decoded fails only when it is read
+    assert!(matches!(
+        file.attr_vec::<i32>("pair"),
+        Err(hdf5::Error::Unsupported { .. })
+    ));
+    assert!(file.root().attrs().contains("pair").unwrap());
+    assert_eq!(file.root().attrs().iter().filter(Result::is_err).count(), 1);
+
+    // Attributes of other objects are found by their path
+    assert_eq!(file.attr::<String>("LOS/SB/units").unwrap(), "erg/s");
+    assert_eq!(file.attr::<f64>("/LOS/SB/data/scale").unwrap(), 0.5);
+    assert_eq!(file.root().attrs().len().unwrap(), 7);
+    assert_eq!(file.attr::<i32>("température").unwrap(), 21);
+    let sb = file.group("LOS/SB").unwrap();
+    assert_eq!(sb.attrs().get::<String>("units").unwrap(), "erg/s");
+    assert!(file.group("LOS").unwrap().attrs().is_empty().unwrap());
+    let data = sb.dataset("data").unwrap();
+    assert_eq!(data.attrs().get::<f64>("scale").unwrap(), 0.5);
+    assert!(data.attrs().get::<f64>("units").is_err());
+    assert_eq!(sb.member("data").unwrap().attrs().len().unwrap(), 1);
+
+    let dense = file.dataset("dense").unwrap().attrs();
+    assert_eq!(dense.len().unwrap(), 20);
+    for i in 0..20 {
+        assert_eq!(dense.get::<i64>(&format!("attr{:02}", i)).unwrap(), i);
+    }
+}
+
+static DAMAGED_PROGRAM: &str = "\
+import numpy as np
+import h5py
+with h5py.File('damaged_attributes.hdf5', 'w') as f:
+    dense = f.create_dataset('dense', data=np.arange(3), track_order=True)
+    for i in range(20):
+        dense.attrs['attr%02d' % i] = np.int64(i)
+# Break the fractal heap that holds the dense attributes, which is the only one in the file
+with open('damaged_attributes.hdf5', 'r+b') as f:
+    contents = bytearray(f.read())
+    contents[contents.index(b'FRHP')] = ord('X')
+    f.seek(0)
+    f.write(contents)
+";
+
+#[test]
+fn damaged_attributes_do_not_stop_their_object_opening() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(DAMAGED_PROGRAM)
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    let file = hdf5::read("damaged_attributes.hdf5").expect("Unable to open the file");
+    let dense = file.dataset("dense").unwrap();
+    assert_eq!(dense.read::<i64>().unwrap(), [0, 1, 2]);
+
+    // Dense attributes are only loaded when they are read, and every lookup reports the error
+    let attrs = dense.attrs();
+    assert!(attrs.len().is_err());
+    assert!(attrs.get::<i64>("attr00").is_err());
+    let listed = attrs.iter().collect::<Vec<_>>();
+    assert_eq!(listed.len(), 1);
+    assert!(listed[0].is_err());
+}
+
+static DAMAGED_TYPE_PROGRAM: &str = "\
+import numpy as np
+import h5py
+with h5py.File('damaged_attribute_type.hdf5', 'w', libver='latest') as f:
+    f['t'] = np.dtype('<i4')
+    data = f.create_dataset('data', data=np.arange(3))
+    data.attrs['plain'] = np.int32(1)
+    data.attrs.create('typed', np.int32(2), dtype=f['t'])
+# Give the committed datatype's object header, which follows the root group's, an unknown version
+with open('damaged_attribute_type.hdf5', 'r+b') as f:
+    contents = bytearray(f.read())
+    contents[contents.index(b'OHDR', contents.index(b'OHDR') + 4) + 4] = 99
+    f.seek(0)
+    f.write(contents)
+";
+
+#[test]
+fn attributes_whose_shared_type_is_damaged_fail_alone() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(DAMAGED_TYPE_PROGRAM)
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    let file = hdf5::read("damaged_attribute_type.hdf5").expect("Unable to open the file");
+    let attrs = file.dataset("data").unwrap().attrs();
+    assert_eq!(attrs.len().unwrap(), 2);
+    assert_eq!(attrs.get::<i32>("plain").unwrap(), 1);
+    assert!(attrs.get::<i32>("typed").is_err());
+    let listed = attrs.iter().collect::<Vec<_>>();
+    assert!(listed[0].is_ok());
+    assert!(listed[1].is_err());
 }
@@ -0,0 +1,211 @@
+extern crate hdf5;
+
+use hdf5::{Filter, Hdf5Dtype, Hyperslab};
+
+static PROGRAM: &str = "\
+import numpy as np
+import h5py
+with h5py.File('datasets.hdf5', 'w') as f:
+    data = np.arange(35, dtype=np.int32).reshape((5, 7))
+    f.create_dataset('contiguous', data=data)
+    f.create_dataset('chunked', data=data, chunks=(2, 3))
+    f.create_dataset('compressed', data=data, chunks=(2, 3), compression='gzip', shuffle=True)
+    f.create_dataset('checksummed', data=data, chunks=(2, 3), fletcher32=True)
+    f.create_dataset('resizable', data=data, maxshape=(None, 7))
+    f.create_dataset('big_endian', data=np.arange(4, dtype='>f8'))
+    f.create_dataset('unsigned', data=np.arange(4, dtype=np.uint16))
+    f['contiguous'].attrs['units'] = np.string_('m')
+";
+
+static LATEST_PROGRAM: &str = "\
+import numpy as np
+import h5py
+with h5py.File('chunk_indexes.hdf5', 'w', libver='latest') as f:
+    data = np.arange(35, dtype=np.int32).reshape((5, 7))
+    # A fixed array indexes the chunks of a dataset whose maximum shape is fixed
+    f.create_dataset('fixed_array', data=data, chunks=(2, 3))
+    f.create_dataset('fixed_array_filtered', data=data, chunks=(2, 3), compression='gzip')
+    # Only the pages of a large fixed array that have been written are stored
+    paged = f.create_dataset('paged', shape=(2000, 1), dtype=np.int32, chunks=(1, 1), fillvalue=-1)
+    paged[1500] = 7
+    # An extensible array indexes the chunks along one unlimited dimension, which comes first
+    f.create_dataset('extensible_array', data=data, chunks=(2, 3), maxshape=(None, 7))
+    f.create_dataset('extensible_array_last', data=data, chunks=(2, 3), maxshape=(5, None))
+    f.create_dataset('extensible_array_filtered', data=data, chunks=(2, 3), maxshape=(None, 7),
+                     compression='gzip')
+    # Enough chunks to fill super blocks whose data blocks are paged
+    long = f.create_dataset('long', shape=(0,), dtype=np.int32, chunks=(1,), maxshape=(None,))
+    long.resize((140000,))
+    long[...] = np.arange(140000, dtype=np.int32)
+    # A version 2 B-tree indexes the chunks along more than one unlimited dimension
+    f.create_dataset('btree', data=data, chunks=(2, 3), maxshape=(None, None))
+    f.create_dataset('btree_filtered', data=data, chunks=(2, 3), maxshape=(None, None),
+                     compression='gzip')
+    # A dataset with one chunk that is as large as its maximum shape
+    f.create_dataset('single', data=data, chunks=(5, 7))
+    f.create_dataset('single_filtered', data=data, chunks=(5, 7), compression='gzip')
+    # The chunks of an unfiltered dataset that are allocated when it is made are stored in order
+    dcpl = h5py.h5p.create(h5py.h5p.DATASET_CREATE)
+    dcpl.set_chunk((2, 3))
+    dcpl.set_alloc_time(h5py.h5d.ALLOC_TIME_EARLY)
+    space = h5py.h5s.create_simple((5, 7))
+    implicit = h5py.h5d.create(f.id, b'implicit', h5py.h5t.NATIVE_INT32, space, dcpl=dcpl)
+    implicit.write(h5py.h5s.ALL, h5py.h5s.ALL, data)
+";
+
+#[test]
+fn can_read_datasets() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(PROGRAM)
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    let file = hdf5::read("datasets.hdf5").expect("Unable to open the file");
+    let expected = (0..35).collect::<Vec<i32>>();
+
+    let contiguous = file.dataset("contiguous").unwrap();
+    assert_eq!(contiguous.shape(), &[5, 7]);
+    assert_eq!(contiguous.dtype(), Hdf5Dtype::I32);
+    assert_eq!(contiguous.chunk_shape(), None);
+    assert_eq!(contiguous.read::<i32>().unwrap(), expected);
+    assert_eq!(contiguous.attrs().get::<String>("units").unwrap(), "m");
+
+    for name in &["chunked", "compressed", "checksummed", "resizable"] {
+        let dataset = file.dataset(name).unwrap();
+        assert!(dataset.chunk_shape().is_some());
+        assert_eq!(dataset.read::<i32>().unwrap(), expected);
+    }
+    assert_eq!(
+        file.dataset("chunked").unwrap().chunk_shape(),
+        Some(&[2, 3][..])
+    );
+    let filters = file.dataset("compressed").unwrap().filters().to_vec();
+    let ids = filters.iter().map(Filter::id).collect::<Vec<_>>();
+    assert_eq!(ids, vec![Filter::SHUFFLE, Filter::DEFLATE]);
+    let resizable = file.dataset("resizable").unwrap();
+    assert_eq!(resizable.dataspace().max_dims(), &[None, Some(7)]);
+
+    // Lossless conversions are allowed, anything else is an error
+    assert_eq!(
+        contiguous.read::<f64>().unwrap(),
+        expected.iter().map(|i| f64::from(*i)).collect::<Vec<_>>()
+    );
+    assert!(contiguous.read::<i16>().is_err());
+
+    let big_endian = file.dataset("big_endian").unwrap();
+    assert_eq!(big_endian.read::<f64>().unwrap(), vec![0.0, 1.0, 2.0, 3.0]);
+    let unsigned = file.dataset("unsigned").unwrap();
+    assert_eq!(unsigned.read::<u64>().unwrap(), vec![0, 1, 2, 3]);
+
+    assert!(file.dataset("missing").is_err());
+}
+
+#[test]
+fn can_iterate_over_chunks() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(PROGRAM.replace("datasets.hdf5", "chunks.hdf5"))
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    let file = hdf5::read("chunks.hdf5").expect("Unable to open the file");
+    let expected = (0..35).collect::<Vec<i32>>();
+
+    for name in &["contiguous", "chunked", "compressed", "checksummed"] {
+        let dataset = file.dataset(name).unwrap();
+        let mut data = vec![-1; 35];
+        let mut count = 0;
+        for chunk in dataset.chunks().unwrap() {
+            let chunk = chunk.unwrap();
+            let values = chunk.read::<i32>().unwrap();
+            let (offset, shape, extent) = (chunk.offset(), chunk.shape(), chunk.extent());
+            for i in 0..extent[0] {
+                for j in 0..extent[1] {
+                    let value = values[(i * shape[1] + j) as usize];
+                    data[((offset[0] + i) * 7 + offset[1] + j) as usize] = value;
+                }
+            }
+            if *name == "chunked" {
+                assert!(matches!(chunk.into_data(), std::borrow::Cow::Borrowed(_)));
+            }
+            count += 1;
+        }
+        assert_eq!(data, expected);
+        assert_eq!(count, if *name == "contiguous" { 1 } else { 9 });
+    }
+}
+
+#[test]
+fn can_read_chunk_indexes_of_the_latest_format() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(LATEST_PROGRAM)
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    let file = hdf5::read("chunk_indexes.hdf5").expect("Unable to open the file");
+    let expected = (0..35).collect::<Vec<i32>>();
+
+    for name in &[
+        "fixed_array",
+        "fixed_array_filtered",
+        "extensible_array",
+        "extensible_array_last",
+        "extensible_array_filtered",
+        "btree",
+        "btree_filtered",
+        "single",
+        "single_filtered",
+        "implicit",
+    ] {
+        let dataset = file.dataset(name).unwrap();
+        assert_eq!(dataset.read::<i32>().unwrap(), expected, "{}", name);
+        let corner = Hyperslab::new(&[3, 5], &[2, 2]);
+        assert_eq!(
+            dataset.read_hyperslab::<i32>(&corner).unwrap(),
+            [26, 27, 33, 34],
+            "{}",
+            name
+        );
+        assert_eq!(
+            dataset
+                .read_points::<i32, _>(&[[4, 6], [0, 0], [2, 3]])
+                .unwrap(),
+            [34, 0, 17],
+            "{}",
+            name
+        );
+
+        let mut data = vec![-1; 35];
+        for chunk in dataset.chunks().unwrap() {
+            let chunk = chunk.unwrap();
+            let values = chunk.read::<i32>().unwrap();
+            let (offset, shape, extent) = (chunk.offset(), chunk.shape(), chunk.extent());
+            for i in 0..extent[0] {
+                for j in 0..extent[1] {
+                    let value = values[(i * shape[1] + j) as usize];
+                    data[((offset[0] + i) * 7 + offset[1] + j) as usize] = value;
+                }
+            }
+        }
+        assert_eq!(data, expected, "{}", name);
+    }
+
+    let paged = file.dataset("paged").unwrap();
+    assert_eq!(paged.chunks().unwrap().count(), 1);
+    assert_eq!(
+        paged.read_points::<i32, _>(&[[1500, 0], [3, 0]]).unwrap(),
+        [7, -1]
+    );
+
+    let long = file.dataset("long").unwrap();
+    assert_eq!(
+        long.read::<i32>().unwrap(),
+        (0..140000).collect::<Vec<i32>>()
+    );
+    assert_eq!(long.chunks().unwrap().count(), 140000);
+}
@@ -0,0 +1,47 @@
+extern crate hdf5;
+
+use hdf5::DataspaceKind;
+
+static PROGRAM: &str = "\
+import numpy as np
+import h5py
+with h5py.File('dataspace.hdf5', 'w') as f:
+    f.attrs['scalar'] = np.int32(1)
+    f.attrs['null'] = h5py.Empty('i4')
+    f.attrs['simple'] = np.arange(6, dtype=np.int32)
+    f.create_dataset('fixed', data=np.zeros((2, 3)))
+    f.create_dataset('resizable', shape=(3, 4), maxshape=(None, 4), dtype=np.float64)
+";
+
+#[test]
+fn can_parse_dataspaces() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(PROGRAM)
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    let file = hdf5::read("dataspace.hdf5").expect("Unable to open the file");
+
+    let scalar = file.attr_dataspace("scalar").unwrap();
+    assert_eq!(scalar.kind(), DataspaceKind::Scalar);
+    assert_eq!(scalar.num_elements(), 1);
+
+    let null = file.attr_dataspace("null").unwrap();
+    assert_eq!(null.kind(), DataspaceKind::Null);
+    assert_eq!(null.num_elements(), 0);
+
+    let simple = file.attr_dataspace("simple").unwrap();
+    assert_eq!(simple.kind(), DataspaceKind::Simple);
+    assert_eq!(simple.dims(), &[6]);
+
+    let fixed = file.dataspace("fixed").unwrap();
+    assert_eq!(fixed.dims(), &[2, 3]);
+    assert_eq!(fixed.max_dims(), &[Some(2), Some(3)]);
+
+    // Unlimited dimensions have no maximum
+    let resizable = file.dataspace("resizable").unwrap();
+    assert_eq!(resizable.dims(), &[3, 4]);
+    assert_eq!(resizable.max_dims(), &[None, Some(4)]);
+}
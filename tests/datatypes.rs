@@ -0,0 +1,58 @@
+extern crate hdf5;
+
+use hdf5::{Hdf5Dtype, ObjectKind};
+
+static PROGRAM: &str = "\
+import numpy as np
+import h5py
+with h5py.File('datatypes.hdf5', 'w') as f:
+    f['big'] = np.dtype('>f8')
+    f['big'].attrs['note'] = np.int32(5)
+    data = f.create_dataset('data', data=np.array([1.5, 2.5, 3.5]), dtype=f['big'])
+    data.attrs.create('scale', data=np.float64(0.5), dtype=f['big'])
+    # Compound types cannot be decoded
+    f['pair'] = np.dtype([('x', '<i4'), ('y', '<f8')])
+";
+
+#[test]
+fn can_read_committed_datatypes() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(PROGRAM)
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    let file = hdf5::read("datatypes.hdf5").expect("Unable to open the file");
+
+    let kinds = file
+        .root()
+        .members()
+        .map(Result::unwrap)
+        .map(|(name, member)| (name, member.kind()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            ("big", ObjectKind::Datatype),
+            ("data", ObjectKind::Dataset),
+            ("pair", ObjectKind::Datatype)
+        ]
+    );
+    let big = file.datatype("big").unwrap();
+    assert_eq!(big.dtype().unwrap(), Hdf5Dtype::F64);
+    assert_eq!(big.size(), 8);
+    assert_eq!(big.attrs().get::<i32>("note").unwrap(), 5);
+    assert!(file.dataset("big").is_err());
+
+    // A type that cannot be decoded is still an object, whose size is known
+    let pair = file.datatype("pair").unwrap();
+    assert_eq!(pair.size(), 12);
+    assert!(matches!(pair.dtype(), Err(hdf5::Error::Unsupported { .. })));
+
+    // The dataset and its attribute refer to the committed datatype for their type
+    let data = file.dataset("data").unwrap();
+    assert_eq!(data.dtype(), Hdf5Dtype::F64);
+    assert_eq!(data.read::<f64>().unwrap(), [1.5, 2.5, 3.5]);
+    assert_eq!(data.attrs().get::<f64>("scale").unwrap(), 0.5);
+}
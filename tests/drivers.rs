@@ -0,0 +1,93 @@
+extern crate hdf5;
+
+static PROGRAM: &str = "\
+import numpy as np
+import h5py
+for name, options in [('family_%03d.hdf5', dict(driver='family', memb_size=4096)),
+                      ('split', dict(driver='split'))]:
+    with h5py.File(name, 'w', **options) as f:
+        f.create_dataset('contiguous', data=np.arange(2000, dtype=np.int64))
+        f.create_dataset('chunked', data=np.arange(2000, dtype=np.int64), chunks=(300,))
+        f.attrs['driver'] = np.string_(options['driver'])
+";
+
+fn check(file: &hdf5::Hdf5File, driver: &str) {
+    let expected = (0..2000).collect::<Vec<i64>>();
+    assert_eq!(file.attr::<String>("driver").unwrap(), driver);
+    assert_eq!(
+        file.dataset("contiguous").unwrap().read::<i64>().unwrap(),
+        expected
+    );
+    assert_eq!(
+        file.dataset("chunked").unwrap().read::<i64>().unwrap(),
+        expected
+    );
+}
+
+#[test]
+fn can_read_files_split_between_member_files() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(PROGRAM)
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    // Family members can be found from the name pattern or from the first member
+    check(&hdf5::read("family_%03d.hdf5").unwrap(), "family");
+    check(&hdf5::read("family_000.hdf5").unwrap(), "family");
+    // The split driver keeps metadata and raw data in separate files
+    check(&hdf5::read("split-m.h5").unwrap(), "split");
+}
+
+static HOSTILE_PROGRAM: &str = "\
+import numpy as np
+import h5py
+with h5py.File('hostile', 'w', driver='split') as f:
+    f.create_dataset('contiguous', data=np.arange(2000, dtype=np.int64))
+# Make the raw data member's name absolute, keeping its length
+with open('hostile-m.h5', 'r+b') as f:
+    contents = bytearray(f.read())
+    start = contents.index(b'%s-r.h5')
+    contents[start:start + 7] = b'/%sr.h5'
+    f.seek(0)
+    f.write(contents)
+";
+
+#[test]
+fn members_outside_the_directory_of_the_file_are_refused() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(HOSTILE_PROGRAM)
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    match hdf5::read("hostile-m.h5") {
+        Err(hdf5::Error::ExternalFileDenied { file }) => assert_eq!(file, "/hostiler.h5"),
+        other => panic!(
+            "expected the member to be refused but got {:?}",
+            other.map(|_| ())
+        ),
+    }
+}
+
+static PATTERN_NAME_PROGRAM: &str = "\
+import numpy as np
+import h5py
+with h5py.File('100%done.hdf5', 'w') as f:
+    f.attrs['driver'] = np.string_('sec2')
+";
+
+#[test]
+fn files_whose_names_look_like_patterns_are_opened_by_name() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(PATTERN_NAME_PROGRAM)
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    let file = hdf5::read("100%done.hdf5").unwrap();
+    assert_eq!(file.attr::<String>("driver").unwrap(), "sec2");
+}
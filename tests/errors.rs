@@ -0,0 +1,90 @@
+extern crate hdf5;
+
+use hdf5::Error;
+
+static PROGRAM: &str = "\
+import numpy as np
+import h5py
+with h5py.File('errors.hdf5', 'w', libver='latest') as f:
+    # Chunks of a fixed-size dataset are indexed with a fixed array
+    f.create_dataset('chunked', data=np.arange(4, dtype=np.int32), chunks=(2,))
+    f.create_dataset('good', data=np.arange(2, dtype=np.int32))
+    f.create_dataset('data', data=np.arange(4, dtype=np.int32))
+# Give the last dataset's object header, which is written after the others, an unknown version
+with open('errors.hdf5', 'r+b') as f:
+    contents = bytearray(f.read())
+    contents[contents.rindex(b'OHDR') + 4] = 99
+    f.seek(0)
+    f.write(contents)
+";
+
+#[test]
+fn parse_errors_say_where_they_happened() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(PROGRAM)
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    let contents = std::fs::read("errors.hdf5").unwrap();
+    let header = contents.windows(4).rposition(|w| w == b"OHDR").unwrap() as u64;
+    // Objects are only read when they are looked up, so the rest of the file can still be read
+    let file = hdf5::read("errors.hdf5").unwrap();
+    assert_eq!(file.dataset("good").unwrap().read::<i32>().unwrap(), [0, 1]);
+    // A member that cannot be read is listed as an error in its place
+    let members = file.root().members().collect::<Vec<_>>();
+    let names = members
+        .iter()
+        .filter_map(|member| Some(member.as_ref().ok()?.0))
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["chunked", "good"]);
+    match &members[1] {
+        Err(Error::Parse { path, .. }) => assert_eq!(path.as_deref(), Some("/data")),
+        _ => panic!("expected the member that cannot be read to be an error"),
+    }
+    assert!(file.root().try_members().is_err());
+    assert_eq!(
+        file.dataset("chunked").unwrap().read::<i32>().unwrap(),
+        [0, 1, 2, 3]
+    );
+    match file.dataset("data") {
+        Err(Error::Parse {
+            offset,
+            context,
+            path,
+            ..
+        }) => {
+            let offset = offset.expect("the offset of the error is known");
+            assert!((header..header + 8).contains(&offset));
+            assert_eq!(context, ["version 2 object header"]);
+            assert_eq!(path.as_deref(), Some("/data"));
+        }
+        other => panic!("expected a parse error but got {:?}", other.map(|_| ())),
+    }
+}
+
+static SMALL_OFFSETS_PROGRAM: &str = "\
+import h5py
+fcpl = h5py.h5p.create(h5py.h5p.FILE_CREATE)
+fcpl.set_sizes(4, 4)
+h5py.h5f.create(b'small_offsets.hdf5', h5py.h5f.ACC_TRUNC, fcpl=fcpl).close()
+";
+
+#[test]
+fn offsets_that_are_not_eight_bytes_are_unsupported() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(SMALL_OFFSETS_PROGRAM)
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    match hdf5::read("small_offsets.hdf5") {
+        Err(Error::Unsupported { feature }) => assert_eq!(feature, "offsets of 4 bytes"),
+        other => panic!(
+            "expected an unsupported error but got {:?}",
+            other.map(|_| ())
+        ),
+    }
+}
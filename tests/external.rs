@@ -0,0 +1,70 @@
+extern crate hdf5;
+
+use hdf5::{Limits, OpenOptions};
+
+static PROGRAM: &str = "\
+import numpy as np
+import h5py
+with h5py.File('external.hdf5', 'w') as f:
+    f.create_dataset('data', shape=(10,), dtype=np.int32,
+                     external=[('external_a.bin', 0, 24), ('external_b.bin', 0, h5py.h5f.UNLIMITED)])
+    f['data'][:] = np.arange(10, dtype=np.int32)
+    # Names that lead out of the directory that external files are looked for in
+    f.create_dataset('absolute', shape=(2,), dtype=np.int32,
+                     external=[('/etc/hostname', 0, 8)])
+    f.create_dataset('escaping', shape=(2,), dtype=np.int32,
+                     external=[('sub/../../external_a.bin', 0, 8)])
+";
+
+#[test]
+fn can_read_data_in_external_files() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(PROGRAM)
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    let expected = (0..10).collect::<Vec<i32>>();
+    let file = hdf5::read("external.hdf5").unwrap();
+    let data = file.dataset("data").unwrap();
+    assert_eq!(data.read::<i32>().unwrap(), expected);
+    assert_eq!(data.read_points::<i32, _>(&[[9], [2]]).unwrap(), [9, 2]);
+    assert!(file.view("data").is_err());
+    for name in &["absolute", "escaping"] {
+        assert!(matches!(
+            file.dataset(name).unwrap().read::<i32>(),
+            Err(hdf5::Error::ExternalFileDenied { .. })
+        ));
+    }
+
+    // Files opened from memory find the external files under the prefix
+    let contents = std::fs::read("external.hdf5").unwrap();
+    let file = OpenOptions::new()
+        .external_file_prefix(".")
+        .open_bytes(contents)
+        .unwrap();
+    assert_eq!(
+        file.dataset("data").unwrap().read::<i32>().unwrap(),
+        expected
+    );
+    // Points are read from the external files without reading the rest of the dataset
+    let file = OpenOptions::new()
+        .limits(Limits::new().max_allocation(16))
+        .open("external.hdf5")
+        .unwrap();
+    let data = file.dataset("data").unwrap();
+    assert!(matches!(
+        data.read::<i32>(),
+        Err(hdf5::Error::LimitExceeded { .. })
+    ));
+    assert_eq!(
+        data.read_points::<i32, _>(&[[9], [2], [5], [6]]).unwrap(),
+        [9, 2, 5, 6]
+    );
+    let file = OpenOptions::new()
+        .external_file_prefix("nonexistent")
+        .open("external.hdf5")
+        .unwrap();
+    assert!(file.dataset("data").unwrap().read::<i32>().is_err());
+}
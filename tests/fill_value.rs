@@ -0,0 +1,59 @@
+extern crate hdf5;
+
+static PROGRAM: &str = "\
+import numpy as np
+import h5py
+for name, libver in [('fill_value_earliest.hdf5', 'earliest'), ('fill_value_latest.hdf5', 'latest')]:
+    with h5py.File(name, 'w', libver=libver) as f:
+        # Contiguous storage is only allocated when data is first written
+        f.create_dataset('unwritten', shape=(6,), dtype=np.int32, fillvalue=-1)
+        f.create_dataset('unwritten_default', shape=(6,), dtype=np.int32)
+        f.create_dataset('no_chunks', shape=(6,), dtype=np.float64, chunks=(2,), fillvalue=0.5)
+        partial = f.create_dataset('partial', shape=(6,), dtype=np.int32, chunks=(2,), fillvalue=-2)
+        partial[0:2] = [0, 1]
+        partial[4:6] = [4, 5]
+";
+
+#[test]
+fn can_read_unallocated_storage() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(PROGRAM)
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    // Old files write the fill value in messages of another version
+    for name in &["fill_value_earliest.hdf5", "fill_value_latest.hdf5"] {
+        let file = hdf5::read(name).unwrap();
+        let unwritten = file.dataset("unwritten").unwrap();
+        assert_eq!(unwritten.read::<i32>().unwrap(), [-1; 6]);
+        assert_eq!(
+            unwritten
+                .read_hyperslab::<i32>(&hdf5::Hyperslab::new(&[2], &[3]))
+                .unwrap(),
+            [-1; 3]
+        );
+        assert!(file.view("unwritten").is_err());
+        assert_eq!(
+            file.dataset("unwritten_default")
+                .unwrap()
+                .read::<i32>()
+                .unwrap(),
+            [0; 6]
+        );
+        assert_eq!(
+            file.dataset("no_chunks").unwrap().read::<f64>().unwrap(),
+            [0.5; 6]
+        );
+
+        // Only the chunks that were written are stored
+        let partial = file.dataset("partial").unwrap();
+        assert_eq!(partial.read::<i32>().unwrap(), [0, 1, -2, -2, 4, 5]);
+        assert_eq!(
+            partial.read_points::<i32, _>(&[[3], [4], [2]]).unwrap(),
+            [-2, 4, -2]
+        );
+        assert_eq!(partial.chunks().unwrap().count(), 2);
+    }
+}
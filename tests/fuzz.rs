@@ -0,0 +1,116 @@
+extern crate hdf5;
+
+use hdf5::{Hdf5Dtype, Member};
+
+static PROGRAM: &str = "\
+import numpy as np
+import h5py
+with h5py.File('fuzz.hdf5', 'w') as f:
+    f.attrs['title'] = 'seed'
+    f.attrs['scale'] = np.float64(1.5)
+    f.create_dataset('contiguous', data=np.arange(12, dtype=np.int32).reshape(3, 4))
+    f.create_dataset('chunked', data=np.arange(20, dtype=np.float64), chunks=(4,),
+                     compression='gzip', shuffle=True)
+    f.create_dataset('a/b/strings', data=np.array([b'one', b'two']))
+    f['a'].attrs['count'] = np.arange(3, dtype=np.uint16)
+    f['soft'] = h5py.SoftLink('/a/b')
+with h5py.File('fuzz_new.hdf5', 'w', libver='latest') as f:
+    g = f.create_group('g', track_order=True)
+    for i in range(12):
+        g.attrs['attr%d' % i] = i
+        g.create_dataset('d%d' % i, data=np.arange(i, dtype=np.int8))
+    f['t'] = np.dtype('<i4')
+";
+
+/// A xorshift generator, so that every run tries the same mutations
+struct Xorshift(u64);
+
+impl Xorshift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// Open the file at `path` and read everything in it, ignoring any errors
+fn read_everything(path: &str) {
+    let file = match hdf5::read(path) {
+        Ok(file) => file,
+        Err(_) => return,
+    };
+    for object in file.walk().flatten() {
+        let member = object.member();
+        let attrs = member.attrs();
+        for (name, dtype, _) in attrs.iter().flatten() {
+            match dtype {
+                Hdf5Dtype::String | Hdf5Dtype::VlenString => {
+                    let _ = attrs.get_vec::<String>(name);
+                }
+                _ => {
+                    let _ = attrs.get_vec::<f64>(name);
+                }
+            }
+        }
+        match member {
+            Member::Dataset(dataset) => {
+                let elements = dataset
+                    .shape()
+                    .iter()
+                    .try_fold(1u64, |n, d| n.checked_mul(*d));
+                if elements.is_some_and(|n| n < 1 << 20) {
+                    let _ = dataset.read::<f64>();
+                    let _ = dataset.read::<String>();
+                }
+                if let Ok(chunks) = dataset.chunks() {
+                    chunks
+                        .take(64)
+                        .flatten()
+                        .for_each(|chunk| drop(chunk.extent()));
+                }
+            }
+            Member::Link(link) => {
+                let _ = link.follow();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn mutated_files_do_not_panic() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(PROGRAM)
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    let mut random = Xorshift(0x2545_f491_4f6c_dd1d);
+    for seed in &["fuzz.hdf5", "fuzz_new.hdf5"] {
+        let original = std::fs::read(seed).expect("Unable to read the file");
+        read_everything(seed);
+        let mutated = format!("mutated_{}", seed);
+        for _ in 0..500 {
+            // Overwrite a few bytes with random values, flipped bits or all ones
+            let mut bytes = original.clone();
+            for _ in 0..1 + random.next() % 4 {
+                let i = (random.next() % bytes.len() as u64) as usize;
+                bytes[i] = match random.next() % 3 {
+                    0 => random.next() as u8,
+                    1 => bytes[i] ^ 1 << (random.next() % 8),
+                    _ => 0xff,
+                };
+            }
+            std::fs::write(&mutated, &bytes).expect("Unable to write the file");
+            let result = std::panic::catch_unwind(|| read_everything(&mutated));
+            assert!(
+                result.is_ok(),
+                "Reading a mutated copy of {} panicked, and it is left in {}",
+                seed,
+                mutated
+            );
+        }
+    }
+}
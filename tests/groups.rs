@@ -0,0 +1,152 @@
+extern crate hdf5;
+
+use hdf5::{Hdf5Dtype, Member, ObjectKind};
+
+static PROGRAM: &str = "\
+import numpy as np
+import h5py
+with h5py.File('groups.hdf5', 'w') as f:
+    f.create_dataset('top', data=np.arange(3, dtype=np.int32))
+    f.create_dataset('a/b/data.v1', data=np.arange(4, dtype=np.int32))
+    f.create_group('a/empty')
+    # A hard link back to an ancestor makes a cycle
+    f['a/b/up'] = f['a']
+    # Enough members that the group's B-tree needs more than one level
+    many = f.create_group('many')
+    for i in range(600):
+        many.create_dataset('d%03d' % i, data=np.int32(i))
+    # Compound types cannot be read
+    f.create_dataset('records', data=np.zeros(2, dtype=[('x', np.int32), ('y', np.float64)]))
+";
+
+#[test]
+fn can_navigate_groups() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(PROGRAM)
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    let file = hdf5::read("groups.hdf5").expect("Unable to open the file");
+    let root = file.root();
+
+    let names = root
+        .members()
+        .map(|member| member.unwrap().0)
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["a", "many", "top"]);
+    assert!(matches!(
+        root.members().last(),
+        Some(Ok((_, Member::Dataset(_))))
+    ));
+    assert_eq!(root.groups().count(), 2);
+    assert_eq!(root.datasets().count(), 1);
+
+    // Relative, absolute and `.` paths all lead to the same place
+    let b = file.group("a/b").unwrap();
+    for path in &["a/b/data.v1", "/a/b/data.v1", "./a//b/./data.v1"] {
+        assert_eq!(
+            file.dataset(path).unwrap().read::<i32>().unwrap(),
+            [0, 1, 2, 3]
+        );
+    }
+    assert_eq!(b.dataset("data.v1").unwrap().shape(), &[4]);
+    assert_eq!(b.dataset("/top").unwrap().shape(), &[3]);
+    assert_eq!(b.group("/a").unwrap().groups().count(), 2);
+
+    assert!(root.contains("a/empty"));
+    assert!(root.contains("/a/b/data.v1"));
+    assert!(!root.contains("a/b/data"));
+    assert!(!root.contains("top/data"));
+
+    // Wrong paths are errors rather than panics
+    assert!(file.dataset("a").is_err());
+    assert!(file.dataset("a/missing").is_err());
+    assert!(file.dataset("top/data").is_err());
+    assert!(file.group("top").is_err());
+
+    // Paths can go around the cycle
+    assert!(file.dataset("a/b/up/b/up/b/data.v1").is_ok());
+
+    let many = file.group("many").unwrap();
+    assert_eq!(many.datasets().count(), 600);
+    assert_eq!(many.dataset("d599").unwrap().read::<i32>().unwrap(), [599]);
+}
+
+#[test]
+fn can_walk_groups() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(PROGRAM.replace("groups.hdf5", "walk.hdf5"))
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    let file = hdf5::read("walk.hdf5").expect("Unable to open the file");
+
+    // Every object is produced once, even though the cycle leads back to `a`, and the dataset
+    // that cannot be read is produced as an error in its place
+    let mut objects = file.walk().collect::<Vec<_>>();
+    assert_eq!(objects.len(), 6 + 600 + 1);
+    assert!(matches!(
+        objects.remove(6 + 600 - 1),
+        Err(hdf5::Error::Unsupported { .. })
+    ));
+    let objects = objects.into_iter().collect::<Result<Vec<_>, _>>().unwrap();
+    let paths = objects.iter().take(6).map(|o| o.path()).collect::<Vec<_>>();
+    assert_eq!(
+        paths,
+        [
+            "/a",
+            "/a/b",
+            "/a/b/data.v1",
+            "/a/empty",
+            "/many",
+            "/many/d000"
+        ]
+    );
+    assert_eq!(objects.last().unwrap().path(), "/top");
+
+    let data = &objects[2];
+    assert_eq!(data.kind(), ObjectKind::Dataset);
+    assert_eq!(data.shape(), Some(&[4][..]));
+    assert_eq!(data.dtype(), Some(Hdf5Dtype::I32));
+    assert_eq!(data.attr_count().unwrap(), 0);
+    assert_eq!(objects[0].kind(), ObjectKind::Group);
+    assert_eq!(objects[0].shape(), None);
+
+    let mut paths = Vec::new();
+    file.group("a")
+        .unwrap()
+        .visit(|o| paths.push(o.path().to_string()))
+        .unwrap();
+    assert_eq!(paths, ["/a/b", "/a/b/data.v1", "/a/empty"]);
+    assert!(file.visit(|_| {}).is_err());
+}
+
+static UTF8_PROGRAM: &str = "\
+import numpy as np
+import h5py
+with h5py.File('utf8_names.hdf5', 'w') as f:
+    f.create_dataset('café', data=np.arange(2, dtype=np.int32))
+";
+
+#[test]
+fn names_in_symbol_tables_are_utf8() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(UTF8_PROGRAM)
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    let file = hdf5::read("utf8_names.hdf5").expect("Unable to open the file");
+    let names = file
+        .root()
+        .members()
+        .map(|member| member.unwrap().0)
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["café"]);
+    assert_eq!(file.dataset("café").unwrap().read::<i32>().unwrap(), [0, 1]);
+}
@@ -0,0 +1,40 @@
+extern crate hdf5;
+
+use hdf5::Hdf5File;
+use std::io::Cursor;
+use std::sync::Arc;
+
+static PROGRAM: &str = "\
+import numpy as np
+import h5py
+with h5py.File('in_memory.hdf5', 'w') as f:
+    f.attrs['name'] = np.string_('in memory')
+    f.create_dataset('group/data', data=np.arange(6, dtype=np.float64), chunks=(2,),
+                     compression='gzip')
+";
+
+fn check(file: &Hdf5File) {
+    assert_eq!(file.attr::<String>("name").unwrap(), "in memory");
+    assert_eq!(
+        file.dataset("group/data").unwrap().read::<f64>().unwrap(),
+        [0.0, 1.0, 2.0, 3.0, 4.0, 5.0]
+    );
+}
+
+#[test]
+fn can_read_from_memory() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(PROGRAM)
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    let bytes = std::fs::read("in_memory.hdf5").unwrap();
+    check(&Hdf5File::from_bytes(&bytes[..]).unwrap());
+    check(&Hdf5File::from_bytes(Arc::<[u8]>::from(&bytes[..])).unwrap());
+    check(&Hdf5File::from_reader_buffered(Cursor::new(&bytes)).unwrap());
+    check(&Hdf5File::from_bytes(bytes).unwrap());
+
+    assert!(Hdf5File::from_bytes(&b"not an HDF5 file"[..]).is_err());
+}
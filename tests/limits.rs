@@ -0,0 +1,96 @@
+extern crate hdf5;
+
+use hdf5::{Error, Limits, OpenOptions};
+
+static PROGRAM: &str = "\
+import numpy as np
+import h5py
+with h5py.File('limits.hdf5', 'w') as f:
+    f.create_dataset('a/b/c/contiguous', data=np.arange(100, dtype=np.int32))
+    f.create_dataset('chunked', data=np.arange(100, dtype=np.int32), chunks=(50,),
+                     compression='gzip')
+with h5py.File('limits_empty.hdf5', 'w') as f:
+    # Nothing is stored, but one dimension is very long
+    f.create_dataset('empty', shape=(0, 2**40), dtype=np.int8)
+";
+
+fn open(limits: Limits) -> Result<hdf5::Hdf5File, Error> {
+    OpenOptions::new().limits(limits).open("limits.hdf5")
+}
+
+fn limit_exceeded<T>(result: Result<T, Error>) -> &'static str {
+    match result {
+        Err(Error::LimitExceeded { limit, .. }) => limit,
+        Err(e) => panic!("expected a limit to be exceeded but got {:?}", e),
+        Ok(_) => panic!("expected a limit to be exceeded"),
+    }
+}
+
+#[test]
+fn limits_are_enforced() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(PROGRAM)
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    // The defaults allow ordinary files
+    let file = open(Limits::new()).unwrap();
+    assert_eq!(
+        file.dataset("chunked")
+            .unwrap()
+            .read::<i32>()
+            .unwrap()
+            .len(),
+        100
+    );
+
+    // Groups are nested three deep, and there are five objects besides the root group, which
+    // are counted as the groups that hold them are read
+    let file = open(Limits::new().max_depth(2)).unwrap();
+    assert!(file.group("a/b").is_ok());
+    assert_eq!(limit_exceeded(file.group("a/b/c")), "group depth");
+    let file = open(Limits::new().max_depth(3)).unwrap();
+    assert!(file.group("a/b/c").is_ok());
+    let file = open(Limits::new().max_objects(5)).unwrap();
+    assert!(file.group("a/b").is_ok());
+    let contiguous = file.dataset("a/b/c/contiguous");
+    assert_eq!(limit_exceeded(contiguous), "number of objects");
+    let file = open(Limits::new().max_objects(6)).unwrap();
+    assert!(file.dataset("a/b/c/contiguous").is_ok());
+
+    let file = open(Limits::new().max_allocation(256)).unwrap();
+    let contiguous = file.dataset("a/b/c/contiguous").unwrap();
+    assert_eq!(limit_exceeded(contiguous.read::<i32>()), "allocation size");
+    assert_eq!(
+        limit_exceeded(file.dataset("chunked").unwrap().read::<i32>()),
+        "allocation size"
+    );
+
+    // Selections are checked before their coordinates are listed
+    let file = OpenOptions::new()
+        .limits(Limits::new().max_allocation(256))
+        .open("limits_empty.hdf5")
+        .unwrap();
+    let empty = file.dataset("empty").unwrap();
+    let all = [hdf5::Indices::All, hdf5::Indices::All];
+    assert_eq!(
+        limit_exceeded(empty.read_indices::<i8>(&all)),
+        "allocation size"
+    );
+    let everything = hdf5::Hyperslab::new(&[0, 0], &[0, 1 << 40]);
+    assert_eq!(
+        limit_exceeded(empty.read_hyperslab::<i8>(&everything)),
+        "allocation size"
+    );
+
+    let file = open(Limits::new().max_chunk_size(100)).unwrap();
+    let chunked = file.dataset("chunked").unwrap();
+    assert_eq!(limit_exceeded(chunked.read::<i32>()), "chunk size");
+    assert!(file
+        .dataset("a/b/c/contiguous")
+        .unwrap()
+        .read::<i32>()
+        .is_ok());
+}
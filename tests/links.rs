@@ -0,0 +1,113 @@
+extern crate hdf5;
+
+use hdf5::{LinkKind, Member, ObjectKind, OpenOptions};
+
+static PROGRAM: &str = "\
+import numpy as np
+import h5py
+with h5py.File('links_target.hdf5', 'w') as f:
+    f.create_dataset('x', data=np.arange(2, dtype=np.int32))
+with h5py.File('links.hdf5', 'w') as f:
+    f.create_dataset('a/data', data=np.arange(3, dtype=np.int32))
+    f['absolute'] = h5py.SoftLink('/a/data')
+    f['a/relative'] = h5py.SoftLink('data')
+    f['chain'] = h5py.SoftLink('/absolute')
+    f['loop'] = h5py.SoftLink('/loop')
+    f['dangling'] = h5py.SoftLink('/missing')
+    f['external'] = h5py.ExternalLink('links_target.hdf5', '/x')
+    # Each link leads through the one before it three times, so following one means following
+    # three times as many links as the one before
+    fan = f.create_group('fan')
+    fan['L0'] = h5py.SoftLink('/fan')
+    for i in range(1, 4):
+        fan['L%d' % i] = h5py.SoftLink('/fan' + '/L%d' % (i - 1) * 3)
+";
+
+#[test]
+fn can_follow_links() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(PROGRAM)
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    let file = hdf5::read("links.hdf5").expect("Unable to open the file");
+
+    // Links are listed without being followed
+    let kinds = file
+        .root()
+        .members()
+        .map(Result::unwrap)
+        .map(|(name, member)| (name, member.kind()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            ("a", ObjectKind::Group),
+            ("absolute", ObjectKind::Link),
+            ("chain", ObjectKind::Link),
+            ("dangling", ObjectKind::Link),
+            ("external", ObjectKind::Link),
+            ("fan", ObjectKind::Group),
+            ("loop", ObjectKind::Link),
+        ]
+    );
+    let absolute = file.link("absolute").unwrap();
+    assert_eq!(absolute.kind(), LinkKind::Soft);
+    assert_eq!(absolute.target(), Some("/a/data"));
+    assert!(matches!(absolute.follow(), Ok(Member::Dataset(_))));
+    let external = file.link("external").unwrap();
+    assert_eq!(external.kind(), LinkKind::External);
+    assert_eq!(external.target_file(), Some("links_target.hdf5"));
+    assert_eq!(external.target(), Some("/x"));
+
+    // Looking up a path follows the links on it
+    for path in &["absolute", "a/relative", "chain"] {
+        assert_eq!(
+            file.dataset(path).unwrap().read::<i32>().unwrap(),
+            [0, 1, 2]
+        );
+    }
+    assert_eq!(
+        file.dataset("external").unwrap().read::<i32>().unwrap(),
+        [0, 1]
+    );
+    assert!(file.dataset("loop").is_err());
+    assert!(file.dataset("dangling").is_err());
+    assert!(!file.root().contains("dangling"));
+
+    // Every link that a lookup follows counts towards one limit, however the links nest
+    assert!(file.group("fan/L2").is_ok());
+    assert!(matches!(
+        file.group("fan/L3"),
+        Err(hdf5::Error::TooManyLinks { .. })
+    ));
+
+    // Walking produces the three objects and ten links without following the links
+    assert_eq!(file.walk().count(), 3 + 10);
+}
+
+#[test]
+fn can_forbid_external_links() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(
+            PROGRAM
+                .replace("links.hdf5", "forbidden.hdf5")
+                .replace("links_target.hdf5", "forbidden_target.hdf5"),
+        )
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    let file = OpenOptions::new()
+        .external_link_filter(|_, _| false)
+        .open("forbidden.hdf5")
+        .expect("Unable to open the file");
+    assert!(matches!(
+        file.dataset("external"),
+        Err(hdf5::Error::ExternalLinkDenied { .. })
+    ));
+    assert!(file.dataset("absolute").is_ok());
+}
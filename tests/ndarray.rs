@@ -0,0 +1,50 @@
+#![cfg(feature = "ndarray")]
+extern crate hdf5;
+
+use ndarray::{Array2, Ix1, Ix2, IxDyn};
+
+static PROGRAM: &str = "\
+import numpy as np
+import h5py
+with h5py.File('ndarray.hdf5', 'w') as f:
+    data = np.arange(12, dtype=np.float64).reshape((3, 4))
+    f.create_dataset('contiguous', data=data)
+    f.create_dataset('chunked', data=data, chunks=(2, 2), compression='gzip')
+    f.attrs['matrix'] = data
+";
+
+#[test]
+fn can_read_arrays() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(PROGRAM)
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    let file = hdf5::read("ndarray.hdf5").expect("Unable to open the file");
+    let expected = Array2::from_shape_fn((3, 4), |(i, j)| (i * 4 + j) as f64);
+
+    for name in &["contiguous", "chunked"] {
+        let dataset = file.dataset(name).unwrap();
+        assert_eq!(dataset.read_array::<f64, Ix2>().unwrap(), expected);
+        assert_eq!(
+            dataset.read_array::<f64, IxDyn>().unwrap(),
+            expected.clone().into_dyn()
+        );
+        assert!(dataset.read_array::<f64, Ix1>().is_err());
+    }
+
+    let contiguous = file.dataset("contiguous").unwrap();
+    assert_eq!(contiguous.view_array::<f64, Ix2>().unwrap(), expected);
+    assert!(contiguous.view_array::<f32, Ix2>().is_err());
+    assert!(file
+        .dataset("chunked")
+        .unwrap()
+        .view_array::<f64, Ix2>()
+        .is_err());
+
+    let attrs = file.root().attrs();
+    assert_eq!(attrs.get_array::<f64, Ix2>("matrix").unwrap(), expected);
+    assert!(attrs.get_array::<f64, Ix1>("matrix").is_err());
+}
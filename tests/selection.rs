@@ -0,0 +1,186 @@
+extern crate hdf5;
+
+use hdf5::{Hyperslab, Indices};
+
+static PROGRAM: &str = "\
+import numpy as np
+import h5py
+with h5py.File('selection.hdf5', 'w') as f:
+    data = np.arange(10 * 20 * 30, dtype=np.int64).reshape((10, 20, 30))
+    f.create_dataset('contiguous', data=data)
+    f.create_dataset('chunked', data=data, chunks=(3, 7, 11), compression='gzip')
+";
+
+fn value(i: u64, j: u64, k: u64) -> i64 {
+    (i * 20 * 30 + j * 30 + k) as i64
+}
+
+#[test]
+fn can_read_hyperslabs() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(PROGRAM)
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    let file = hdf5::read("selection.hdf5").expect("Unable to open the file");
+
+    for name in &["contiguous", "chunked"] {
+        let dataset = file.dataset(name).unwrap();
+
+        // A single time step
+        let step = Hyperslab::new(&[4, 0, 0], &[1, 20, 30]);
+        let expected = (0..20)
+            .flat_map(|j| (0..30).map(move |k| value(4, j, k)))
+            .collect::<Vec<_>>();
+        assert_eq!(dataset.read_hyperslab::<i64>(&step).unwrap(), expected);
+
+        // Strided blocks
+        let blocks = Hyperslab::new(&[1, 2, 3], &[2, 3, 2])
+            .stride(&[5, 6, 10])
+            .block(&[2, 1, 3]);
+        assert_eq!(blocks.shape().unwrap(), vec![4, 3, 6]);
+        let mut expected = Vec::new();
+        for i in &[1, 2, 6, 7] {
+            for j in &[2, 8, 14] {
+                for k in &[3, 4, 5, 13, 14, 15] {
+                    expected.push(value(*i, *j, *k));
+                }
+            }
+        }
+        assert_eq!(dataset.read_hyperslab::<i64>(&blocks).unwrap(), expected);
+
+        // Selections must fit inside the dataset and not overlap themselves
+        let too_far = Hyperslab::new(&[9, 0, 0], &[2, 1, 1]);
+        assert!(dataset.read_hyperslab::<i64>(&too_far).is_err());
+        let overlapping = Hyperslab::new(&[0, 0, 0], &[2, 1, 1])
+            .stride(&[1, 1, 1])
+            .block(&[2, 1, 1]);
+        assert!(dataset.read_hyperslab::<i64>(&overlapping).is_err());
+        let too_many = Hyperslab::new(&[0, 0, 0], &[1 << 40, 1, 1]).block(&[1 << 40, 1, 1]);
+        assert!(matches!(
+            too_many.shape(),
+            Err(hdf5::Error::InvalidSelection { .. })
+        ));
+        let wrong_rank = Hyperslab::new(&[0, 0], &[1, 1]);
+        assert!(dataset.read_hyperslab::<i64>(&wrong_rank).is_err());
+    }
+}
+
+#[test]
+fn can_read_points_and_indices() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(PROGRAM.replace("selection.hdf5", "points.hdf5"))
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    let file = hdf5::read("points.hdf5").expect("Unable to open the file");
+
+    for name in &["contiguous", "chunked"] {
+        let dataset = file.dataset(name).unwrap();
+
+        // Points come back in the order they were asked for, even when repeated
+        let points = [[9, 19, 29], [0, 0, 0], [4, 7, 11], [0, 0, 1], [9, 19, 29]];
+        let expected = points
+            .iter()
+            .map(|p| value(p[0], p[1], p[2]))
+            .collect::<Vec<_>>();
+        assert_eq!(dataset.read_points::<i64, _>(&points).unwrap(), expected);
+        assert!(dataset.read_points::<i64, _>(&[[10, 0, 0]]).is_err());
+        assert!(dataset.read_points::<i64, _>(&[[0, 0]]).is_err());
+
+        // Like ds[[3, 17, 9], :, [0, 29]] in h5py
+        let rows = [3, 17, 9];
+        let indices = [
+            Indices::List(vec![7]),
+            Indices::List(rows.to_vec()),
+            Indices::List(vec![0, 29]),
+        ];
+        let mut expected = Vec::new();
+        for j in &rows {
+            for k in &[0, 29] {
+                expected.push(value(7, *j, *k));
+            }
+        }
+        assert_eq!(dataset.read_indices::<i64>(&indices).unwrap(), expected);
+
+        let indices = [Indices::List(vec![2, 5]), Indices::All, Indices::All];
+        let expected = [2, 5]
+            .iter()
+            .flat_map(|i| (0..20).flat_map(move |j| (0..30).map(move |k| value(*i, j, k))))
+            .collect::<Vec<_>>();
+        assert_eq!(dataset.read_indices::<i64>(&indices).unwrap(), expected);
+
+        let out_of_range = [Indices::List(vec![10]), Indices::All, Indices::All];
+        assert!(dataset.read_indices::<i64>(&out_of_range).is_err());
+    }
+}
+
+static HUGE_PROGRAM: &str = "\
+import struct
+import numpy as np
+import h5py
+with h5py.File('huge.hdf5', 'w') as f:
+    # Too many elements to count in 64 bits, none of which are written
+    f.create_dataset('sparse', shape=(2**32, 2**32, 16), dtype=np.int8, chunks=(1, 1, 16), fillvalue=7)
+    f.create_dataset('contiguous', data=np.arange(16, dtype=np.int8).reshape((1, 1, 16)))
+# Give the contiguous dataset a shape that is just as large
+with open('huge.hdf5', 'r+b') as f:
+    contents = f.read()
+    small, huge = struct.pack('<3Q', 1, 1, 16), struct.pack('<3Q', 2**32, 2**32, 16)
+    assert contents.count(small) == 1
+    f.seek(0)
+    f.write(contents.replace(small, huge))
+";
+
+#[test]
+fn huge_shapes_do_not_overflow() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(HUGE_PROGRAM)
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    let file = hdf5::read("huge.hdf5").expect("Unable to open the file");
+    let last = (1 << 32) - 1;
+
+    let sparse = file.dataset("sparse").unwrap();
+    assert_eq!(
+        sparse
+            .read_points::<i8, _>(&[[last, last, 15], [0, 0, 0]])
+            .unwrap(),
+        [7, 7]
+    );
+
+    let contiguous = file.dataset("contiguous").unwrap();
+    assert_eq!(
+        contiguous
+            .read_points::<i8, _>(&[[0, 0, 3], [0, 0, 1]])
+            .unwrap(),
+        [3, 1]
+    );
+    assert!(matches!(
+        contiguous.read_points::<i8, _>(&[[last, 0, 0]]),
+        Err(hdf5::Error::InvalidSelection { .. })
+    ));
+    assert_eq!(
+        contiguous
+            .read_hyperslab::<i8>(&Hyperslab::new(&[0, 0, 2], &[1, 1, 3]))
+            .unwrap(),
+        [2, 3, 4]
+    );
+    assert!(matches!(
+        contiguous.read_hyperslab::<i8>(&Hyperslab::new(&[last, 0, 0], &[1, 1, 1])),
+        Err(hdf5::Error::InvalidSelection { .. })
+    ));
+    assert_eq!(
+        sparse
+            .read_hyperslab::<i8>(&Hyperslab::new(&[last, 0, 0], &[1, 1, 2]))
+            .unwrap(),
+        [7, 7]
+    );
+}
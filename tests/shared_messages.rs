@@ -0,0 +1,47 @@
+extern crate hdf5;
+
+static PROGRAM: &str = "\
+import ctypes
+import numpy as np
+import h5py
+# h5py does not expose shared message indexes, so they are set up through the HDF5 library that
+# it is linked against
+lib = ctypes.CDLL(h5py.h5.__file__)
+fcpl = h5py.h5p.create(h5py.h5p.FILE_CREATE)
+assert lib.H5Pset_shared_mesg_nindexes(ctypes.c_long(fcpl.id), 1) >= 0
+# Share dataspaces, datatypes, fill values, filter pipelines and attributes of any size
+flags = (1 << 1) | (1 << 3) | (1 << 5) | (1 << 11) | (1 << 12)
+assert lib.H5Pset_shared_mesg_index(ctypes.c_long(fcpl.id), 0, flags, 1) >= 0
+fid = h5py.h5f.create(b'shared_messages.hdf5', h5py.h5f.ACC_TRUNC, fcpl=fcpl)
+with h5py.File(fid) as f:
+    for name in ['a', 'b']:
+        data = f.create_dataset(name, data=np.arange(3, dtype='>f8'))
+        data.attrs['units'] = np.int32(7)
+    for i in range(20):
+        f['b'].attrs['extra%02d' % i] = np.int16(i)
+";
+
+#[test]
+fn can_read_shared_messages() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(PROGRAM)
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    let file = hdf5::read("shared_messages.hdf5").expect("Unable to open the file");
+
+    // Both datasets share one datatype, dataspace and attribute
+    for name in &["a", "b"] {
+        let data = file.dataset(name).unwrap();
+        assert_eq!(data.shape(), &[3]);
+        assert_eq!(data.read::<f64>().unwrap(), [0.0, 1.0, 2.0]);
+        assert_eq!(data.attrs().get::<i32>("units").unwrap(), 7);
+    }
+
+    // Enough attributes for dense storage, whose records refer to the shared message heap
+    let b = file.dataset("b").unwrap().attrs();
+    assert_eq!(b.len().unwrap(), 21);
+    assert_eq!(b.get::<i16>("extra19").unwrap(), 19);
+}
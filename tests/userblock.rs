@@ -0,0 +1,40 @@
+extern crate hdf5;
+
+static PROGRAM: &str = "\
+import numpy as np
+import h5py
+with h5py.File('userblock.hdf5', 'w', userblock_size=1024) as f:
+    f.create_dataset('group/data', data=np.arange(5, dtype=np.int64))
+    f.attrs['units'] = np.string_('m')
+with open('userblock.hdf5', 'r+b') as f:
+    f.write(b'MATLAB 7.3 MAT-file')
+";
+
+#[test]
+fn can_read_files_with_a_userblock() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(PROGRAM)
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    let file = hdf5::read("userblock.hdf5").unwrap();
+    let userblock = file.userblock();
+    assert_eq!(userblock.len(), 1024);
+    assert!(userblock.starts_with(b"MATLAB 7.3 MAT-file"));
+    assert_eq!(
+        file.dataset("group/data").unwrap().read::<i64>().unwrap(),
+        [0, 1, 2, 3, 4]
+    );
+    assert_eq!(file.attr::<String>("units").unwrap(), "m");
+
+    // Addresses are relative to the superblock, so the file can be read without its user block
+    let contents = std::fs::read("userblock.hdf5").unwrap();
+    let file = hdf5::Hdf5File::from_bytes(&contents[1024..]).unwrap();
+    assert!(file.userblock().is_empty());
+    assert_eq!(
+        file.dataset("group/data").unwrap().read::<i64>().unwrap(),
+        [0, 1, 2, 3, 4]
+    );
+}
@@ -0,0 +1,165 @@
+extern crate hdf5;
+
+static PROGRAM: &str = "\
+import numpy as np
+import h5py
+for i in range(3):
+    with h5py.File('vds_module_%d.hdf5' % i, 'w') as f:
+        f.create_dataset('data', data=np.arange(5, dtype=np.int32) + 10 * i)
+with h5py.File('vds.hdf5', 'w') as f:
+    # One row from each module file
+    layout = h5py.VirtualLayout(shape=(3, 5), dtype=np.int32)
+    for i in range(3):
+        layout[i] = h5py.VirtualSource('vds_module_%d.hdf5' % i, 'data', shape=(5,))
+    f.create_virtual_dataset('modules', layout, fillvalue=-1)
+    # One row from each file that matches a pattern, as many as there are
+    layout = h5py.VirtualLayout(shape=(4, 5), maxshape=(None, 5), dtype=np.int32)
+    source = h5py.VirtualSource('vds_module_%b.hdf5', 'data', shape=(5,))
+    layout[h5py.MultiBlockSlice(start=0, stride=1, count=h5py.h5s.UNLIMITED, block=1), :] = source
+    f.create_virtual_dataset('pattern', layout, fillvalue=-1)
+    # The same pattern over files where the second one is missing
+    for i in [0, 2]:
+        with h5py.File('vds_gap_%d.hdf5' % i, 'w') as g:
+            g.create_dataset('data', data=np.arange(5, dtype=np.int32) + 10 * i)
+    layout = h5py.VirtualLayout(shape=(3, 5), maxshape=(None, 5), dtype=np.int32)
+    source = h5py.VirtualSource('vds_gap_%b.hdf5', 'data', shape=(5,))
+    layout[h5py.MultiBlockSlice(start=0, stride=1, count=h5py.h5s.UNLIMITED, block=1), :] = source
+    f.create_virtual_dataset('gap', layout, fillvalue=-1)
+    # Part of a dataset in the same file, with the rest left unmapped
+    f.create_dataset('plain', data=np.arange(100, 108, dtype=np.int32))
+    layout = h5py.VirtualLayout(shape=(6,), dtype=np.int32)
+    layout[3:6] = h5py.VirtualSource('.', 'plain', shape=(8,))[0:3]
+    f.create_virtual_dataset('partial', layout, fillvalue=-1)
+    # Every other row and column of a chunked dataset, and all of it in a larger dataset
+    f.create_dataset('grid', data=np.arange(24, dtype=np.int32).reshape(4, 6), chunks=(2, 3))
+    layout = h5py.VirtualLayout(shape=(2, 3), dtype=np.int32)
+    layout[:, :] = h5py.VirtualSource('.', 'grid', shape=(4, 6))[::2, ::2]
+    f.create_virtual_dataset('strided', layout, fillvalue=-1)
+    layout = h5py.VirtualLayout(shape=(6, 6), dtype=np.int32)
+    layout[1:5, :] = h5py.VirtualSource('.', 'grid', shape=(4, 6))
+    f.create_virtual_dataset('framed', layout, fillvalue=-1)
+    # A large virtual dataset of which only the last row is mapped
+    layout = h5py.VirtualLayout(shape=(1000, 5), dtype=np.int32)
+    layout[999] = h5py.VirtualSource('vds_module_2.hdf5', 'data', shape=(5,))
+    f.create_virtual_dataset('large', layout, fillvalue=-1)
+    # Two virtual datasets that are mapped from each other
+    for name, other in [('cycle_a', 'cycle_b'), ('cycle_b', 'cycle_a')]:
+        layout = h5py.VirtualLayout(shape=(3,), dtype=np.int32)
+        layout[:] = h5py.VirtualSource('.', other, shape=(3,))
+        f.create_virtual_dataset(name, layout)
+";
+
+#[test]
+fn can_read_virtual_datasets() {
+    let status = std::process::Command::new("python3.8")
+        .arg("-c")
+        .arg(PROGRAM)
+        .status()
+        .expect("Unable to generate the file");
+    assert!(status.success());
+
+    let file = hdf5::read("vds.hdf5").unwrap();
+    let modules = (0..3)
+        .flat_map(|i| (0..5).map(move |k| 10 * i + k))
+        .collect::<Vec<i32>>();
+    let modules_dataset = file.dataset("modules").unwrap();
+    assert_eq!(modules_dataset.shape(), [3, 5]);
+    assert_eq!(modules_dataset.read::<i32>().unwrap(), modules);
+    assert_eq!(
+        modules_dataset
+            .read_hyperslab::<i32>(&hdf5::Hyperslab::new(&[1, 2], &[2, 2]))
+            .unwrap(),
+        [12, 13, 22, 23]
+    );
+    assert_eq!(
+        modules_dataset
+            .read_points::<i32, _>(&[[2, 4], [0, 1]])
+            .unwrap(),
+        [24, 1]
+    );
+
+    // There is no fourth module file, so its row is filled
+    let mut pattern = modules.clone();
+    pattern.extend([-1; 5]);
+    assert_eq!(
+        file.dataset("pattern").unwrap().read::<i32>().unwrap(),
+        pattern
+    );
+
+    // The sources of a pattern end at the first one that is missing, however they are read
+    let gap = file.dataset("gap").unwrap();
+    let mut expected = vec![-1; 15];
+    expected[..5].copy_from_slice(&modules[..5]);
+    assert_eq!(gap.read::<i32>().unwrap(), expected);
+    assert_eq!(
+        gap.read_hyperslab::<i32>(&hdf5::Hyperslab::new(&[2, 0], &[1, 2]))
+            .unwrap(),
+        [-1, -1]
+    );
+
+    assert_eq!(
+        file.dataset("partial").unwrap().read::<i32>().unwrap(),
+        [-1, -1, -1, 100, 101, 102]
+    );
+
+    assert_eq!(
+        file.dataset("strided").unwrap().read::<i32>().unwrap(),
+        [0, 2, 4, 12, 14, 16]
+    );
+    let framed = file.dataset("framed").unwrap();
+    assert_eq!(
+        framed
+            .read_hyperslab::<i32>(&hdf5::Hyperslab::new(&[3, 1], &[3, 3]))
+            .unwrap(),
+        [13, 14, 15, 19, 20, 21, -1, -1, -1]
+    );
+    assert_eq!(
+        framed.read_points::<i32, _>(&[[4, 5], [0, 0]]).unwrap(),
+        [23, -1]
+    );
+
+    assert!(file.view("modules").is_err());
+
+    // Sources that the external link filter refuses are left filled, like missing ones
+    let file = hdf5::OpenOptions::new()
+        .external_link_filter(|path, _| !path.ends_with("vds_module_1.hdf5"))
+        .open("vds.hdf5")
+        .unwrap();
+    let mut refused = modules.clone();
+    refused[5..10].copy_from_slice(&[-1; 5]);
+    assert_eq!(
+        file.dataset("modules").unwrap().read::<i32>().unwrap(),
+        refused
+    );
+    let mut refused = modules[..5].to_vec();
+    refused.extend([-1; 15]);
+    assert_eq!(
+        file.dataset("pattern").unwrap().read::<i32>().unwrap(),
+        refused
+    );
+
+    // Points are read without reading the rest of the dataset
+    let file = hdf5::OpenOptions::new()
+        .limits(hdf5::Limits::new().max_allocation(1024))
+        .open("vds.hdf5")
+        .unwrap();
+    let large = file.dataset("large").unwrap();
+    assert!(matches!(
+        large.read::<i32>(),
+        Err(hdf5::Error::LimitExceeded { .. })
+    ));
+    assert_eq!(
+        large.read_points::<i32, _>(&[[999, 3], [0, 0]]).unwrap(),
+        [23, -1]
+    );
+
+    // Reading stops once virtual datasets are mapped from others too deep
+    let file = hdf5::OpenOptions::new()
+        .limits(hdf5::Limits::new().max_depth(16))
+        .open("vds.hdf5")
+        .unwrap();
+    assert!(matches!(
+        file.dataset("cycle_a").unwrap().read::<i32>(),
+        Err(hdf5::Error::LimitExceeded { .. })
+    ));
+}